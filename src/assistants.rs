@@ -0,0 +1,643 @@
+use std::time::Duration;
+
+use reqwest::Method;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::client::{ChatGPT, Deletion, List};
+use crate::functions::{ToolCall, ToolRegistry};
+use crate::types::{Role, TokenUsage};
+
+/// A request creating an assistant
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AssistantRequest {
+    /// The model used by the assistant
+    pub model: String,
+    /// Name of the assistant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// System instructions of the assistant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// Descriptors of the tools the assistant can call, as sent to the API
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<serde_json::Value>,
+}
+
+impl AssistantRequest {
+    /// Constructs a request creating an assistant with the provided model
+    pub fn new<S: Into<String>>(model: S) -> Self {
+        Self {
+            model: model.into(),
+            name: None,
+            instructions: None,
+            tools: Vec::new(),
+        }
+    }
+
+    /// Sets the name of the assistant
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets the system instructions of the assistant
+    pub fn with_instructions<S: Into<String>>(mut self, instructions: S) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Adds the functions of the registry to the tools of the assistant
+    pub fn with_tools(mut self, registry: &ToolRegistry) -> Self {
+        self.tools.extend_from_slice(registry.descriptors());
+        self
+    }
+}
+
+/// An assistant stored by the API
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct Assistant {
+    /// Identifier of the assistant
+    pub id: String,
+    /// Unix timestamp of the creation of the assistant
+    #[serde(default)]
+    pub created_at: u64,
+    /// The model used by the assistant
+    pub model: String,
+    /// Name of the assistant
+    #[serde(default)]
+    pub name: Option<String>,
+    /// System instructions of the assistant
+    #[serde(default)]
+    pub instructions: Option<String>,
+}
+
+/// A thread of messages stored by the API
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct Thread {
+    /// Identifier of the thread
+    pub id: String,
+    /// Unix timestamp of the creation of the thread
+    #[serde(default)]
+    pub created_at: u64,
+}
+
+/// A message of a thread
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct ThreadMessage {
+    /// Identifier of the message
+    pub id: String,
+    /// Unix timestamp of the creation of the message
+    #[serde(default)]
+    pub created_at: u64,
+    /// Identifier of the thread containing the message
+    pub thread_id: String,
+    /// The author of the message, either the user or the assistant
+    pub role: Role,
+    /// Parts of the message
+    #[serde(default)]
+    pub content: Vec<MessageContent>,
+    /// Identifier of the assistant that wrote the message
+    #[serde(default)]
+    pub assistant_id: Option<String>,
+    /// Identifier of the run that produced the message
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+impl ThreadMessage {
+    /// Concatenated text parts of the message
+    pub fn text(&self) -> String {
+        self.content
+            .iter()
+            .filter_map(|content| match content {
+                MessageContent::Text { text } => Some(text.value.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// A part of a thread message
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MessageContent {
+    /// Text of the message
+    Text {
+        /// The text
+        text: MessageText,
+    },
+    /// An image uploaded with the files API
+    ImageFile {
+        /// The image
+        image_file: ImageFile,
+    },
+    /// A part not supported by the crate yet
+    #[serde(other)]
+    Other,
+}
+
+/// Text of a thread message
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct MessageText {
+    /// The text
+    pub value: String,
+}
+
+/// An image of a thread message
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct ImageFile {
+    /// Id of the uploaded image
+    pub file_id: String,
+}
+
+/// A run of an assistant on a thread
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct Run {
+    /// Identifier of the run
+    pub id: String,
+    /// Unix timestamp of the creation of the run
+    #[serde(default)]
+    pub created_at: u64,
+    /// Identifier of the thread the run is executed on
+    pub thread_id: String,
+    /// Identifier of the assistant executing the run
+    pub assistant_id: String,
+    /// Current status of the run
+    pub status: RunStatus,
+    /// Action required to continue the run, if its status is [`RunStatus::RequiresAction`]
+    #[serde(default)]
+    pub required_action: Option<RequiredAction>,
+    /// The reason of the failure, if the run failed
+    #[serde(default)]
+    pub last_error: Option<RunError>,
+    /// Tokens used by the run, once it is finished
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+}
+
+impl Run {
+    /// Tool calls that need to be answered with [`ChatGPT::submit_tool_outputs()`] to continue the run
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        self.required_action
+            .as_ref()
+            .and_then(|action| action.submit_tool_outputs.as_ref())
+            .map_or(&[], |outputs| &outputs.tool_calls)
+    }
+}
+
+/// The status of a run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    /// The run is waiting to be started
+    Queued,
+    /// The assistant is working on the run
+    InProgress,
+    /// The run waits for the outputs of tool calls
+    RequiresAction,
+    /// The run is being cancelled
+    Cancelling,
+    /// The run was cancelled
+    Cancelled,
+    /// The run failed, see [`Run::last_error`]
+    Failed,
+    /// The run completed successfully
+    Completed,
+    /// The run ended before completing, e.g. because of the token limit
+    Incomplete,
+    /// The tool outputs were not submitted in time
+    Expired,
+}
+
+impl RunStatus {
+    /// Returns `true` if the run is still being processed by the API
+    pub fn is_pending(self) -> bool {
+        matches!(self, Self::Queued | Self::InProgress | Self::Cancelling)
+    }
+
+    /// The name of the status, as returned by the API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::InProgress => "in_progress",
+            Self::RequiresAction => "requires_action",
+            Self::Cancelling => "cancelling",
+            Self::Cancelled => "cancelled",
+            Self::Failed => "failed",
+            Self::Completed => "completed",
+            Self::Incomplete => "incomplete",
+            Self::Expired => "expired",
+        }
+    }
+}
+
+/// An action required to continue a run
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct RequiredAction {
+    /// Type of the action, currently always `submit_tool_outputs`
+    #[serde(rename = "type")]
+    pub action_type: String,
+    /// The tool calls to answer
+    #[serde(default)]
+    pub submit_tool_outputs: Option<SubmitToolOutputs>,
+}
+
+/// Tool calls waiting for their outputs
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct SubmitToolOutputs {
+    /// The tool calls
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// The reason a run failed
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct RunError {
+    /// Machine-readable code of the error
+    #[serde(default)]
+    pub code: String,
+    /// Description of the error
+    #[serde(default)]
+    pub message: String,
+}
+
+/// The output of a tool call of a run
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize)]
+pub struct ToolOutput {
+    /// Identifier of the answered tool call
+    pub tool_call_id: String,
+    /// The output of the tool
+    pub output: String,
+}
+
+impl ChatGPT {
+    /// Creates an assistant.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn create_assistant(&self, request: &AssistantRequest) -> crate::Result<Assistant> {
+        self.beta_post(self.resource_url("assistants", &[])?, request)
+            .await
+    }
+
+    /// Deletes the assistant with the provided id. Returns `true` if it was deleted.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn delete_assistant(&self, id: &str) -> crate::Result<bool> {
+        let url = self.resource_url("assistants", &[id])?;
+        let deletion: Deletion = self.beta_fetch(Method::DELETE, url).await?;
+        Ok(deletion.deleted)
+    }
+
+    /// Creates an empty thread.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn create_thread(&self) -> crate::Result<Thread> {
+        let body = serde_json::json!({});
+        self.beta_post(self.resource_url("threads", &[])?, &body)
+            .await
+    }
+
+    /// Deletes the thread with the provided id. Returns `true` if it was deleted.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn delete_thread(&self, id: &str) -> crate::Result<bool> {
+        let url = self.resource_url("threads", &[id])?;
+        let deletion: Deletion = self.beta_fetch(Method::DELETE, url).await?;
+        Ok(deletion.deleted)
+    }
+
+    /// Appends a user message to the thread.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn add_thread_message<S: Into<String>>(
+        &self,
+        thread_id: &str,
+        content: S,
+    ) -> crate::Result<ThreadMessage> {
+        let body = serde_json::json!({ "role": "user", "content": content.into() });
+        let url = self.resource_url("threads", &[thread_id, "messages"])?;
+        self.beta_post(url, &body).await
+    }
+
+    /// Lists the messages of the thread, the most recent first.
+    ///
+    /// Messages preceding the message with the id `after` are listed if it is set, up to `limit` messages.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn list_thread_messages(
+        &self,
+        thread_id: &str,
+        after: Option<&str>,
+        limit: Option<u32>,
+    ) -> crate::Result<Vec<ThreadMessage>> {
+        let url = self.page_url("threads", &[thread_id, "messages"], after, limit)?;
+        let list: List<ThreadMessage> = self.beta_fetch(Method::GET, url).await?;
+        Ok(list.data)
+    }
+
+    /// Starts a run of the assistant on the thread.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn create_run(&self, thread_id: &str, assistant_id: &str) -> crate::Result<Run> {
+        let body = serde_json::json!({ "assistant_id": assistant_id });
+        let url = self.resource_url("threads", &[thread_id, "runs"])?;
+        self.beta_post(url, &body).await
+    }
+
+    /// Retrieves the run with the provided id.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn retrieve_run(&self, thread_id: &str, run_id: &str) -> crate::Result<Run> {
+        let url = self.resource_url("threads", &[thread_id, "runs", run_id])?;
+        self.beta_fetch(Method::GET, url).await
+    }
+
+    /// Cancels the run with the provided id.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn cancel_run(&self, thread_id: &str, run_id: &str) -> crate::Result<Run> {
+        let url = self.resource_url("threads", &[thread_id, "runs", run_id, "cancel"])?;
+        self.beta_fetch(Method::POST, url).await
+    }
+
+    /// Answers the tool calls of a run waiting for them, continuing the run.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn submit_tool_outputs(
+        &self,
+        thread_id: &str,
+        run_id: &str,
+        outputs: &[ToolOutput],
+    ) -> crate::Result<Run> {
+        let body = serde_json::json!({ "tool_outputs": outputs });
+        let url = self.resource_url(
+            "threads",
+            &[thread_id, "runs", run_id, "submit_tool_outputs"],
+        )?;
+        self.beta_post(url, &body).await
+    }
+
+    /// Polls the run every `poll_interval` until it is no longer processed by the API, e.g. until it is completed
+    /// or waits for tool outputs.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn wait_for_run(
+        &self,
+        thread_id: &str,
+        run_id: &str,
+        poll_interval: Duration,
+    ) -> crate::Result<Run> {
+        loop {
+            let run = self.retrieve_run(thread_id, run_id).await?;
+            if !run.status.is_pending() {
+                return Ok(run);
+            }
+            crate::client::sleep(poll_interval).await;
+        }
+    }
+
+    /// Creates a new thread, that is answered by the assistant with the provided id.
+    ///
+    /// Requires the `assistants` crate feature
+    pub async fn new_assistant_thread<S: Into<String>>(
+        &self,
+        assistant_id: S,
+    ) -> crate::Result<AssistantThread> {
+        let thread = self.create_thread().await?;
+        Ok(self.restore_assistant_thread(assistant_id, thread.id))
+    }
+
+    /// Continues an existing thread, that is answered by the assistant with the provided id.
+    ///
+    /// Requires the `assistants` crate feature
+    pub fn restore_assistant_thread<A: Into<String>, T: Into<String>>(
+        &self,
+        assistant_id: A,
+        thread_id: T,
+    ) -> AssistantThread {
+        AssistantThread {
+            client: self.clone(),
+            id: thread_id.into(),
+            assistant_id: assistant_id.into(),
+            poll_interval: Duration::from_secs(1),
+            max_tool_iterations: 8,
+        }
+    }
+
+    async fn beta_fetch<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: url::Url,
+    ) -> crate::Result<T> {
+        self.send_beta_request(method, url, None::<&()>).await
+    }
+
+    async fn beta_post<B, T>(&self, url: url::Url, body: &B) -> crate::Result<T>
+    where
+        B: Serialize + Sync + ?Sized,
+        T: DeserializeOwned,
+    {
+        self.send_beta_request(Method::POST, url, Some(body)).await
+    }
+}
+
+/// A conversation backed by a thread stored by the API, answered by an assistant.
+///
+/// Unlike [`Conversation`](crate::converse::Conversation), the message history is kept by the API
+#[derive(Debug, Clone)]
+pub struct AssistantThread {
+    client: ChatGPT,
+    /// Identifier of the thread
+    pub id: String,
+    /// Identifier of the assistant answering the messages
+    pub assistant_id: String,
+    /// Interval between the polls of a run
+    pub poll_interval: Duration,
+    /// Maximum amount of rounds of tool calls processed by [`Self::send_message_with_tools()`] before giving up.
+    pub max_tool_iterations: u32,
+}
+
+impl AssistantThread {
+    /// Sends the message to the thread, and waits for the reply of the assistant.
+    ///
+    /// Fails with [`Error::RunFailed`](crate::err::Error::RunFailed) if the run does not complete, e.g. if the
+    /// assistant calls a tool. The run is cancelled in that case, so that the thread is not locked
+    pub async fn send_message<S: Into<String>>(&self, message: S) -> crate::Result<ThreadMessage> {
+        let run = self.start_run(message).await?;
+        if run.status == RunStatus::RequiresAction {
+            self.client.cancel_run(&self.id, &run.id).await?;
+        }
+        self.reply(run).await
+    }
+
+    /// Sends the message to the thread, answering the tool calls of the assistant with the functions of the registry,
+    /// and waits for the reply of the assistant.
+    ///
+    /// Fails with [`Error::ToolIterationsExceeded`](crate::err::Error::ToolIterationsExceeded) if the assistant is
+    /// still calling tools after [`Self::max_tool_iterations`] rounds. The run is cancelled in that case
+    pub async fn send_message_with_tools<S: Into<String>>(
+        &self,
+        message: S,
+        registry: &ToolRegistry,
+    ) -> crate::Result<ThreadMessage> {
+        let mut run = self.start_run(message).await?;
+        let mut iterations = 0;
+        while run.status == RunStatus::RequiresAction {
+            if iterations == self.max_tool_iterations {
+                self.client.cancel_run(&self.id, &run.id).await?;
+                return Err(crate::err::Error::ToolIterationsExceeded(iterations));
+            }
+            iterations += 1;
+            let mut outputs = Vec::with_capacity(run.tool_calls().len());
+            for call in run.tool_calls() {
+                outputs.push(ToolOutput {
+                    tool_call_id: call.id.clone(),
+                    output: registry.call(call).await.content,
+                });
+            }
+            run = self
+                .client
+                .submit_tool_outputs(&self.id, &run.id, &outputs)
+                .await?;
+            run = self
+                .client
+                .wait_for_run(&self.id, &run.id, self.poll_interval)
+                .await?;
+        }
+        self.reply(run).await
+    }
+
+    /// Messages of the thread, the most recent first
+    pub async fn messages(&self, limit: Option<u32>) -> crate::Result<Vec<ThreadMessage>> {
+        self.client
+            .list_thread_messages(&self.id, None, limit)
+            .await
+    }
+
+    async fn start_run<S: Into<String>>(&self, message: S) -> crate::Result<Run> {
+        self.client.add_thread_message(&self.id, message).await?;
+        let run = self.client.create_run(&self.id, &self.assistant_id).await?;
+        self.client
+            .wait_for_run(&self.id, &run.id, self.poll_interval)
+            .await
+    }
+
+    /// The latest message of the thread, if the run completed
+    async fn reply(&self, run: Run) -> crate::Result<ThreadMessage> {
+        if run.status != RunStatus::Completed {
+            return Err(crate::err::Error::RunFailed {
+                status: run.status.as_str().to_owned(),
+                message: run.last_error.map(|err| err.message).unwrap_or_default(),
+            });
+        }
+        self.messages(Some(1))
+            .await?
+            .pop()
+            .ok_or_else(|| crate::err::Error::ParsingError("The thread has no messages".to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    use crate::client::ChatGPT;
+    use crate::functions::ToolRegistry;
+    use crate::transport::{request_json, HttpTransport, JsonTransport};
+    use crate::types::Role;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct WeatherArguments {
+        /// City to look up
+        city: String,
+    }
+
+    fn run(status: &str) -> serde_json::Value {
+        let mut run = serde_json::json!({
+            "id": "run_1", "object": "thread.run", "thread_id": "thread_1",
+            "assistant_id": "asst_1", "status": status
+        });
+        if status == "requires_action" {
+            run["required_action"] = serde_json::json!({
+                "type": "submit_tool_outputs",
+                "submit_tool_outputs": { "tool_calls": [{
+                    "id": "call_1", "type": "function",
+                    "function": { "name": "weather", "arguments": r#"{"city":"Paris"}"# }
+                }] }
+            });
+        }
+        run
+    }
+
+    /// Serves a thread where the assistant looks up the weather before replying
+    fn thread_transport() -> impl HttpTransport {
+        let polls = AtomicUsize::new(0);
+        JsonTransport(move |request: &reqwest::Request| {
+            assert_eq!(request.headers()["openai-beta"], "assistants=v2");
+            let body = request_json(request);
+            let path = request.url().path();
+            match (request.method().as_str(), path) {
+                ("POST", "/v1/threads") => serde_json::json!({ "id": "thread_1", "created_at": 1 }),
+                ("POST", "/v1/threads/thread_1/messages") => {
+                    assert_eq!(body["content"], "What's the weather in Paris?");
+                    serde_json::json!({
+                        "id": "msg_1", "thread_id": "thread_1", "role": "user",
+                        "content": [{ "type": "text", "text": { "value": body["content"], "annotations": [] } }]
+                    })
+                }
+                ("POST", "/v1/threads/thread_1/runs") => {
+                    assert_eq!(body["assistant_id"], "asst_1");
+                    run("queued")
+                }
+                ("GET", "/v1/threads/thread_1/runs/run_1") => {
+                    match polls.fetch_add(1, Ordering::SeqCst) {
+                        0 => run("requires_action"),
+                        _ => run("completed"),
+                    }
+                }
+                ("POST", "/v1/threads/thread_1/runs/run_1/submit_tool_outputs") => {
+                    assert_eq!(
+                        body["tool_outputs"],
+                        serde_json::json!([{ "tool_call_id": "call_1", "output": "\"Sunny in Paris\"" }])
+                    );
+                    run("in_progress")
+                }
+                ("GET", "/v1/threads/thread_1/messages") => {
+                    assert_eq!(request.url().query(), Some("limit=1"));
+                    serde_json::json!({ "object": "list", "data": [{
+                        "id": "msg_2", "thread_id": "thread_1", "role": "assistant",
+                        "assistant_id": "asst_1", "run_id": "run_1",
+                        "content": [
+                            { "type": "text", "text": { "value": "It is sunny", "annotations": [] } },
+                            { "type": "image_url", "image_url": { "url": "https://example.com" } }
+                        ]
+                    }] })
+                }
+                _ => panic!("Unexpected request to {path}"),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_assistant_thread_tools() -> crate::Result<()> {
+        let client = ChatGPT::new_with_transport("", thread_transport(), Default::default())?;
+        let mut registry = ToolRegistry::new();
+        registry.register(
+            "weather",
+            "Looks up the weather",
+            |args: WeatherArguments| async move { Ok(format!("Sunny in {}", args.city)) },
+        );
+
+        let mut thread = client.new_assistant_thread("asst_1").await?;
+        thread.poll_interval = std::time::Duration::from_millis(1);
+        assert_eq!(thread.id, "thread_1");
+        let reply = thread
+            .send_message_with_tools("What's the weather in Paris?", &registry)
+            .await?;
+        assert_eq!(reply.role, Role::Assistant);
+        assert_eq!(reply.text(), "It is sunny");
+        Ok(())
+    }
+}
@@ -0,0 +1,103 @@
+use reqwest::multipart::Form;
+use serde::Deserialize;
+
+use crate::client::ChatGPT;
+use crate::upload::FileUpload;
+
+/// The model used for audio requests when none is provided
+const DEFAULT_AUDIO_MODEL: &str = "whisper-1";
+
+/// Options of an audio translation request
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd)]
+pub struct TranslationOptions {
+    /// The model translating the audio. `whisper-1` if `None`
+    pub model: Option<String>,
+    /// Text in English guiding the style of the translation, or continuing a previous audio segment
+    pub prompt: Option<String>,
+    /// Sampling temperature between 0 and 1. The endpoint default if `None`
+    pub temperature: Option<f32>,
+}
+
+impl TranslationOptions {
+    /// Sets the model translating the audio
+    pub fn with_model<S: Into<String>>(mut self, model: S) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Sets the text guiding the style of the translation
+    pub fn with_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.prompt = Some(prompt.into());
+        self
+    }
+
+    /// Sets the sampling temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+}
+
+/// The text returned by the audio endpoints
+#[derive(Debug, Deserialize)]
+struct AudioText {
+    text: String,
+}
+
+impl ChatGPT {
+    /// Translates the speech in the audio file into English text with the audio translations endpoint.
+    ///
+    /// The file is streamed as the request is sent, see [`FileUpload`].
+    ///
+    /// Requires the `audio` crate feature
+    pub async fn translate_audio(
+        &self,
+        file: FileUpload,
+        options: &TranslationOptions,
+    ) -> crate::Result<String> {
+        let model = options.model.as_deref().unwrap_or(DEFAULT_AUDIO_MODEL);
+        let mut form = Form::new()
+            .part("file", file.into_part()?)
+            .text("model", model.to_owned())
+            .text("response_format", "json");
+        if let Some(prompt) = &options.prompt {
+            form = form.text("prompt", prompt.clone());
+        }
+        if let Some(temperature) = options.temperature {
+            form = form.text("temperature", temperature.to_string());
+        }
+        let translation: AudioText = self
+            .send_multipart_request("audio/translations", form)
+            .await?;
+        Ok(translation.text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TranslationOptions;
+    use crate::client::ChatGPT;
+    use crate::transport::JsonTransport;
+    use crate::upload::FileUpload;
+
+    #[tokio::test]
+    async fn test_translate_audio() -> crate::Result<()> {
+        let transport = JsonTransport(|request: &reqwest::Request| {
+            assert_eq!(
+                request.url().as_str(),
+                "https://api.openai.com/v1/audio/translations"
+            );
+            let content_type = request.headers()["content-type"].to_str().unwrap();
+            assert!(content_type.starts_with("multipart/form-data"));
+            serde_json::json!({ "text": "Hello, world" })
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let file = FileUpload::from_bytes(&b"RIFF"[..], "greeting.wav");
+        let options = TranslationOptions::default().with_prompt("A greeting");
+        assert_eq!(
+            client.translate_audio(file, &options).await?,
+            "Hello, world"
+        );
+        Ok(())
+    }
+}
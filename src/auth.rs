@@ -0,0 +1,26 @@
+use async_trait::async_trait;
+
+/// A provider of short-lived credentials, e.g. Azure AD or OAuth access tokens, that have to be refreshed.
+///
+/// The client asks the provider for a token before each request, and sends it in the `Authorization: Bearer` header.
+/// See [`ChatGPT::new_with_auth_provider()`](crate::client::ChatGPT::new_with_auth_provider).
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait AuthProvider: Send + Sync {
+    /// Returns the token for the next request.
+    ///
+    /// Called before every request, so implementations should cache the token until it is about to expire.
+    async fn token(&self) -> crate::Result<String>;
+}
+
+/// A hook that supplies a new API key once the API rejects the current one, e.g. by asking the user to re-enter it.
+///
+/// See [`ChatGPT::with_credential_refresh()`](crate::client::ChatGPT::with_credential_refresh).
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait CredentialRefresh: Send + Sync {
+    /// Returns the new API key, or `None` to give up and return the rejected response.
+    ///
+    /// Called when the API responds with `401 Unauthorized`.
+    async fn refresh_key(&self) -> crate::Result<Option<String>>;
+}
@@ -0,0 +1,656 @@
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::Arc;
+
+use reqwest::Proxy;
+use tokio::runtime::Runtime;
+
+use crate::config::{ModelConfiguration, RequestOptions, ResponseFormat};
+#[cfg(feature = "functions")]
+use crate::functions::{FunctionArgument, FunctionDescriptor, ToolDescriptor, ToolRegistry};
+use crate::sampling::{ConsensusResponse, RankedResponse};
+use crate::types::{ChatMessage, CompletionRequest, CompletionResponse, Role, UsageSnapshot};
+
+/// A blocking wrapper of the [`ChatGPT`](crate::client::ChatGPT) client, for programs that do not run an async runtime.
+///
+/// Requests are executed on an internal single-threaded runtime, so the blocking client **must not** be used
+/// from within an async runtime, it panics in that case. Methods of the async client that have no blocking
+/// counterpart, e.g. the endpoint APIs, can be run with [`Self::block_on()`] on the client returned by [`Self::as_async()`].
+#[derive(Debug, Clone)]
+pub struct ChatGPT {
+    inner: crate::client::ChatGPT,
+    runtime: Arc<Runtime>,
+}
+
+impl ChatGPT {
+    /// Constructs a new blocking ChatGPT API client with provided API key and default configuration
+    pub fn new<S: Into<String>>(api_key: S) -> crate::Result<Self> {
+        Self::from_async(crate::client::ChatGPT::new(api_key)?)
+    }
+
+    /// Constructs a new blocking ChatGPT API client with provided API key and Configuration
+    pub fn new_with_config<S: Into<String>>(
+        api_key: S,
+        config: ModelConfiguration,
+    ) -> crate::Result<Self> {
+        Self::from_async(crate::client::ChatGPT::new_with_config(api_key, config)?)
+    }
+
+    /// Constructs a new blocking ChatGPT API client with provided API key, Configuration and Reqwest proxy
+    pub fn new_with_config_proxy<S: Into<String>>(
+        api_key: S,
+        config: ModelConfiguration,
+        proxy: Proxy,
+    ) -> crate::Result<Self> {
+        Self::from_async(crate::client::ChatGPT::new_with_config_proxy(
+            api_key, config, proxy,
+        )?)
+    }
+
+    /// Wraps an async client, e.g. one constructed with an auth provider or a custom transport
+    pub fn from_async(client: crate::client::ChatGPT) -> crate::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self {
+            inner: client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The wrapped async client
+    pub fn as_async(&self) -> &crate::client::ChatGPT {
+        &self.inner
+    }
+
+    /// Runs the future, e.g. a method of the async client without a blocking counterpart, to completion
+    /// on the runtime of this client.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Returns the requests and token usage accumulated by this client and its clones so far.
+    ///
+    /// See [`crate::client::ChatGPT::usage_snapshot()`].
+    pub fn usage_snapshot(&self) -> UsageSnapshot {
+        self.inner.usage_snapshot()
+    }
+
+    /// Resets the accumulated usage to zero, returning the usage accumulated before
+    pub fn reset_usage(&self) -> UsageSnapshot {
+        self.inner.reset_usage()
+    }
+
+    /// Constructs a completion request for the history with the configuration of this client
+    pub fn completion_request<'a>(&'a self, history: &'a [ChatMessage]) -> CompletionRequest<'a> {
+        self.inner.completion_request(history)
+    }
+
+    /// Starts a new conversation with a default starting message.
+    ///
+    /// Conversations record message history.
+    pub fn new_conversation(&self) -> Conversation {
+        self.wrap_conversation(self.inner.new_conversation())
+    }
+
+    /// Starts a new conversation with a specified starting message.
+    ///
+    /// Conversations record message history.
+    pub fn new_conversation_directed<S: Into<String>>(&self, direction_message: S) -> Conversation {
+        self.wrap_conversation(self.inner.new_conversation_directed(direction_message))
+    }
+
+    /// Starts a new conversation with a specified developer message, that replaces the system message for o-series reasoning models.
+    ///
+    /// Conversations record message history.
+    pub fn new_conversation_developer<S: Into<String>>(
+        &self,
+        developer_message: S,
+    ) -> Conversation {
+        self.wrap_conversation(self.inner.new_conversation_developer(developer_message))
+    }
+
+    /// Starts a new conversation with the provided message history
+    pub fn new_conversation_with_history(&self, history: Vec<ChatMessage>) -> Conversation {
+        self.wrap_conversation(crate::converse::Conversation::new_with_history(
+            self.inner.clone(),
+            history,
+        ))
+    }
+
+    /// Wraps an async conversation to continue it with this client's runtime
+    pub fn wrap_conversation(&self, conversation: crate::converse::Conversation) -> Conversation {
+        Conversation {
+            inner: conversation,
+            runtime: self.runtime.clone(),
+        }
+    }
+
+    /// Restores a conversation from local conversation JSON file.
+    /// The conversation file can originally be saved using the [`Conversation::save_history_json()`].
+    ///
+    /// Requires the `json` crate feature
+    #[cfg(all(feature = "json", not(target_arch = "wasm32")))]
+    pub fn restore_conversation_json<P: AsRef<std::path::Path>>(
+        &self,
+        file: P,
+    ) -> crate::Result<Conversation> {
+        let conversation = self
+            .runtime
+            .block_on(self.inner.restore_conversation_json(file))?;
+        Ok(self.wrap_conversation(conversation))
+    }
+
+    /// Restores a conversation from a JSON string without touching the filesystem.
+    ///
+    /// Requires the `json` crate feature
+    #[cfg(feature = "json")]
+    pub fn restore_conversation_from_str(&self, json: &str) -> crate::Result<Conversation> {
+        Ok(self.wrap_conversation(self.inner.restore_conversation_from_str(json)?))
+    }
+
+    /// Restores a conversation from JSON bytes without touching the filesystem.
+    ///
+    /// Requires the `json` crate feature
+    #[cfg(feature = "json")]
+    pub fn restore_conversation_from_json_bytes(&self, json: &[u8]) -> crate::Result<Conversation> {
+        Ok(self.wrap_conversation(self.inner.restore_conversation_from_json_bytes(json)?))
+    }
+
+    /// Restores a conversation from local conversation postcard file.
+    /// The conversation file can originally be saved using the [`Conversation::save_history_postcard()`].
+    ///
+    /// Requires the `postcard` crate feature
+    #[cfg(all(feature = "postcard", not(target_arch = "wasm32")))]
+    pub fn restore_conversation_postcard<P: AsRef<std::path::Path>>(
+        &self,
+        file: P,
+    ) -> crate::Result<Conversation> {
+        let conversation = self
+            .runtime
+            .block_on(self.inner.restore_conversation_postcard(file))?;
+        Ok(self.wrap_conversation(conversation))
+    }
+
+    /// Restores a conversation from postcard bytes without touching the filesystem.
+    ///
+    /// Requires the `postcard` crate feature
+    #[cfg(feature = "postcard")]
+    pub fn restore_conversation_from_bytes(&self, bytes: &[u8]) -> crate::Result<Conversation> {
+        Ok(self.wrap_conversation(self.inner.restore_conversation_from_bytes(bytes)?))
+    }
+
+    /// Explicitly sends whole message history to the API.
+    ///
+    /// See [`crate::client::ChatGPT::send_history()`].
+    pub fn send_history(&self, history: &[ChatMessage]) -> crate::Result<CompletionResponse> {
+        self.runtime.block_on(self.inner.send_history(history))
+    }
+
+    /// Explicitly sends whole message history to the API, overriding the response format of the configuration.
+    pub fn send_history_with_format(
+        &self,
+        history: &[ChatMessage],
+        response_format: ResponseFormat,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime.block_on(
+            self.inner
+                .send_history_with_format(history, response_format),
+        )
+    }
+
+    /// Explicitly sends whole message history to the API, overriding the stop sequences of the configuration.
+    pub fn send_history_with_stop<S: Into<String>, I: IntoIterator<Item = S>>(
+        &self,
+        history: &[ChatMessage],
+        stop: I,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_history_with_stop(history, stop))
+    }
+
+    /// Explicitly sends whole message history to the API, overriding the configuration with the request options.
+    pub fn send_history_with_options(
+        &self,
+        history: &[ChatMessage],
+        options: &RequestOptions,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_history_with_options(history, options))
+    }
+
+    /// Sends a pre-constructed request to the API.
+    ///
+    /// See [`crate::client::ChatGPT::send_request()`].
+    pub fn send_request(
+        &self,
+        request: &CompletionRequest<'_>,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime.block_on(self.inner.send_request(request))
+    }
+
+    /// Sends a pre-constructed request to the API, overriding the configuration with the request options.
+    pub fn send_request_with_options(
+        &self,
+        request: &CompletionRequest<'_>,
+        options: &RequestOptions,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_request_with_options(request, options))
+    }
+
+    /// Sends a single message to the API without preserving message history.
+    pub fn send_message<S: Into<String>>(&self, message: S) -> crate::Result<CompletionResponse> {
+        self.runtime.block_on(self.inner.send_message(message))
+    }
+
+    /// Sends a single message to the API without preserving message history, overriding the configuration
+    /// with the request options.
+    pub fn send_message_with_options<S: Into<String>>(
+        &self,
+        message: S,
+        options: &RequestOptions,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_with_options(message, options))
+    }
+
+    /// Sends a single message to the API without preserving message history, overriding the response format of the configuration.
+    pub fn send_message_with_format<S: Into<String>>(
+        &self,
+        message: S,
+        response_format: ResponseFormat,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime.block_on(
+            self.inner
+                .send_message_with_format(message, response_format),
+        )
+    }
+
+    /// Sends a single message to the API without preserving message history, overriding the stop sequences
+    /// of the configuration.
+    pub fn send_message_with_stop<M: Into<String>, S: Into<String>, I: IntoIterator<Item = S>>(
+        &self,
+        message: M,
+        stop: I,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_with_stop(message, stop))
+    }
+
+    /// Sends the history to the API, and deserializes the structured reply into `T`.
+    /// See [`crate::client::ChatGPT::send_history_structured()`] for details.
+    ///
+    /// Requires the `structured` crate feature
+    #[cfg(feature = "structured")]
+    pub fn send_history_structured<T: schemars::JsonSchema + serde::de::DeserializeOwned>(
+        &self,
+        history: &[ChatMessage],
+    ) -> crate::Result<crate::structured::StructuredResponse<T>> {
+        self.runtime
+            .block_on(self.inner.send_history_structured(history))
+    }
+
+    /// Sends a single message to the API without preserving message history, and deserializes the structured
+    /// reply into `T`. See [`crate::client::ChatGPT::send_history_structured()`] for details.
+    ///
+    /// Requires the `structured` crate feature
+    #[cfg(feature = "structured")]
+    pub fn send_message_structured<
+        T: schemars::JsonSchema + serde::de::DeserializeOwned,
+        S: Into<String>,
+    >(
+        &self,
+        message: S,
+    ) -> crate::Result<crate::structured::StructuredResponse<T>> {
+        self.runtime
+            .block_on(self.inner.send_message_structured(message))
+    }
+
+    /// Sends a message with specified function descriptors. ChatGPT is then able to call these functions.
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_message_functions<S: Into<String>, A: FunctionArgument>(
+        &self,
+        message: S,
+        functions: Vec<FunctionDescriptor<A>>,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_functions(message, functions))
+    }
+
+    /// Sends a message with specified pre-baked function descriptors.
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_message_functions_baked<S: Into<String>>(
+        &self,
+        message: S,
+        baked_functions: Vec<serde_json::Value>,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime.block_on(
+            self.inner
+                .send_message_functions_baked(message, baked_functions),
+        )
+    }
+
+    /// Sends whole message history alongside with defined baked functions.
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_history_functions(
+        &self,
+        history: &[ChatMessage],
+        functions: &[serde_json::Value],
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_history_functions(history, functions))
+    }
+
+    /// Sends a message with specified tool descriptors. The model is then able to call these tools.
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_message_tools<S: Into<String>, A: FunctionArgument>(
+        &self,
+        message: S,
+        tools: Vec<ToolDescriptor<A>>,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_tools(message, tools))
+    }
+
+    /// Sends a message with specified pre-baked tool descriptors.
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_message_tools_baked<S: Into<String>>(
+        &self,
+        message: S,
+        baked_tools: Vec<serde_json::Value>,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_tools_baked(message, baked_tools))
+    }
+
+    /// Sends whole message history alongside with defined baked tools.
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_history_tools(
+        &self,
+        history: &[ChatMessage],
+        tools: &[serde_json::Value],
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_history_tools(history, tools))
+    }
+
+    /// Requests `count` completion choices for the history, scores each of them with the provided scorer
+    /// and returns them ranked. See [`crate::client::ChatGPT::send_history_best_of()`] for details.
+    ///
+    /// The scorer is called outside of the runtime, so it can use the blocking client, e.g. [`Self::judge_score()`].
+    pub fn send_history_best_of<F>(
+        &self,
+        history: &[ChatMessage],
+        count: u32,
+        mut scorer: F,
+    ) -> crate::Result<RankedResponse>
+    where
+        F: FnMut(&ChatMessage) -> crate::Result<f64>,
+    {
+        let response = self
+            .runtime
+            .block_on(self.inner.sample_history(history, count, None))?;
+        if response.message_choices.is_empty() {
+            return Err(crate::err::Error::NoChoices);
+        }
+        let scores = response
+            .message_choices
+            .iter()
+            .map(|choice| scorer(&choice.message))
+            .collect::<crate::Result<Vec<_>>>()?;
+        Ok(RankedResponse::from_scores(response, scores))
+    }
+
+    /// Requests `count` completion choices for a single message, without preserving message history,
+    /// and ranks them with the provided scorer. See [`Self::send_history_best_of()`] for details.
+    pub fn send_message_best_of<S, F>(
+        &self,
+        message: S,
+        count: u32,
+        scorer: F,
+    ) -> crate::Result<RankedResponse>
+    where
+        S: Into<String>,
+        F: FnMut(&ChatMessage) -> crate::Result<f64>,
+    {
+        let history = vec![ChatMessage::new(Role::User, message)];
+        self.send_history_best_of(&history, count, scorer)
+    }
+
+    /// Samples `samples` completion choices for the history with the provided temperature and returns the consensus
+    /// answer. See [`crate::client::ChatGPT::send_history_self_consistent()`] for details.
+    pub fn send_history_self_consistent<T, F>(
+        &self,
+        history: &[ChatMessage],
+        samples: u32,
+        temperature: f32,
+        parser: F,
+    ) -> crate::Result<ConsensusResponse<T>>
+    where
+        T: Eq + Hash + Clone,
+        F: FnMut(&str) -> Option<T>,
+    {
+        self.runtime
+            .block_on(self.inner.send_history_self_consistent(
+                history,
+                samples,
+                temperature,
+                parser,
+            ))
+    }
+
+    /// Samples multiple completion choices for a single message, without preserving message history,
+    /// and returns the consensus answer. See [`Self::send_history_self_consistent()`] for details.
+    pub fn send_message_self_consistent<S, T, F>(
+        &self,
+        message: S,
+        samples: u32,
+        temperature: f32,
+        parser: F,
+    ) -> crate::Result<ConsensusResponse<T>>
+    where
+        S: Into<String>,
+        T: Eq + Hash + Clone,
+        F: FnMut(&str) -> Option<T>,
+    {
+        self.runtime
+            .block_on(self.inner.send_message_self_consistent(
+                message,
+                samples,
+                temperature,
+                parser,
+            ))
+    }
+
+    /// Asks the model to grade the answer against the provided criteria, and returns the grade from 0 to 10.
+    ///
+    /// Can be used as a scorer for [`Self::send_history_best_of()`].
+    pub fn judge_score<C: AsRef<str>, A: AsRef<str>>(
+        &self,
+        criteria: C,
+        answer: A,
+    ) -> crate::Result<f64> {
+        self.runtime
+            .block_on(self.inner.judge_score(criteria, answer))
+    }
+}
+
+/// A blocking wrapper of the [`Conversation`](crate::converse::Conversation), created by the blocking client.
+///
+/// The history and the other non-async state of the conversation are available through [`Self::as_async()`]
+/// and [`Self::as_async_mut()`].
+pub struct Conversation {
+    inner: crate::converse::Conversation,
+    runtime: Arc<Runtime>,
+}
+
+impl Conversation {
+    /// The wrapped async conversation, e.g. to read its history
+    pub fn as_async(&self) -> &crate::converse::Conversation {
+        &self.inner
+    }
+
+    /// The wrapped async conversation, e.g. to roll back or pin its messages
+    pub fn as_async_mut(&mut self) -> &mut crate::converse::Conversation {
+        &mut self.inner
+    }
+
+    /// Sends the message to the ChatGPT API and returns the completion response.
+    pub fn send_message<S: Into<String> + Send + Sync>(
+        &mut self,
+        message: S,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime.block_on(self.inner.send_message(message))
+    }
+
+    /// Sends a message from a specified role to the ChatGPT API and returns the completion response.
+    pub fn send_role_message<S: Into<String> + Send + Sync>(
+        &mut self,
+        role: Role,
+        message: S,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_role_message(role, message))
+    }
+
+    /// Sends the message to the ChatGPT API and requests `count` candidate replies, without recording any of them.
+    ///
+    /// See [`crate::converse::Conversation::send_message_multiple()`].
+    pub fn send_message_multiple<S: Into<String>>(
+        &mut self,
+        message: S,
+        count: u32,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_multiple(message, count))
+    }
+
+    /// Sends a message with the functions of this conversation to the ChatGPT API, calling them if requested.
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_message_functions<S: Into<String>>(
+        &mut self,
+        message: S,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_functions(message))
+    }
+
+    /// Sends the message with the tools of the registry, calling the requested tools until the model replies
+    /// without calling any. See [`crate::converse::Conversation::send_message_with_tools()`].
+    ///
+    /// Requires the `functions` crate feature
+    #[cfg(feature = "functions")]
+    pub fn send_message_with_tools<S: Into<String>>(
+        &mut self,
+        message: S,
+        registry: &ToolRegistry,
+    ) -> crate::Result<CompletionResponse> {
+        self.runtime
+            .block_on(self.inner.send_message_with_tools(message, registry))
+    }
+
+    /// Summarizes the older messages if the history exceeds the compaction threshold.
+    /// See [`crate::converse::Conversation::compact_history()`].
+    ///
+    /// Requires the `tokenizer` crate feature
+    #[cfg(feature = "tokenizer")]
+    pub fn compact_history(&mut self) -> crate::Result<bool> {
+        self.runtime.block_on(self.inner.compact_history())
+    }
+
+    /// Saves the history of this conversation to a local JSON file.
+    ///
+    /// Requires the `json` crate feature
+    #[cfg(all(feature = "json", not(target_arch = "wasm32")))]
+    pub fn save_history_json<P: AsRef<std::path::Path>>(&self, to: P) -> crate::Result<()> {
+        self.runtime.block_on(self.inner.save_history_json(to))
+    }
+
+    /// Saves the history of this conversation to a local postcard file.
+    ///
+    /// Requires the `postcard` crate feature
+    #[cfg(all(feature = "postcard", not(target_arch = "wasm32")))]
+    pub fn save_history_postcard<P: AsRef<std::path::Path>>(&self, to: P) -> crate::Result<()> {
+        self.runtime.block_on(self.inner.save_history_postcard(to))
+    }
+
+    /// Unwraps the async conversation
+    pub fn into_async(self) -> crate::converse::Conversation {
+        self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChatGPT;
+    use crate::transport::{request_json, HttpTransport, JsonTransport};
+
+    /// Replies with the amount of sent messages
+    fn echo_transport() -> impl HttpTransport {
+        JsonTransport(|request: &reqwest::Request| {
+            let messages = request_json(request)["messages"].as_array().unwrap().len();
+            let choices = request_json(request)["n"].as_u64().unwrap_or(1);
+            serde_json::json!({ "choices": (0..choices).map(|index| serde_json::json!({
+                "index": index, "message": {
+                    "role": "assistant", "content": format!("{messages} messages, choice {index}")
+                }
+            })).collect::<Vec<_>>() })
+        })
+    }
+
+    fn client() -> crate::Result<ChatGPT> {
+        ChatGPT::from_async(crate::client::ChatGPT::new_with_transport(
+            "",
+            echo_transport(),
+            Default::default(),
+        )?)
+    }
+
+    #[test]
+    fn test_blocking_conversation() -> crate::Result<()> {
+        let client = client()?;
+        assert_eq!(
+            client.send_message("Hello")?.message().content,
+            "1 messages, choice 0"
+        );
+
+        let mut conversation = client.new_conversation();
+        conversation.send_message("Hello")?;
+        let response = conversation.send_message("Hello again")?;
+        assert_eq!(response.message().content, "4 messages, choice 0");
+        assert_eq!(conversation.as_async().history.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_blocking_best_of() -> crate::Result<()> {
+        let client = client()?;
+        // the scorer runs outside of the runtime, so it may block on the client itself
+        let ranked = client.send_message_best_of("Hello", 3, |message| {
+            client.send_message(message.content.as_str())?;
+            Ok(if message.content.ends_with('1') {
+                1.0
+            } else {
+                0.0
+            })
+        })?;
+        assert_eq!(ranked.best_index, 1);
+        assert_eq!(ranked.message().content, "1 messages, choice 1");
+        Ok(())
+    }
+}
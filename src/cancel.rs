@@ -0,0 +1,148 @@
+use std::future::Future;
+use std::task::Poll;
+
+pub use tokio_util::sync::CancellationToken;
+
+use crate::client::ChatGPT;
+#[cfg(feature = "streams")]
+use crate::types::ResponseChunk;
+use crate::types::{ChatMessage, CompletionResponse, Role};
+#[cfg(feature = "streams")]
+use futures_util::Stream;
+
+impl ChatGPT {
+    /// Explicitly sends whole message history to the API, aborting the request once the token is cancelled.
+    ///
+    /// Returns [`Error::Cancelled`](crate::err::Error::Cancelled) if the token is cancelled before the reply arrives.
+    ///
+    /// Requires the `cancellation` crate feature
+    pub async fn send_history_cancellable(
+        &self,
+        history: &[ChatMessage],
+        token: &CancellationToken,
+    ) -> crate::Result<CompletionResponse> {
+        until_cancelled(token, self.send_history(history)).await?
+    }
+
+    /// Sends a single message to the API without preserving message history, aborting the request once the token
+    /// is cancelled. See [`Self::send_history_cancellable()`] for details.
+    ///
+    /// Requires the `cancellation` crate feature
+    pub async fn send_message_cancellable<S: Into<String>>(
+        &self,
+        message: S,
+        token: &CancellationToken,
+    ) -> crate::Result<CompletionResponse> {
+        let message = ChatMessage::new(Role::User, message);
+        self.send_history_cancellable(std::slice::from_ref(&message), token)
+            .await
+    }
+
+    /// Explicitly sends whole message history to the API and returns the response as stream, that ends with
+    /// [`Error::Cancelled`](crate::err::Error::Cancelled) once the token is cancelled.
+    ///
+    /// The connection is closed as soon as the cancellation is observed, without waiting for the stream to be dropped.
+    ///
+    /// Requires the `cancellation` and `streams` crate features
+    #[cfg(feature = "streams")]
+    pub async fn send_history_streaming_cancellable(
+        &self,
+        history: &[ChatMessage],
+        token: &CancellationToken,
+    ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
+        let stream = until_cancelled(token, self.send_history_streaming(history)).await??;
+        Ok(cancellable_stream(stream, token.clone()))
+    }
+
+    /// Sends a single message to the API without preserving message history and returns the response as stream,
+    /// that ends once the token is cancelled. See [`Self::send_history_streaming_cancellable()`] for details.
+    ///
+    /// Requires the `cancellation` and `streams` crate features
+    #[cfg(feature = "streams")]
+    pub async fn send_message_streaming_cancellable<S: Into<String>>(
+        &self,
+        message: S,
+        token: &CancellationToken,
+    ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
+        let message = ChatMessage::new(Role::User, message);
+        self.send_history_streaming_cancellable(std::slice::from_ref(&message), token)
+            .await
+    }
+}
+
+/// Drives the future until it completes, or fails with [`Error::Cancelled`](crate::err::Error::Cancelled)
+/// once the token is cancelled. The future is dropped on cancellation, which aborts its request
+async fn until_cancelled<F: Future>(
+    token: &CancellationToken,
+    future: F,
+) -> crate::Result<F::Output> {
+    let mut future = std::pin::pin!(future);
+    let mut cancelled = std::pin::pin!(token.cancelled());
+    std::future::poll_fn(|cx| {
+        if cancelled.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(Err(crate::err::Error::Cancelled));
+        }
+        future.as_mut().poll(cx).map(Ok)
+    })
+    .await
+}
+
+/// Forwards the stream until the token is cancelled, then drops it and yields a single
+/// [`Error::Cancelled`](crate::err::Error::Cancelled)
+#[cfg(feature = "streams")]
+fn cancellable_stream<S: Stream<Item = crate::Result<ResponseChunk>>>(
+    stream: S,
+    token: CancellationToken,
+) -> impl Stream<Item = crate::Result<ResponseChunk>> {
+    let mut stream = Some(Box::pin(stream));
+    let mut cancelled = Box::pin(token.cancelled_owned());
+    futures_util::stream::poll_fn(move |cx| {
+        let Some(inner) = stream.as_mut() else {
+            return Poll::Ready(None);
+        };
+        if cancelled.as_mut().poll(cx).is_ready() {
+            // dropping the response closes the connection
+            stream = None;
+            return Poll::Ready(Some(Err(crate::err::Error::Cancelled)));
+        }
+        let item = inner.as_mut().poll_next(cx);
+        if let Poll::Ready(None) = item {
+            stream = None;
+        }
+        item
+    })
+}
+
+#[cfg(test)]
+#[cfg(feature = "streams")]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::{cancellable_stream, until_cancelled, CancellationToken};
+    use crate::err::Error;
+    use crate::types::ResponseChunk;
+
+    #[tokio::test]
+    async fn test_until_cancelled() {
+        let token = CancellationToken::new();
+        assert!(matches!(until_cancelled(&token, async { 1 }).await, Ok(1)));
+
+        let cancel = token.clone();
+        let pending = until_cancelled(&token, std::future::pending::<()>());
+        let (result, _) = tokio::join!(pending, async move { cancel.cancel() });
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_cancellable_stream() {
+        let token = CancellationToken::new();
+        let chunks = futures_util::stream::iter([Ok(ResponseChunk::Done)])
+            .chain(futures_util::stream::pending());
+        let mut stream = Box::pin(cancellable_stream(chunks, token.clone()));
+        assert!(matches!(stream.next().await, Some(Ok(ResponseChunk::Done))));
+
+        token.cancel();
+        assert!(matches!(stream.next().await, Some(Err(Error::Cancelled))));
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -2,6 +2,20 @@
 #![doc = include_str!("../README.md")]
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
+#[cfg(feature = "assistants")]
+/// Contains the clients of the assistants endpoints, backed by threads stored by the API
+pub mod assistants;
+#[cfg(feature = "audio")]
+/// Contains the clients of the audio endpoints
+pub mod audio;
+/// Contains the providers of short-lived credentials
+pub mod auth;
+#[cfg(feature = "cancellation")]
+/// Contains cancellable variants of the requests
+pub mod cancel;
+#[cfg(feature = "blocking")]
+/// Contains the blocking client, for programs that do not run an async runtime
+pub mod blocking;
 /// This module contains the ChatGPT client
 pub mod client;
 /// This module contains additional configuration for ChatGPT
@@ -10,24 +24,73 @@ pub mod config;
 pub mod converse;
 /// This module contains the errors related to the API
 pub mod err;
+#[cfg(feature = "files")]
+/// Contains the client of the files endpoint
+pub mod files;
+#[cfg(feature = "fine_tuning")]
+/// Contains the client of the fine-tuning endpoints
+pub mod fine_tuning;
+#[cfg(feature = "images")]
+/// Contains the client of the image generation endpoint
+pub mod images;
+/// Contains hooks for inspecting and modifying the requests sent to the API
+pub mod intercept;
 #[cfg(feature = "functions")]
 /// Contains API for function calling
 pub mod functions;
+#[cfg(feature = "models")]
+/// Contains the client of the models endpoint
+pub mod models;
+#[cfg(feature = "moderations")]
+/// Contains the client of the moderations endpoint
+pub mod moderation;
+#[cfg(feature = "organization")]
+/// Contains the client of the organization usage and costs endpoints
+pub mod organization;
+/// Helpers for sampling multiple completions and choosing between them
+pub mod sampling;
+#[cfg(feature = "responses")]
+/// Contains the client of the responses endpoint
+pub mod responses;
+#[cfg(feature = "structured")]
+/// Contains helpers for requesting typed structured outputs
+pub mod structured;
+#[cfg(feature = "pricing")]
+/// Contains the pricing table of the models, used to estimate the cost of requests
+pub mod pricing;
 /// The prelude module. Import everything from it to get the necessary elements from this library
 pub mod prelude;
+#[cfg(feature = "tokenizer")]
+/// Contains token encoding utilities, backed by tiktoken
+pub mod tokenizer;
+/// Contains the HTTP transport abstraction of the client
+pub mod transport;
 /// Types returned from the API and sent to it
 pub mod types;
+#[cfg(feature = "uploads")]
+/// Contains file upload types used by multipart API endpoints
+pub mod upload;
+#[cfg(feature = "vector_stores")]
+/// Contains the client of the vector stores endpoints
+pub mod vector_stores;
+#[cfg(feature = "vision")]
+/// Contains helpers for preparing images for vision requests
+pub mod vision;
 
 /// Result that is returned from most ChatGPT functions
 pub type Result<T> = std::result::Result<T, err::Error>;
 
 #[cfg(test)]
-pub mod test {
+mod test {
     use std::path::Path;
 
     use futures::StreamExt;
 
-    use crate::{client::ChatGPT, config::ModelConfiguration, types::ResponseChunk};
+    use crate::{
+        client::ChatGPT,
+        config::ModelConfiguration,
+        types::{FinishReason, ResponseChunk},
+    };
 
     #[tokio::test]
     async fn test_client() -> crate::Result<()> {
@@ -154,7 +217,7 @@ pub mod test {
             .await?;
         assert_eq!(
             response.message_choices.first().unwrap().finish_reason,
-            "length".to_string()
+            Some(FinishReason::Length)
         );
         Ok(())
     }
@@ -173,8 +236,27 @@ pub mod test {
             .await?;
         assert_eq!(
             response.message_choices.first().unwrap().finish_reason,
-            "length".to_string()
+            Some(FinishReason::Length)
         );
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_conversation_auto_continue() -> crate::Result<()> {
+        let client = ChatGPT::new_with_config(
+            std::env::var("TEST_API_KEY")?,
+            ModelConfiguration {
+                max_tokens: Some(20),
+                ..Default::default()
+            },
+        )?;
+        let mut conversation = client.new_conversation();
+        conversation.max_continuations = 3;
+        conversation
+            .send_message("Could you give me names of three popular Rust web frameworks?")
+            .await?;
+        // the system message, the question and a single stitched reply
+        assert_eq!(conversation.history.len(), 3);
+        Ok(())
+    }
 }
@@ -1,34 +1,111 @@
+#[cfg(all(
+    feature = "tokio",
+    any(feature = "json", feature = "postcard"),
+    not(target_arch = "wasm32")
+))]
 use std::path::Path;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
 
 use reqwest::header::AUTHORIZATION;
 use reqwest::header::{HeaderMap, HeaderValue};
-use reqwest::{self, Proxy};
+#[cfg(not(target_arch = "wasm32"))]
+use reqwest::Proxy;
+use reqwest::{self, Response, StatusCode};
+use serde::de::DeserializeOwned;
+#[cfg(all(
+    feature = "tokio",
+    any(feature = "json", feature = "postcard"),
+    not(target_arch = "wasm32")
+))]
 use tokio::fs::File;
+#[cfg(all(
+    feature = "tokio",
+    any(feature = "json", feature = "postcard"),
+    not(target_arch = "wasm32")
+))]
 use tokio::io::AsyncReadExt;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
 
 #[cfg(feature = "streams")]
-use reqwest::Response;
+use crate::config::StreamLengthLimit;
 #[cfg(feature = "streams")]
 use {
-    crate::types::InboundChunkPayload, crate::types::InboundResponseChunk,
+    crate::types::InboundChunkPayload, crate::types::InboundFunctionCallDelta,
+    crate::types::InboundResponseChunk, crate::types::InboundToolCallDelta,
     crate::types::ResponseChunk, futures_util::Stream,
 };
 
-use crate::config::ModelConfiguration;
+use crate::auth::{AuthProvider, CredentialRefresh};
+use crate::config::{ApiBackend, ModelConfiguration, RequestOptions, ResponseFormat};
 use crate::converse::Conversation;
-use crate::types::{ChatMessage, CompletionRequest, CompletionResponse, Role, ServerResponse};
+#[cfg(any(feature = "json", feature = "postcard"))]
+use crate::converse::SavedConversation;
+use crate::intercept::{Interceptor, RequestParts, ResponseParts};
+use crate::transport::HttpTransport;
+use crate::types::{
+    ChatMessage, CompletionRequest, CompletionResponse, RateLimitInfo, Role, ServerResponse,
+    TokenUsage, UsageSnapshot,
+};
 
 #[cfg(feature = "functions")]
-use crate::functions::{FunctionArgument, FunctionDescriptor};
+use crate::functions::{FunctionArgument, FunctionDescriptor, ToolDescriptor};
 
 /// The client that operates the ChatGPT API
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct ChatGPT {
+    /// Builds the requests, that are then sent by the transport
     client: reqwest::Client,
+    transport: Arc<dyn HttpTransport>,
+    credentials: Credentials,
+    credential_refresh: Option<Arc<dyn CredentialRefresh>>,
+    interceptors: Vec<Arc<dyn Interceptor>>,
+    circuit: Arc<Mutex<CircuitState>>,
+    usage: Arc<Mutex<UsageSnapshot>>,
     /// The configuration for this ChatGPT client
     pub config: ModelConfiguration,
 }
 
+/// Credentials sent with each request. The API key is shared between clones of the client, so a refreshed key applies to all of them
+#[derive(Clone)]
+enum Credentials {
+    ApiKey(Arc<RwLock<String>>),
+    Provider(Arc<dyn AuthProvider>),
+}
+
+impl Credentials {
+    fn api_key(api_key: String) -> crate::Result<Self> {
+        // validated upfront, so that invalid keys fail when constructing the client
+        HeaderValue::from_bytes(api_key.as_bytes())?;
+        Ok(Self::ApiKey(Arc::new(RwLock::new(api_key))))
+    }
+}
+
+/// Failures of the endpoint, tracked for the circuit breaker
+#[derive(Debug, Default)]
+struct CircuitState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+}
+
+impl std::fmt::Debug for ChatGPT {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChatGPT")
+            .field("client", &self.client)
+            .field(
+                "auth_provider",
+                &matches!(self.credentials, Credentials::Provider(_)),
+            )
+            .field("credential_refresh", &self.credential_refresh.is_some())
+            .field("interceptors", &self.interceptors.len())
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
 impl ChatGPT {
     /// Constructs a new ChatGPT API client with provided API key and default configuration
     pub fn new<S: Into<String>>(api_key: S) -> crate::Result<Self> {
@@ -36,51 +113,195 @@ impl ChatGPT {
     }
 
     /// Constructs a new ChatGPT API client with provided API key, default configuration and a reqwest proxy
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new_with_proxy<S: Into<String>>(api_key: S, proxy: Proxy) -> crate::Result<Self> {
         Self::new_with_config_proxy(api_key, ModelConfiguration::default(), proxy)
     }
 
     /// Constructs a new ChatGPT API client with provided API Key and Configuration
+    ///
+    /// Unless [`ModelConfiguration::use_system_proxy`] is disabled, the client uses proxies
+    /// from the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment variables.
     pub fn new_with_config<S: Into<String>>(
         api_key: S,
         config: ModelConfiguration,
     ) -> crate::Result<Self> {
-        let api_key = api_key.into();
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_bytes(format!("Bearer {api_key}").as_bytes())?,
-        );
-        let client = reqwest::ClientBuilder::new()
-            .default_headers(headers)
-            .timeout(config.timeout)
-            .build()?;
-        Ok(Self { client, config })
+        let client = Self::client_builder(&config)?.build()?;
+        Self::from_parts(client, Credentials::api_key(api_key.into())?, config)
     }
 
     /// Constructs a new ChatGPT API client with provided API Key, Configuration and Reqwest proxy
+    ///
+    /// The provided proxy takes precedence over proxies from the environment variables.
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn new_with_config_proxy<S: Into<String>>(
         api_key: S,
         config: ModelConfiguration,
         proxy: Proxy,
     ) -> crate::Result<Self> {
-        let api_key = api_key.into();
-        let mut headers = HeaderMap::new();
-        headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_bytes(format!("Bearer {api_key}").as_bytes())?,
-        );
+        let client = Self::client_builder(&config)?.proxy(proxy).build()?;
+        Self::from_parts(client, Credentials::api_key(api_key.into())?, config)
+    }
 
-        let client = reqwest::ClientBuilder::new()
-            .default_headers(headers)
-            .timeout(config.timeout)
-            .proxy(proxy)
-            .build()?;
-        Ok(Self { client, config })
+    /// Constructs a new ChatGPT API client with provided Configuration, that authenticates each request
+    /// with a token requested from the auth provider instead of a static API key.
+    ///
+    /// Can be used with Azure AD tokens and other short-lived credentials.
+    pub fn new_with_auth_provider<P: AuthProvider + 'static>(
+        auth_provider: P,
+        config: ModelConfiguration,
+    ) -> crate::Result<Self> {
+        let client = Self::client_builder(&config)?.build()?;
+        Self::from_parts(
+            client,
+            Credentials::Provider(Arc::new(auth_provider)),
+            config,
+        )
+    }
+
+    /// Constructs a new ChatGPT API client with provided API key and Configuration, that reuses the provided
+    /// HTTP client, e.g. to share its connection pool or to customize TLS and DNS resolution.
+    ///
+    /// The API key is sent with each request, so the client does not need any default headers.
+    /// The client is used as is, so the timeout, proxy and TLS settings of the configuration are ignored.
+    pub fn with_client<S: Into<String>>(
+        client: reqwest::Client,
+        api_key: S,
+        config: ModelConfiguration,
+    ) -> crate::Result<Self> {
+        Self::from_parts(client, Credentials::api_key(api_key.into())?, config)
+    }
+
+    /// Constructs a new ChatGPT API client with provided API key and Configuration, that sends all requests through
+    /// the provided middleware client, e.g. to reuse existing retry, tracing or caching middleware.
+    ///
+    /// The middleware client is used as is, so the timeout, proxy and TLS settings of the configuration are ignored.
+    ///
+    /// Requires the `middleware` crate feature
+    #[cfg(feature = "middleware")]
+    pub fn new_with_middleware<S: Into<String>>(
+        api_key: S,
+        client: reqwest_middleware::ClientWithMiddleware,
+        config: ModelConfiguration,
+    ) -> crate::Result<Self> {
+        Self::new_with_transport(api_key, client, config)
+    }
+
+    /// Constructs a new ChatGPT API client with provided API key and Configuration, that sends all requests
+    /// with the provided transport instead of its own HTTP client.
+    ///
+    /// The transport is used as is, so the timeout, proxy and TLS settings of the configuration are ignored.
+    pub fn new_with_transport<S: Into<String>, T: HttpTransport + 'static>(
+        api_key: S,
+        transport: T,
+        config: ModelConfiguration,
+    ) -> crate::Result<Self> {
+        let mut chatgpt = Self::from_parts(
+            reqwest::Client::new(),
+            Credentials::api_key(api_key.into())?,
+            config,
+        )?;
+        chatgpt.transport = Arc::new(transport);
+        Ok(chatgpt)
+    }
+
+    fn from_parts(
+        client: reqwest::Client,
+        credentials: Credentials,
+        config: ModelConfiguration,
+    ) -> crate::Result<Self> {
+        Ok(Self {
+            transport: Arc::new(client.clone()),
+            client,
+            credentials,
+            credential_refresh: None,
+            interceptors: Vec::new(),
+            circuit: Default::default(),
+            usage: Default::default(),
+            config,
+        })
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn client_builder(config: &ModelConfiguration) -> crate::Result<reqwest::ClientBuilder> {
+        let builder = match config.read_timeout {
+            // the total timeout is then applied to non-streaming requests only
+            Some(timeout) => reqwest::ClientBuilder::new().read_timeout(timeout),
+            None => reqwest::ClientBuilder::new().timeout(config.timeout),
+        };
+        let builder = match config.connect_timeout {
+            Some(timeout) => builder.connect_timeout(timeout),
+            None => builder,
+        };
+        let builder = config.tls.apply(builder)?;
+        #[cfg(unix)]
+        let builder = match &config.unix_socket {
+            Some(path) => builder.unix_socket(path.clone()),
+            None => builder,
+        };
+        Ok(if config.use_system_proxy {
+            builder
+        } else {
+            builder.no_proxy()
+        })
+    }
+
+    /// Requests are sent with the `fetch` API of the browser, which handles timeouts, proxies and TLS on its own
+    #[cfg(target_arch = "wasm32")]
+    fn client_builder(_config: &ModelConfiguration) -> crate::Result<reqwest::ClientBuilder> {
+        Ok(reqwest::ClientBuilder::new())
+    }
+
+    /// Sets the hook that supplies a new API key once the API rejects the current one with `401 Unauthorized`.
+    /// The rejected request is then retried once with the new key.
+    ///
+    /// The new key is shared with all clones of this client, including the ones owned by conversations.
+    /// Ignored by clients constructed with an auth provider.
+    pub fn with_credential_refresh<R: CredentialRefresh + 'static>(mut self, refresh: R) -> Self {
+        self.credential_refresh = Some(Arc::new(refresh));
+        self
+    }
+
+    /// Registers an interceptor, that is called around each request sent by this client and its clones created afterwards.
+    /// Interceptors are called in the order they were registered.
+    pub fn with_interceptor<I: Interceptor + 'static>(mut self, interceptor: I) -> Self {
+        self.interceptors.push(Arc::new(interceptor));
+        self
+    }
+
+    /// Returns the requests and token usage accumulated by this client and its clones so far.
+    ///
+    /// Usage is only accumulated when `track_usage` is enabled in the configuration. Token usage is known from
+    /// completions, responses, and streams with `include_stream_usage` enabled
+    pub fn usage_snapshot(&self) -> UsageSnapshot {
+        *self.usage.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Resets the accumulated usage to zero, returning the usage accumulated before, e.g. to report it periodically
+    pub fn reset_usage(&self) -> UsageSnapshot {
+        std::mem::take(&mut *self.usage.lock().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Adds the token usage of a completion to the accumulated usage, if tracked
+    pub(crate) fn record_usage(&self, usage: &TokenUsage) {
+        if self.config.track_usage {
+            record_usage(&self.usage, usage);
+        }
     }
+
+    /// Counts a request sent to the API, if tracked
+    fn record_request(&self) {
+        if self.config.track_usage {
+            self.usage
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .requests += 1;
+        }
+    }
+
     /// Restores a conversation from local conversation JSON file.
     /// The conversation file can originally be saved using the [`Conversation::save_history_json()`].
-    #[cfg(feature = "json")]
+    #[cfg(all(feature = "tokio", feature = "json", not(target_arch = "wasm32")))]
     pub async fn restore_conversation_json<P: AsRef<Path>>(
         &self,
         file: P,
@@ -94,15 +315,28 @@ impl ChatGPT {
         let mut file = File::open(path).await?;
         let mut buf = String::new();
         file.read_to_string(&mut buf).await?;
-        Ok(Conversation::new_with_history(
-            self.clone(),
-            serde_json::from_str(&buf)?,
-        ))
+        self.restore_conversation_from_str(&buf)
+    }
+
+    /// Restores a conversation from a JSON string without touching the filesystem.
+    /// The string can originally be produced using the [`Conversation::history_to_json_string()`].
+    #[cfg(feature = "json")]
+    pub fn restore_conversation_from_str(&self, json: &str) -> crate::Result<Conversation> {
+        let saved: SavedConversation = serde_json::from_str(json)?;
+        Ok(saved.into_conversation(self.clone()))
+    }
+
+    /// Restores a conversation from JSON bytes without touching the filesystem.
+    /// The bytes can originally be produced using the [`Conversation::to_json_bytes()`].
+    #[cfg(feature = "json")]
+    pub fn restore_conversation_from_json_bytes(&self, json: &[u8]) -> crate::Result<Conversation> {
+        let saved: SavedConversation = serde_json::from_slice(json)?;
+        Ok(saved.into_conversation(self.clone()))
     }
 
     /// Restores a conversation from local conversation postcard file.
     /// The conversation file can originally be saved using the [`Conversation::save_history_postcard()`].
-    #[cfg(feature = "postcard")]
+    #[cfg(all(feature = "tokio", feature = "postcard", not(target_arch = "wasm32")))]
     pub async fn restore_conversation_postcard<P: AsRef<Path>>(
         &self,
         file: P,
@@ -116,10 +350,14 @@ impl ChatGPT {
         let mut file = File::open(path).await?;
         let mut buf = Vec::new();
         file.read_to_end(&mut buf).await?;
-        Ok(Conversation::new_with_history(
-            self.clone(),
-            postcard::from_bytes(&buf)?,
-        ))
+        self.restore_conversation_from_bytes(&buf)
+    }
+
+    /// Restores a conversation from postcard bytes without touching the filesystem.
+    /// The bytes can originally be produced using the [`Conversation::to_postcard_bytes()`].
+    #[cfg(feature = "postcard")]
+    pub fn restore_conversation_from_bytes(&self, bytes: &[u8]) -> crate::Result<Conversation> {
+        Ok(SavedConversation::from_postcard(bytes)?.into_conversation(self.clone()))
     }
 
     /// Starts a new conversation with a default starting message.
@@ -139,41 +377,475 @@ impl ChatGPT {
         Conversation::new(self.clone(), direction_message.into())
     }
 
+    /// Starts a new conversation with a specified developer message, that replaces the system message for o-series reasoning models.
+    ///
+    /// Conversations record message history.
+    pub fn new_conversation_developer<S: Into<String>>(
+        &self,
+        developer_message: S,
+    ) -> Conversation {
+        Conversation::new_with_history(
+            self.clone(),
+            vec![ChatMessage::new(Role::Developer, developer_message)],
+        )
+    }
+
     /// Explicitly sends whole message history to the API.
     ///
     /// In most cases, if you would like to store message history, you should be looking at the [`Conversation`] struct, and
     /// [`Self::new_conversation()`] and [`Self::new_conversation_directed()`]
-    pub async fn send_history(
+    ///
+    /// History stored in a `VecDeque` can be sent with [`VecDeque::make_contiguous()`](std::collections::VecDeque::make_contiguous).
+    pub async fn send_history(&self, history: &[ChatMessage]) -> crate::Result<CompletionResponse> {
+        self.send_request(&self.completion_request(history)).await
+    }
+
+    /// Explicitly sends whole message history to the API, overriding the response format of the configuration,
+    /// e.g. with [`ResponseFormat::JsonObject`] to enable JSON mode.
+    pub async fn send_history_with_format(
         &self,
-        history: &Vec<ChatMessage>,
+        history: &[ChatMessage],
+        response_format: ResponseFormat,
     ) -> crate::Result<CompletionResponse> {
-        let response: ServerResponse = self
-            .client
-            .post(self.config.api_url.clone())
-            .json(&CompletionRequest {
-                model: self.config.engine.as_ref(),
-                messages: history,
-                stream: false,
-                temperature: self.config.temperature,
-                top_p: self.config.top_p,
-                max_tokens: self.config.max_tokens,
-                frequency_penalty: self.config.frequency_penalty,
-                presence_penalty: self.config.presence_penalty,
-                reply_count: self.config.reply_count,
-                #[cfg(feature = "functions")]
-                functions: &Vec::new(),
-            })
-            .send()
-            .await?
-            .json()
-            .await?;
+        let mut request = self.completion_request(history);
+        request.response_format = Some(response_format);
+        self.send_request(&request).await
+    }
+
+    /// Explicitly sends whole message history to the API, overriding the stop sequences of the configuration.
+    pub async fn send_history_with_stop<S: Into<String>, I: IntoIterator<Item = S>>(
+        &self,
+        history: &[ChatMessage],
+        stop: I,
+    ) -> crate::Result<CompletionResponse> {
+        let mut request = self.completion_request(history);
+        request.stop = stop.into_iter().map(Into::into).collect();
+        self.send_request(&request).await
+    }
+
+    /// Explicitly sends whole message history to the API, overriding the configuration with the request options,
+    /// e.g. to allow a longer timeout for a single long running request.
+    pub async fn send_history_with_options(
+        &self,
+        history: &[ChatMessage],
+        options: &RequestOptions,
+    ) -> crate::Result<CompletionResponse> {
+        self.send_request_with_options(&self.completion_request(history), options)
+            .await
+    }
+
+    /// Sends a pre-constructed request to the API.
+    ///
+    /// The request is sent as is, ignoring the configuration of this client, except for the API URL and timeout.
+    /// The `stream` flag of the request is ignored.
+    pub async fn send_request(
+        &self,
+        request: &CompletionRequest<'_>,
+    ) -> crate::Result<CompletionResponse> {
+        self.send_request_with_options(request, &RequestOptions::default())
+            .await
+    }
+
+    /// Sends a pre-constructed request to the API, overriding the configuration with the request options.
+    /// See [`Self::send_request()`] for details.
+    pub async fn send_request_with_options(
+        &self,
+        request: &CompletionRequest<'_>,
+        options: &RequestOptions,
+    ) -> crate::Result<CompletionResponse> {
+        let response = self.execute(request, false, options).await?;
+        let rate_limit = rate_limit_info(response.headers());
+        let response: ServerResponse = parse_json_response(response).await?;
         match response {
             ServerResponse::Error { error } => Err(crate::err::Error::BackendError {
                 message: error.message,
                 error_type: error.error_type,
             }),
-            ServerResponse::Completion(completion) => Ok(completion),
+            ServerResponse::Completion(mut completion) => {
+                self.record_usage(&completion.usage);
+                completion.rate_limit = rate_limit;
+                Ok(completion)
+            }
+        }
+    }
+
+    /// Sends a pre-constructed request to the API and returns the response as stream. **Stream will be empty** if
+    /// any errors are returned from the server.
+    ///
+    /// The request is sent as is, ignoring the configuration of this client, except for the API URL, timeout and
+    /// stream length limit. The `stream` flag of the request is ignored.
+    ///
+    /// Requires the `streams` crate feature
+    #[cfg(feature = "streams")]
+    pub async fn send_request_streaming(
+        &self,
+        request: &CompletionRequest<'_>,
+    ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
+        self.send_request_streaming_with_options(request, &RequestOptions::default())
+            .await
+    }
+
+    /// Sends a pre-constructed request to the API, overriding the configuration with the request options,
+    /// and returns the response as stream. See [`Self::send_request_streaming()`] for details.
+    ///
+    /// Requires the `streams` crate feature
+    #[cfg(feature = "streams")]
+    pub async fn send_request_streaming_with_options(
+        &self,
+        request: &CompletionRequest<'_>,
+        options: &RequestOptions,
+    ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
+        use futures_util::StreamExt;
+
+        let response = self.execute(request, true, options).await?;
+        let stream = self.process_streaming_response(response)?;
+        let resume =
+            (self.config.stream_resume_attempts > 0 && request.reply_count == 1).then(|| {
+                ResumeState {
+                    client: self.clone(),
+                    request: request.to_static(),
+                    options: *options,
+                    attempts: self.config.stream_resume_attempts,
+                    partial: String::new(),
+                    tool_call: false,
+                    resumed: false,
+                }
+            });
+
+        // re-sends the request with the partial response as an assistant prefix whenever the stream drops
+        let stream = Box::pin(futures::stream::unfold(
+            (Some(stream), resume),
+            |(mut stream, mut resume)| async move {
+                loop {
+                    let item = stream.as_mut()?.next().await?;
+                    let Some(state) = resume.as_mut() else {
+                        return Some((item, (stream, resume)));
+                    };
+                    match &item {
+                        // the resumed response continues the message that has already begun
+                        Ok(ResponseChunk::BeginResponse { .. }) if state.resumed => continue,
+                        Ok(ResponseChunk::Content { delta, .. }) => state.partial.push_str(delta),
+                        Ok(ResponseChunk::ToolCallBegin { .. }) => state.tool_call = true,
+                        Err(
+                            crate::err::Error::ClientError(_) | crate::err::Error::StreamStalled,
+                        ) if state.attempts > 0 && !state.tool_call => {
+                            state.attempts -= 1;
+                            state.resumed = true;
+                            let mut request = state.request.borrowed_with_stream(true);
+                            let mut messages = request.messages.into_owned();
+                            messages
+                                .push(ChatMessage::new(Role::Assistant, state.partial.as_str()));
+                            request.messages = messages.into();
+                            return match state
+                                .client
+                                .execute(&request, true, &state.options)
+                                .await
+                                .and_then(|response| {
+                                    state.client.process_streaming_response(response)
+                                }) {
+                                Ok(resumed) => {
+                                    Some((Ok(ResponseChunk::Resumed), (Some(resumed), resume)))
+                                }
+                                Err(err) => Some((Err(err), (None, resume))),
+                            };
+                        }
+                        _ => {}
+                    }
+                    return Some((item, (stream, resume)));
+                }
+            },
+        ));
+        // limits the resumed responses together, so the limit does not start over on every resume
+        Ok(limit_stream_length(stream, self.config.stream_length_limit))
+    }
+
+    /// Starts an authenticated request to the URL
+    async fn authorized(
+        &self,
+        method: reqwest::Method,
+        url: url::Url,
+    ) -> crate::Result<reqwest::RequestBuilder> {
+        let builder = self.client.request(method, url);
+        Ok(match &self.credentials {
+            Credentials::ApiKey(api_key) => {
+                let api_key = api_key.read().unwrap_or_else(PoisonError::into_inner);
+                match self.config.backend {
+                    ApiBackend::OpenAi => builder.header(
+                        AUTHORIZATION,
+                        HeaderValue::from_bytes(format!("Bearer {api_key}").as_bytes())?,
+                    ),
+                    ApiBackend::Azure { .. } => {
+                        builder.header("api-key", HeaderValue::from_bytes(api_key.as_bytes())?)
+                    }
+                }
+            }
+            Credentials::Provider(provider) => builder.bearer_auth(provider.token().await?),
+        })
+    }
+
+    /// Sends the request to the endpoint, see [`Self::execute_json()`]
+    async fn execute(
+        &self,
+        request: &CompletionRequest<'_>,
+        stream: bool,
+        options: &RequestOptions,
+    ) -> crate::Result<Response> {
+        #[cfg(feature = "models")]
+        if self.config.validate_model_capabilities {
+            if let Some(capabilities) = crate::models::ModelCapabilities::of(&request.model) {
+                capabilities.check(request)?;
+            }
+        }
+        #[allow(unused_mut)]
+        let mut body = request.borrowed_with_stream(stream);
+        #[cfg(all(feature = "tokenizer", feature = "models"))]
+        if let Some(policy) = self.config.context_window_policy {
+            if let Some(messages) = crate::tokenizer::fit_context_window(request, policy)? {
+                body.messages = std::borrow::Cow::Owned(messages);
+            }
+        }
+        self.execute_json(self.config.endpoint_url()?, &body, stream, options)
+            .await
+    }
+
+    /// Sends the JSON body to the URL, refreshing the API key and retrying once if it is rejected.
+    ///
+    /// Transient failures are retried according to the retry policy of the configuration.
+    pub(crate) async fn execute_json<B: serde::Serialize + Sync + ?Sized>(
+        &self,
+        url: url::Url,
+        body: &B,
+        stream: bool,
+        options: &RequestOptions,
+    ) -> crate::Result<Response> {
+        let with_body = |builder: reqwest::RequestBuilder| builder.json(body);
+        self.execute_with(reqwest::Method::POST, url, with_body, stream, options)
+            .await
+    }
+
+    /// Sends the request built by `with_body`, see [`Self::execute_json()`]
+    async fn execute_with<F>(
+        &self,
+        method: reqwest::Method,
+        url: url::Url,
+        with_body: F,
+        stream: bool,
+        options: &RequestOptions,
+    ) -> crate::Result<Response>
+    where
+        F: Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder + Sync,
+    {
+        self.record_request();
+        let mut attempt = 1;
+        let mut refreshed = false;
+        loop {
+            self.check_circuit()?;
+            let builder = self.authorized(method.clone(), url.clone()).await?;
+            let mut builder = with_body(builder);
+            let timeout = options.timeout.or_else(|| {
+                (!stream && self.config.read_timeout.is_some()).then_some(self.config.timeout)
+            });
+            if let Some(timeout) = timeout {
+                builder = builder.timeout(timeout);
+            }
+            let result = self.send(builder).await;
+            self.record_outcome(match &result {
+                Ok(response) => !response.status().is_server_error(),
+                Err(_) => false,
+            });
+            let policy = self
+                .config
+                .retry
+                .as_ref()
+                .filter(|policy| attempt < policy.max_attempts);
+            let result = match result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    let retry_after = retry_after(response.headers());
+                    let error = rate_limit_error(response, retry_after).await;
+                    // waits longer than the policy allows are left to the caller
+                    match policy.filter(
+                        |policy| !matches!(retry_after, Some(wait) if wait > policy.max_delay),
+                    ) {
+                        Some(policy) if matches!(error, crate::err::Error::RateLimited { .. }) => {
+                            sleep(retry_after.unwrap_or_else(|| policy.delay(attempt))).await;
+                            attempt += 1;
+                            continue;
+                        }
+                        _ => return Err(error),
+                    }
+                }
+                result => result,
+            };
+            let delay = match &result {
+                Ok(response) if response.status() == StatusCode::UNAUTHORIZED && !refreshed => {
+                    if self.refresh_key().await? {
+                        refreshed = true;
+                        continue;
+                    }
+                    None
+                }
+                Ok(response) => policy
+                    .filter(|policy| policy.retries_status(response.status().as_u16()))
+                    .map(|policy| policy.delay(attempt)),
+                Err(crate::err::Error::ClientError(err)) if is_transient(err) => {
+                    policy.map(|policy| policy.delay(attempt))
+                }
+                Err(_) => None,
+            };
+            match delay {
+                Some(delay) => {
+                    sleep(delay).await;
+                    attempt += 1;
+                }
+                None => return result,
+            }
+        }
+    }
+
+    /// Sends a request to a beta endpoint of the API, e.g. `threads`, and deserializes the response
+    #[cfg(any(feature = "assistants", feature = "vector_stores"))]
+    pub(crate) async fn send_beta_request<B, T>(
+        &self,
+        method: reqwest::Method,
+        url: url::Url,
+        body: Option<&B>,
+    ) -> crate::Result<T>
+    where
+        B: serde::Serialize + Sync + ?Sized,
+        T: DeserializeOwned,
+    {
+        let with_body = |builder: reqwest::RequestBuilder| {
+            let builder = builder.header("OpenAI-Beta", "assistants=v2");
+            match body {
+                Some(body) => builder.json(body),
+                None => builder,
+            }
+        };
+        let response = self
+            .execute_with(method, url, with_body, false, &RequestOptions::default())
+            .await?;
+        parse_endpoint_response(response).await
+    }
+
+    /// Sends the multipart form to another endpoint of the API, e.g. `audio/translations`, and deserializes the
+    /// response.
+    ///
+    /// Forms may contain streamed files, so the request is not retried
+    #[cfg(any(feature = "audio", feature = "files"))]
+    pub(crate) async fn send_multipart_request<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        form: reqwest::multipart::Form,
+    ) -> crate::Result<T> {
+        self.check_circuit()?;
+        self.record_request();
+        let url = self.config.endpoint_url_for(path)?;
+        let builder = self.authorized(reqwest::Method::POST, url).await?;
+        let result = self.send(builder.multipart(form)).await;
+        self.record_outcome(match &result {
+            Ok(response) => !response.status().is_server_error(),
+            Err(_) => false,
+        });
+        parse_endpoint_response(result?).await
+    }
+
+    /// Downloads a file served by the API outside of its endpoints, e.g. a generated image.
+    ///
+    /// Such URLs are pre-signed, so the request is sent without credentials
+    #[cfg(all(feature = "images", feature = "tokio", not(target_arch = "wasm32")))]
+    pub(crate) async fn download(&self, url: &str) -> crate::Result<bytes::Bytes> {
+        let response = self.send(self.client.get(url)).await?;
+        Ok(response.error_for_status()?.bytes().await?)
+    }
+
+    /// Sends the request with the transport, passing it through the interceptors
+    async fn send(&self, builder: reqwest::RequestBuilder) -> crate::Result<Response> {
+        let mut request = builder.build()?;
+        if self.interceptors.is_empty() {
+            return self.transport.send(request).await;
+        }
+        // streamed bodies (e.g. multipart uploads) can not be inspected, and are sent as is
+        let streamed = request.body().is_some_and(|body| body.as_bytes().is_none());
+        let mut parts = RequestParts {
+            url: request.url().clone(),
+            headers: std::mem::take(request.headers_mut()),
+            body: request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(<[u8]>::to_vec)
+                .unwrap_or_default(),
+        };
+        for interceptor in &self.interceptors {
+            interceptor.on_request(&mut parts).await;
+        }
+        *request.url_mut() = parts.url;
+        *request.headers_mut() = parts.headers;
+        if !streamed {
+            *request.body_mut() = Some(parts.body.into());
+        }
+
+        let response = self.transport.send(request).await?;
+        let parts = ResponseParts {
+            url: response.url().clone(),
+            status: response.status(),
+            headers: response.headers().clone(),
+        };
+        for interceptor in &self.interceptors {
+            interceptor.on_response(&parts).await;
+        }
+        Ok(response)
+    }
+
+    /// Fails fast if the circuit breaker is open
+    fn check_circuit(&self) -> crate::Result<()> {
+        if self.config.circuit_breaker.is_none() {
+            return Ok(());
+        }
+        let circuit = self.circuit.lock().unwrap_or_else(PoisonError::into_inner);
+        match circuit.open_until {
+            Some(open_until) if open_until > Instant::now() => {
+                Err(crate::err::Error::CircuitOpen {
+                    retry_after: open_until - Instant::now(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Counts consecutive failures of the endpoint, opening the circuit breaker once the threshold is reached
+    fn record_outcome(&self, succeeded: bool) {
+        let Some(breaker) = &self.config.circuit_breaker else {
+            return;
+        };
+        let mut circuit = self.circuit.lock().unwrap_or_else(PoisonError::into_inner);
+        if succeeded {
+            *circuit = CircuitState::default();
+            return;
         }
+        circuit.consecutive_failures = circuit.consecutive_failures.saturating_add(1);
+        if circuit.consecutive_failures >= breaker.failure_threshold {
+            circuit.open_until = Some(Instant::now() + breaker.cooldown);
+        }
+    }
+
+    /// Requests a new API key from the credential refresh hook. Returns `true` if the key was replaced
+    async fn refresh_key(&self) -> crate::Result<bool> {
+        let (Credentials::ApiKey(api_key), Some(refresh)) =
+            (&self.credentials, &self.credential_refresh)
+        else {
+            return Ok(false);
+        };
+        let Some(new_key) = refresh.refresh_key().await? else {
+            return Ok(false);
+        };
+        HeaderValue::from_bytes(new_key.as_bytes())?;
+        *api_key.write().unwrap_or_else(PoisonError::into_inner) = new_key;
+        Ok(true)
+    }
+
+    /// Constructs a request for the message history with the configuration of this client
+    pub fn completion_request<'a>(&'a self, history: &'a [ChatMessage]) -> CompletionRequest<'a> {
+        CompletionRequest::from_config(&self.config, history)
     }
 
     /// Explicitly sends whole message history to the API and returns the response as stream. **Stream will be empty** if
@@ -186,28 +858,10 @@ impl ChatGPT {
     #[cfg(feature = "streams")]
     pub async fn send_history_streaming(
         &self,
-        history: &Vec<ChatMessage>,
+        history: &[ChatMessage],
     ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
-        let response = self
-            .client
-            .post(self.config.api_url.clone())
-            .json(&CompletionRequest {
-                model: self.config.engine.as_ref(),
-                stream: true,
-                messages: history,
-                temperature: self.config.temperature,
-                top_p: self.config.top_p,
-                max_tokens: self.config.max_tokens,
-                frequency_penalty: self.config.frequency_penalty,
-                presence_penalty: self.config.presence_penalty,
-                reply_count: self.config.reply_count,
-                #[cfg(feature = "functions")]
-                functions: &Vec::new(),
-            })
-            .send()
-            .await?;
-
-        Self::process_streaming_response(response)
+        self.send_request_streaming(&self.completion_request(history))
+            .await
     }
 
     /// Sends a single message to the API without preserving message history.
@@ -215,38 +869,49 @@ impl ChatGPT {
         &self,
         message: S,
     ) -> crate::Result<CompletionResponse> {
-        let response: ServerResponse = self
-            .client
-            .post(self.config.api_url.clone())
-            .json(&CompletionRequest {
-                model: self.config.engine.as_ref(),
-                messages: &vec![ChatMessage {
-                    role: Role::User,
-                    content: message.into(),
-                    #[cfg(feature = "functions")]
-                    function_call: None,
-                }],
-                stream: false,
-                temperature: self.config.temperature,
-                top_p: self.config.top_p,
-                max_tokens: self.config.max_tokens,
-                frequency_penalty: self.config.frequency_penalty,
-                presence_penalty: self.config.presence_penalty,
-                reply_count: self.config.reply_count,
-                #[cfg(feature = "functions")]
-                functions: &Vec::new(),
-            })
-            .send()
-            .await?
-            .json()
-            .await?;
-        match response {
-            ServerResponse::Error { error } => Err(crate::err::Error::BackendError {
-                message: error.message,
-                error_type: error.error_type,
-            }),
-            ServerResponse::Completion(completion) => Ok(completion),
-        }
+        let message = ChatMessage::new(Role::User, message);
+        self.send_request(&self.completion_request(std::slice::from_ref(&message)))
+            .await
+    }
+
+    /// Sends a single message to the API without preserving message history, overriding the configuration
+    /// with the request options. See [`Self::send_history_with_options()`] for details.
+    pub async fn send_message_with_options<S: Into<String>>(
+        &self,
+        message: S,
+        options: &RequestOptions,
+    ) -> crate::Result<CompletionResponse> {
+        let message = ChatMessage::new(Role::User, message);
+        self.send_history_with_options(std::slice::from_ref(&message), options)
+            .await
+    }
+
+    /// Sends a single message to the API without preserving message history, overriding the response format
+    /// of the configuration. See [`Self::send_history_with_format()`] for details.
+    pub async fn send_message_with_format<S: Into<String>>(
+        &self,
+        message: S,
+        response_format: ResponseFormat,
+    ) -> crate::Result<CompletionResponse> {
+        let message = ChatMessage::new(Role::User, message);
+        self.send_history_with_format(std::slice::from_ref(&message), response_format)
+            .await
+    }
+
+    /// Sends a single message to the API without preserving message history, overriding the stop sequences
+    /// of the configuration.
+    pub async fn send_message_with_stop<
+        M: Into<String>,
+        S: Into<String>,
+        I: IntoIterator<Item = S>,
+    >(
+        &self,
+        message: M,
+        stop: I,
+    ) -> crate::Result<CompletionResponse> {
+        let message = ChatMessage::new(Role::User, message);
+        self.send_history_with_stop(std::slice::from_ref(&message), stop)
+            .await
     }
 
     /// Sends a single message to the API, and returns the response as stream, without preserving message history. **Stream will be empty** if
@@ -258,118 +923,204 @@ impl ChatGPT {
         &self,
         message: S,
     ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
-        let response = self
-            .client
-            .post(self.config.api_url.clone())
-            .json(&CompletionRequest {
-                model: self.config.engine.as_ref(),
-                messages: &vec![ChatMessage {
-                    role: Role::User,
-                    content: message.into(),
-                    #[cfg(feature = "functions")]
-                    function_call: None,
-                }],
-                stream: true,
-                temperature: self.config.temperature,
-                top_p: self.config.top_p,
-                max_tokens: self.config.max_tokens,
-                frequency_penalty: self.config.frequency_penalty,
-                presence_penalty: self.config.presence_penalty,
-                reply_count: self.config.reply_count,
-                #[cfg(feature = "functions")]
-                functions: &Vec::new(),
-            })
-            .send()
-            .await?;
+        let message = ChatMessage::new(Role::User, message);
+        self.send_request_streaming(&self.completion_request(std::slice::from_ref(&message)))
+            .await
+    }
+
+    /// Explicitly sends whole message history to the API, writing the content of the reply into the writer
+    /// (e.g. a file, socket or stdout) as it is streamed, and returns the assembled response.
+    ///
+    /// Only the content of the first message choice is written. The writer is flushed after each piece of content.
+    ///
+    /// Requires the `streams` and `tokio` crate features
+    #[cfg(all(feature = "streams", feature = "tokio", not(target_arch = "wasm32")))]
+    pub async fn stream_history_to_writer<W: tokio::io::AsyncWrite + Unpin + ?Sized>(
+        &self,
+        history: &[ChatMessage],
+        writer: &mut W,
+    ) -> crate::Result<CompletionResponse> {
+        use futures_util::StreamExt;
+        use tokio::io::AsyncWriteExt;
 
-        Self::process_streaming_response(response)
+        let mut stream = self.send_history_streaming(history).await?;
+        let mut accumulator = crate::types::ResponseAccumulator::default();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk?;
+            if let ResponseChunk::Content {
+                delta,
+                response_index: 0,
+            } = &chunk
+            {
+                writer.write_all(delta.as_bytes()).await?;
+                writer.flush().await?;
+            }
+            accumulator.push(&chunk);
+        }
+        Ok(accumulator.finish())
+    }
+
+    /// Sends a single message to the API without preserving message history, writing the content of the reply
+    /// into the writer as it is streamed. See [`Self::stream_history_to_writer()`] for details.
+    ///
+    /// Requires the `streams` and `tokio` crate features
+    #[cfg(all(feature = "streams", feature = "tokio", not(target_arch = "wasm32")))]
+    pub async fn stream_message_to_writer<
+        S: Into<String>,
+        W: tokio::io::AsyncWrite + Unpin + ?Sized,
+    >(
+        &self,
+        message: S,
+        writer: &mut W,
+    ) -> crate::Result<CompletionResponse> {
+        let message = ChatMessage::new(Role::User, message);
+        self.stream_history_to_writer(std::slice::from_ref(&message), writer)
+            .await
+    }
+
+    /// Explicitly sends whole message history to the API, and forwards the streamed response into a channel
+    /// from a spawned task, for consumers that do not poll streams themselves, e.g. GUI event loops.
+    ///
+    /// The channel holds up to 64 chunks. Once the receiver is dropped, the task stops and aborts the request
+    /// on the next chunk.
+    /// Errors of the initial request are returned directly, later errors are sent through the channel.
+    ///
+    /// Requires the `channel` crate feature, and must be called within a Tokio runtime. Not available on WASM
+    #[cfg(all(feature = "channel", not(target_arch = "wasm32")))]
+    pub async fn send_history_streaming_channel(
+        &self,
+        history: &[ChatMessage],
+    ) -> crate::Result<(
+        tokio::sync::mpsc::Receiver<crate::Result<ResponseChunk>>,
+        tokio::task::JoinHandle<()>,
+    )> {
+        use futures_util::StreamExt;
+
+        let mut stream = self.send_history_streaming(history).await?;
+        let (sender, receiver) = tokio::sync::mpsc::channel(64);
+        let handle = tokio::spawn(async move {
+            while let Some(chunk) = stream.next().await {
+                if sender.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok((receiver, handle))
+    }
+
+    /// Sends a single message to the API without preserving message history, and forwards the streamed response
+    /// into a channel. See [`Self::send_history_streaming_channel()`] for details.
+    ///
+    /// Requires the `channel` crate feature, and must be called within a Tokio runtime. Not available on WASM
+    #[cfg(all(feature = "channel", not(target_arch = "wasm32")))]
+    pub async fn send_message_streaming_channel<S: Into<String>>(
+        &self,
+        message: S,
+    ) -> crate::Result<(
+        tokio::sync::mpsc::Receiver<crate::Result<ResponseChunk>>,
+        tokio::task::JoinHandle<()>,
+    )> {
+        let message = ChatMessage::new(Role::User, message);
+        self.send_history_streaming_channel(std::slice::from_ref(&message))
+            .await
     }
 
     #[cfg(feature = "streams")]
     fn process_streaming_response(
+        &self,
+        response: Response,
+    ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
+        use futures_util::StreamExt;
+
+        let usage = self.config.track_usage.then(|| self.usage.clone());
+        Self::parse_streaming_response(response)
+            .map(|stream| {
+                stream.inspect(move |chunk| {
+                    if let (Some(tracked), Ok(ResponseChunk::Usage(usage))) = (&usage, chunk) {
+                        record_usage(tracked, usage);
+                    }
+                })
+            })
+            .map(|stream| idle_timeout_stream(stream, self.config.stream_idle_timeout))
+    }
+
+    #[cfg(feature = "streams")]
+    fn parse_streaming_response(
         response: Response,
     ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
-        use core::str;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
 
+        use eventsource_stream::{EventStreamError, Eventsource};
         use futures_util::StreamExt;
 
+        let rate_limit = rate_limit_info(response.headers());
         // also handles errors
         response
             .error_for_status()
             .map(|response| response.bytes_stream())
             .map(|stream| {
-                let mut unparsed = "".to_string();
-                stream.map(move |part| {
-                    let unwrapped_bytes = match part {
-                        Ok(received_bytes) => received_bytes,
-                        Err(err) => {
-                            return vec![crate::Result::Err(
-                                crate::err::Error::ClientError(err),
-                            )]
-                        }
-                    };
-                    let parsed_bytes = match str::from_utf8(&unwrapped_bytes) {
-                        Ok(parsed_bytes) => parsed_bytes,
-                        Err(parse_error) => {
-                            return vec![crate::Result::Err(
-                                crate::err::Error::ParsingError(format!("{}", parse_error)),
-                            )]
-                        }
-                    };
-                    let mut unparsed_for_iteration = unparsed.clone();
-                    let mut content_to_iterate = parsed_bytes;
-                    if !unparsed.is_empty() {
-                        unparsed_for_iteration += content_to_iterate;
-                        content_to_iterate = &unparsed_for_iteration;
-                        unparsed = "".to_string();
-                    }
-                    let mut response_chunks: Vec<ResponseChunk> = vec![];
-                    for chunk in content_to_iterate.split_inclusive("\n\n").filter_map(|line| line.strip_prefix("data: ")) {
-                        if chunk.is_empty() {
-                            continue;
-                        }
-                        let parsed_chunk = if let Some(data) = chunk.strip_suffix("\n\n") {
-                            if data == "[DONE]" {
-                                ResponseChunk::Done
-                            } else {
-                            let parsed_data: InboundResponseChunk = serde_json::from_str(chunk)
-                                .unwrap_or_else(|_| {
-                                    panic!("Invalid inbound streaming response payload: {}. Total err: {:#?}", chunk, unwrapped_bytes)
-                                });
-                            let choice = parsed_data.choices[0].to_owned();
-                            match choice.delta {
-                                InboundChunkPayload::AnnounceRoles { role } => {
-                                    ResponseChunk::BeginResponse {
-                                        role,
-                                        response_index: choice.index,
-                                    }
-                                }
-                                InboundChunkPayload::StreamContent { content } => {
-                                    ResponseChunk::Content {
-                                        delta: content,
-                                        response_index: choice.index,
+                // reused between events to avoid allocating a buffer for each of them
+                let mut json_buffer = Vec::new();
+                let mut parser = InboundChunkParser::default();
+                // set once the stream is done or failed, so that no `Done` chunk has to be synthesized
+                let done = Arc::new(AtomicBool::new(false));
+                let finished = done.clone();
+                // the rate limit state is known from the headers before any data arrives
+                let rate_limit =
+                    rate_limit.map(|info| vec![crate::Result::Ok(ResponseChunk::RateLimit(info))]);
+                futures::stream::iter(rate_limit)
+                    .chain(stream.eventsource().map(move |event| {
+                        let event = match event {
+                            Ok(event) => event,
+                            Err(err) => {
+                                done.store(true, Ordering::Relaxed);
+                                return vec![Err(match err {
+                                    EventStreamError::Transport(err) => {
+                                        crate::err::Error::ClientError(err)
                                     }
-                                }
-                                InboundChunkPayload::Close {} => ResponseChunk::CloseResponse {
-                                    response_index: choice.index,
-                                },
-                            }
+                                    err => crate::err::Error::ParsingError(err.to_string()),
+                                })];
                             }
-                        } else {
-                            unparsed = chunk.to_owned();
-                            break;
                         };
-                        response_chunks.push(parsed_chunk);
-                    }
-
-                    response_chunks
-                        .into_iter()
-                        .map(crate::Result::Ok)
-                        .collect::<Vec<crate::Result<ResponseChunk>>>()
-                })
-                .flat_map(|results| {
-                    futures::stream::iter(results)
-                })
+                        if event.data == "[DONE]" {
+                            done.store(true, Ordering::Relaxed);
+                            return vec![Ok(ResponseChunk::Done)];
+                        }
+                        // events named by gateways, e.g. `ping`, carry no completion chunks
+                        if !matches!(event.event.as_str(), "message" | "error")
+                            || event.data.is_empty()
+                        {
+                            return Vec::new();
+                        }
+                        json_buffer.clear();
+                        json_buffer.extend_from_slice(event.data.as_bytes());
+                        // malformed events are reported, and the following ones are still processed
+                        match parse_json_slice::<InboundResponseChunk>(&mut json_buffer) {
+                            Ok(InboundResponseChunk {
+                                error: Some(error), ..
+                            }) => vec![Err(crate::err::Error::BackendError {
+                                message: error.message,
+                                error_type: error.error_type,
+                            })],
+                            Ok(parsed_data) => {
+                                let mut response_chunks = Vec::new();
+                                parser.push_chunks(parsed_data, &mut response_chunks);
+                                response_chunks.into_iter().map(Ok).collect()
+                            }
+                            Err(err) => vec![Err(crate::err::Error::ParsingError(format!(
+                                "Invalid inbound streaming response payload: {}. Error: {err}",
+                                event.data
+                            )))],
+                        }
+                    }))
+                    .flat_map(futures::stream::iter)
+                    .map(Some)
+                    // some backends (e.g. llama.cpp) close the stream without sending `[DONE]`
+                    .chain(futures::stream::once(futures::future::lazy(move |_| {
+                        (!finished.load(Ordering::Relaxed)).then_some(Ok(ResponseChunk::Done))
+                    })))
+                    .filter_map(futures::future::ready)
             })
             .map_err(crate::err::Error::from)
     }
@@ -405,73 +1156,1617 @@ impl ChatGPT {
         message: S,
         baked_functions: Vec<serde_json::Value>,
     ) -> crate::Result<CompletionResponse> {
-        let response: ServerResponse = self
-            .client
-            .post(self.config.api_url.clone())
-            .json(&CompletionRequest {
-                model: self.config.engine.as_ref(),
-                messages: &vec![ChatMessage {
-                    role: Role::User,
-                    content: message.into(),
-                    #[cfg(feature = "functions")]
-                    function_call: None,
-                }],
-                stream: false,
-                temperature: self.config.temperature,
-                top_p: self.config.top_p,
-                frequency_penalty: self.config.frequency_penalty,
-                presence_penalty: self.config.presence_penalty,
-                reply_count: self.config.reply_count,
-                max_tokens: self.config.max_tokens,
-                #[cfg(feature = "functions")]
-                functions: &baked_functions,
-            })
-            .send()
-            .await?
-            .json()
-            .await?;
-
-        match response {
-            ServerResponse::Error { error } => Err(crate::err::Error::BackendError {
-                message: error.message,
-                error_type: error.error_type,
-            }),
-            ServerResponse::Completion(completion) => Ok(completion),
-        }
+        let message = ChatMessage::new(Role::User, message);
+        let mut request = self.completion_request(std::slice::from_ref(&message));
+        request.functions = baked_functions.into();
+        self.send_request(&request).await
     }
 
     /// Sends whole message history alongside with defined baked functions.
     #[cfg(feature = "functions")]
     pub async fn send_history_functions(
         &self,
-        history: &Vec<ChatMessage>,
-        functions: &Vec<serde_json::Value>,
+        history: &[ChatMessage],
+        functions: &[serde_json::Value],
     ) -> crate::Result<CompletionResponse> {
-        let response: ServerResponse = self
-            .client
-            .post(self.config.api_url.clone())
-            .json(&CompletionRequest {
-                model: self.config.engine.as_ref(),
-                messages: history,
-                stream: false,
-                temperature: self.config.temperature,
-                top_p: self.config.top_p,
-                frequency_penalty: self.config.frequency_penalty,
-                presence_penalty: self.config.presence_penalty,
-                reply_count: self.config.reply_count,
-                max_tokens: self.config.max_tokens,
-                functions,
-            })
-            .send()
-            .await?
-            .json()
-            .await?;
-        match response {
-            ServerResponse::Error { error } => Err(crate::err::Error::BackendError {
+        let mut request = self.completion_request(history);
+        request.functions = functions.into();
+        self.send_request(&request).await
+    }
+
+    /// Sends a message with specified tool descriptors. The model is then able to call these tools,
+    /// see [`ChatMessage::tool_calls`] and [`ChatMessage::tool_result()`].
+    ///
+    /// Tools replace the deprecated functions on newer models. Whether tools are called can be controlled with
+    /// [`ModelConfiguration::tool_choice`](crate::config::ModelConfiguration::tool_choice).
+    #[cfg(feature = "functions")]
+    pub async fn send_message_tools<S: Into<String>, A: FunctionArgument>(
+        &self,
+        message: S,
+        tools: Vec<ToolDescriptor<A>>,
+    ) -> crate::Result<CompletionResponse> {
+        self.send_message_tools_baked(
+            message,
+            tools
+                .into_iter()
+                .map(serde_json::to_value)
+                .collect::<serde_json::Result<Vec<serde_json::Value>>>()
+                .map_err(crate::err::Error::from)?,
+        )
+        .await
+    }
+
+    /// Sends a message with specified pre-baked tool descriptors. The model is then able to call these tools.
+    #[cfg(feature = "functions")]
+    pub async fn send_message_tools_baked<S: Into<String>>(
+        &self,
+        message: S,
+        baked_tools: Vec<serde_json::Value>,
+    ) -> crate::Result<CompletionResponse> {
+        let message = ChatMessage::new(Role::User, message);
+        let mut request = self.completion_request(std::slice::from_ref(&message));
+        request.tools = baked_tools.into();
+        self.send_request(&request).await
+    }
+
+    /// Sends whole message history alongside with defined baked tools.
+    #[cfg(feature = "functions")]
+    pub async fn send_history_tools(
+        &self,
+        history: &[ChatMessage],
+        tools: &[serde_json::Value],
+    ) -> crate::Result<CompletionResponse> {
+        let mut request = self.completion_request(history);
+        request.tools = tools.into();
+        self.send_request(&request).await
+    }
+}
+
+/// Converts parsed inbound chunks into response chunks, keeping track of tool calls that are being streamed
+#[cfg(feature = "streams")]
+#[derive(Debug, Default)]
+struct InboundChunkParser {
+    /// Index of the tool call currently streamed in each response
+    open_tool_calls: Vec<Option<usize>>,
+}
+
+#[cfg(feature = "streams")]
+impl InboundChunkParser {
+    /// Converts a parsed inbound chunk into response chunks.
+    ///
+    /// Chunks without choices (e.g. the content filtering results sent first by Azure OpenAI) are tolerated.
+    fn push_chunks(
+        &mut self,
+        inbound: InboundResponseChunk,
+        response_chunks: &mut Vec<ResponseChunk>,
+    ) {
+        for filter in inbound.prompt_filter_results {
+            response_chunks.push(ResponseChunk::PromptFilter {
+                prompt_index: filter.prompt_index,
+                results: filter.content_filter_results,
+            });
+        }
+        for mut choice in inbound.choices {
+            let response_index = choice.index;
+            match choice.delta {
+                Some(InboundChunkPayload::AnnounceRoles {
+                    role,
+                    content,
+                    reasoning_content,
+                    tool_calls,
+                    function_call,
+                }) => {
+                    response_chunks.push(ResponseChunk::BeginResponse {
+                        role,
+                        response_index,
+                    });
+                    if let Some(delta) = non_empty(reasoning_content) {
+                        response_chunks.push(ResponseChunk::ReasoningContent {
+                            delta,
+                            response_index,
+                        });
+                    }
+                    // some backends (e.g. llama.cpp) send the first piece of content alongside the role
+                    if let Some(delta) = non_empty(content) {
+                        response_chunks.push(ResponseChunk::Content {
+                            delta,
+                            response_index,
+                        });
+                    }
+                    self.push_tool_calls(tool_calls, response_index, response_chunks);
+                    if let Some(function_call) = function_call {
+                        self.push_function_call(function_call, response_index, response_chunks);
+                    }
+                }
+                Some(InboundChunkPayload::StreamToolCalls { tool_calls }) => {
+                    self.push_tool_calls(tool_calls, response_index, response_chunks)
+                }
+                Some(InboundChunkPayload::StreamFunctionCall { function_call }) => {
+                    self.push_function_call(function_call, response_index, response_chunks)
+                }
+                Some(InboundChunkPayload::StreamReasoning {
+                    reasoning_content,
+                    content,
+                }) => {
+                    response_chunks.push(ResponseChunk::ReasoningContent {
+                        delta: reasoning_content,
+                        response_index,
+                    });
+                    if let Some(delta) = non_empty(content) {
+                        response_chunks.push(ResponseChunk::Content {
+                            delta,
+                            response_index,
+                        });
+                    }
+                }
+                Some(InboundChunkPayload::StreamContent { content }) => {
+                    response_chunks.push(ResponseChunk::Content {
+                        delta: content,
+                        response_index,
+                    })
+                }
+                Some(InboundChunkPayload::Close {}) => {
+                    self.end_tool_call(response_index, response_chunks);
+                    response_chunks.push(ResponseChunk::CloseResponse {
+                        response_index,
+                        finish_reason: choice.finish_reason.take(),
+                    });
+                }
+                None => {}
+            }
+            // some backends send the finish reason alongside the last piece of content
+            if let Some(finish_reason) = choice.finish_reason.take() {
+                self.end_tool_call(response_index, response_chunks);
+                response_chunks.push(ResponseChunk::CloseResponse {
+                    response_index,
+                    finish_reason: Some(finish_reason),
+                });
+            }
+            if let Some(results) = choice
+                .content_filter_results
+                .filter(|results| !results.0.is_empty())
+            {
+                response_chunks.push(ResponseChunk::ContentFilter {
+                    results,
+                    response_index,
+                });
+            }
+        }
+        if let Some(usage) = inbound.usage {
+            response_chunks.push(ResponseChunk::Usage(usage));
+        }
+    }
+
+    fn push_tool_calls(
+        &mut self,
+        tool_calls: Vec<InboundToolCallDelta>,
+        response_index: usize,
+        response_chunks: &mut Vec<ResponseChunk>,
+    ) {
+        for call in tool_calls {
+            self.push_call_delta(
+                call.id,
+                call.function,
+                call.index,
+                response_index,
+                response_chunks,
+            );
+        }
+    }
+
+    fn push_function_call(
+        &mut self,
+        function_call: InboundFunctionCallDelta,
+        response_index: usize,
+        response_chunks: &mut Vec<ResponseChunk>,
+    ) {
+        // a message contains at most one deprecated function call
+        self.push_call_delta(None, function_call, 0, response_index, response_chunks);
+    }
+
+    fn push_call_delta(
+        &mut self,
+        id: Option<String>,
+        function: InboundFunctionCallDelta,
+        call_index: usize,
+        response_index: usize,
+        response_chunks: &mut Vec<ResponseChunk>,
+    ) {
+        if id.is_some() || function.name.is_some() {
+            self.end_tool_call(response_index, response_chunks);
+            response_chunks.push(ResponseChunk::ToolCallBegin {
+                id,
+                name: function.name.unwrap_or_default(),
+                call_index,
+                response_index,
+            });
+            if self.open_tool_calls.len() <= response_index {
+                self.open_tool_calls.resize(response_index + 1, None);
+            }
+            self.open_tool_calls[response_index] = Some(call_index);
+        }
+        if let Some(delta) = non_empty(function.arguments) {
+            response_chunks.push(ResponseChunk::ToolCallArgumentsDelta {
+                delta,
+                call_index,
+                response_index,
+            });
+        }
+    }
+
+    fn end_tool_call(&mut self, response_index: usize, response_chunks: &mut Vec<ResponseChunk>) {
+        if let Some(call_index) = self
+            .open_tool_calls
+            .get_mut(response_index)
+            .and_then(Option::take)
+        {
+            response_chunks.push(ResponseChunk::ToolCallEnd {
+                call_index,
+                response_index,
+            });
+        }
+    }
+}
+
+#[cfg(feature = "streams")]
+fn non_empty(text: Option<String>) -> Option<String> {
+    text.filter(|text| !text.is_empty())
+}
+
+/// Whether the request failed before reaching the API, so that it can be retried
+fn is_transient(err: &reqwest::Error) -> bool {
+    #[cfg(not(target_arch = "wasm32"))]
+    return err.is_timeout() || err.is_connect();
+    #[cfg(target_arch = "wasm32")]
+    err.is_timeout()
+}
+
+/// Waits before retrying a request, using the timers of Tokio if it is enabled, or the browser on WASM
+pub(crate) async fn sleep(duration: Duration) {
+    #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+    tokio::time::sleep(duration).await;
+    #[cfg(not(any(feature = "tokio", target_arch = "wasm32")))]
+    futures_timer::Delay::new(duration).await;
+    #[cfg(target_arch = "wasm32")]
+    gloo_timers::future::sleep(duration).await;
+}
+
+/// How long the API asked to wait before retrying a rate limited request.
+///
+/// `Retry-After` is preferred, otherwise the latest of the `x-ratelimit-reset-*` headers is used
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok();
+    if let Some(millis) = header("retry-after-ms").and_then(|value| value.parse::<f64>().ok()) {
+        return Duration::try_from_secs_f64(millis / 1000.0).ok();
+    }
+    // HTTP dates are not supported, the backoff of the retry policy is used instead
+    if let Some(seconds) = header("retry-after").and_then(|value| value.parse::<f64>().ok()) {
+        return Duration::try_from_secs_f64(seconds).ok();
+    }
+    ["x-ratelimit-reset-requests", "x-ratelimit-reset-tokens"]
+        .into_iter()
+        .filter_map(|name| parse_reset_duration(header(name)?))
+        .max()
+}
+
+/// Collects the rate limit state from the `x-ratelimit-*` headers, if any of them are present
+fn rate_limit_info(headers: &HeaderMap) -> Option<RateLimitInfo> {
+    let header = |name: &str| headers.get(name)?.to_str().ok();
+    let count = |name: &str| header(name)?.trim().parse().ok();
+    let reset = |name: &str| parse_reset_duration(header(name)?);
+    let info = RateLimitInfo {
+        limit_requests: count("x-ratelimit-limit-requests"),
+        limit_tokens: count("x-ratelimit-limit-tokens"),
+        remaining_requests: count("x-ratelimit-remaining-requests"),
+        remaining_tokens: count("x-ratelimit-remaining-tokens"),
+        reset_requests: reset("x-ratelimit-reset-requests"),
+        reset_tokens: reset("x-ratelimit-reset-tokens"),
+    };
+    (info != RateLimitInfo::default()).then_some(info)
+}
+
+/// Parses durations of the `x-ratelimit-reset-*` headers, e.g. `1m30.5s` or `20ms`
+fn parse_reset_duration(value: &str) -> Option<Duration> {
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let number_end = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_end].parse().ok()?;
+        rest = &rest[number_end..];
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_end] {
+            "h" => 3600.0,
+            "m" => 60.0,
+            "s" | "" => 1.0,
+            "ms" => 1e-3,
+            "us" | "µs" => 1e-6,
+            "ns" => 1e-9,
+            _ => return None,
+        };
+        seconds += number * unit;
+        rest = &rest[unit_end..];
+    }
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+/// The error of a response rejected with status 429. Exhausted quota is not transient, so it is reported as the
+/// error returned by the API instead of [`Error::RateLimited`](crate::err::Error::RateLimited)
+async fn rate_limit_error(response: Response, retry_after: Option<Duration>) -> crate::err::Error {
+    #[derive(serde::Deserialize)]
+    struct ErrorBody {
+        error: crate::types::CompletionError,
+    }
+
+    match parse_json_response::<ErrorBody>(response).await {
+        Ok(ErrorBody { error })
+            if error.error_type == "insufficient_quota"
+                || error.code.as_deref() == Some("insufficient_quota") =>
+        {
+            crate::err::Error::BackendError {
                 message: error.message,
                 error_type: error.error_type,
+            }
+        }
+        _ => crate::err::Error::RateLimited { retry_after },
+    }
+}
+
+/// Deserializes the JSON body of a response, with SIMD acceleration if the `simd-json` feature is enabled
+async fn parse_json_response<T: DeserializeOwned>(response: reqwest::Response) -> crate::Result<T> {
+    #[cfg(feature = "simd-json")]
+    {
+        let mut body = response.bytes().await?.to_vec();
+        Ok(simd_json::serde::from_slice(&mut body)?)
+    }
+    #[cfg(not(feature = "simd-json"))]
+    Ok(response.json().await?)
+}
+
+/// Deserializes JSON from the buffer, with SIMD acceleration if the `simd-json` feature is enabled.
+/// The buffer contents are clobbered in the process
+#[cfg(feature = "streams")]
+fn parse_json_slice<T: DeserializeOwned>(buffer: &mut [u8]) -> crate::Result<T> {
+    #[cfg(feature = "simd-json")]
+    return Ok(simd_json::serde::from_slice(buffer)?);
+    #[cfg(not(feature = "simd-json"))]
+    Ok(serde_json::from_slice(buffer)?)
+}
+
+/// Adds the token usage of a completion to the accumulated usage
+fn record_usage(tracked: &Mutex<UsageSnapshot>, usage: &TokenUsage) {
+    tracked
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .add_usage(usage);
+}
+
+/// Requests to the endpoints other than chat completions. Not every endpoint uses all of them
+#[cfg(feature = "endpoints")]
+#[allow(dead_code)]
+impl ChatGPT {
+    /// Sends the JSON body to another endpoint of the API, e.g. `moderations`, and deserializes the response
+    pub(crate) async fn send_endpoint_request<B, T>(&self, path: &str, body: &B) -> crate::Result<T>
+    where
+        B: serde::Serialize + Sync + ?Sized,
+        T: DeserializeOwned,
+    {
+        let url = self.config.endpoint_url_for(path)?;
+        let response = self
+            .execute_json(url, body, false, &RequestOptions::default())
+            .await?;
+        parse_endpoint_response(response).await
+    }
+
+    /// Sends a request without a body to another endpoint of the API, e.g. `files`, and returns the raw response
+    pub(crate) async fn fetch_endpoint(
+        &self,
+        method: reqwest::Method,
+        url: url::Url,
+    ) -> crate::Result<Response> {
+        self.execute_with(
+            method,
+            url,
+            |builder| builder,
+            false,
+            &RequestOptions::default(),
+        )
+        .await
+    }
+
+    /// The URL of a resource of another endpoint of the API, e.g. `files/{id}/content`.
+    ///
+    /// The segments are percent-encoded, so identifiers can be passed as is
+    pub(crate) fn resource_url(&self, path: &str, segments: &[&str]) -> crate::Result<url::Url> {
+        let mut url = self.config.endpoint_url_for(path)?;
+        let invalid = crate::err::Error::ParsingError(format!("Invalid endpoint URL: {url}"));
+        url.path_segments_mut()
+            .map_err(|_| invalid)?
+            .extend(segments);
+        Ok(url)
+    }
+
+    /// The URL of a page of a list endpoint of the API, e.g. `fine_tuning/jobs`, listing the items after the item
+    /// with the id `after` if it is set, up to `limit` items
+    pub(crate) fn page_url(
+        &self,
+        path: &str,
+        segments: &[&str],
+        after: Option<&str>,
+        limit: Option<u32>,
+    ) -> crate::Result<url::Url> {
+        let mut url = self.resource_url(path, segments)?;
+        if let Some(after) = after {
+            url.query_pairs_mut().append_pair("after", after);
+        }
+        if let Some(limit) = limit {
+            url.query_pairs_mut()
+                .append_pair("limit", &limit.to_string());
+        }
+        Ok(url)
+    }
+}
+
+/// A response from an endpoint other than chat completions
+#[cfg(feature = "endpoints")]
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum EndpointResponse<T> {
+    Error {
+        error: crate::types::CompletionError,
+    },
+    Success(T),
+}
+
+/// A list of items returned by an endpoint
+#[cfg(feature = "endpoints")]
+#[allow(dead_code)]
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct List<T> {
+    pub(crate) data: Vec<T>,
+}
+
+/// The result of deleting a resource of an endpoint
+#[cfg(feature = "endpoints")]
+#[allow(dead_code)]
+#[derive(Debug, serde::Deserialize)]
+pub(crate) struct Deletion {
+    pub(crate) deleted: bool,
+}
+
+/// Deserializes the response of an endpoint other than chat completions, failing with the error it returned
+#[cfg(feature = "endpoints")]
+pub(crate) async fn parse_endpoint_response<T: DeserializeOwned>(
+    response: Response,
+) -> crate::Result<T> {
+    match parse_json_response(response).await? {
+        EndpointResponse::Error { error } => Err(crate::err::Error::BackendError {
+            message: error.message,
+            error_type: error.error_type,
+        }),
+        EndpointResponse::Success(value) => Ok(value),
+    }
+}
+
+/// Reads the raw body of a successful response of an endpoint, failing with the error it returned otherwise
+#[cfg(feature = "files")]
+pub(crate) async fn endpoint_bytes(response: Response) -> crate::Result<bytes::Bytes> {
+    if response.status().is_success() {
+        return Ok(response.bytes().await?);
+    }
+    Err(endpoint_error(response).await)
+}
+
+/// The error returned by an endpoint with an unsuccessful response
+#[cfg(any(feature = "files", all(feature = "responses", feature = "streams")))]
+pub(crate) async fn endpoint_error(response: Response) -> crate::err::Error {
+    #[derive(serde::Deserialize)]
+    struct ErrorBody {
+        error: crate::types::CompletionError,
+    }
+
+    let status = response.status();
+    match parse_json_response::<ErrorBody>(response).await {
+        Ok(ErrorBody { error }) => crate::err::Error::BackendError {
+            message: error.message,
+            error_type: error.error_type,
+        },
+        Err(_) => crate::err::Error::BackendError {
+            message: format!("The endpoint responded with status {status}"),
+            error_type: String::new(),
+        },
+    }
+}
+
+/// The state of a stream that is resumed once it drops mid-response
+#[cfg(feature = "streams")]
+struct ResumeState {
+    client: ChatGPT,
+    request: CompletionRequest<'static>,
+    options: RequestOptions,
+    attempts: u32,
+    /// The content streamed so far, sent as an assistant prefix when resuming
+    partial: String,
+    tool_call: bool,
+    resumed: bool,
+}
+
+/// Ends the stream with [`Error::StreamStalled`](crate::err::Error::StreamStalled) once no chunk arrives
+/// within the timeout. The underlying response is dropped at that point, which aborts the request.
+#[cfg(feature = "streams")]
+pub(crate) fn idle_timeout_stream<T, S: Stream<Item = crate::Result<T>> + Unpin>(
+    stream: S,
+    timeout: Option<Duration>,
+) -> impl Stream<Item = crate::Result<T>> + Unpin {
+    use futures_util::StreamExt;
+    use std::future::Future;
+    use std::task::Poll;
+
+    let mut inner = Some(stream);
+    let mut timer = timeout.map(|timeout| Box::pin(sleep(timeout)));
+    futures_util::stream::poll_fn(move |cx| {
+        let Some(stream) = inner.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let item = match stream.poll_next_unpin(cx) {
+            Poll::Ready(item) => item,
+            Poll::Pending => {
+                if let Some(timer) = timer.as_mut() {
+                    if timer.as_mut().poll(cx).is_ready() {
+                        inner = None;
+                        return Poll::Ready(Some(Err(crate::err::Error::StreamStalled)));
+                    }
+                }
+                return Poll::Pending;
+            }
+        };
+        match (&item, timeout) {
+            (None, _) => inner = None,
+            (Some(_), Some(timeout)) => timer = Some(Box::pin(sleep(timeout))),
+            (Some(_), None) => {}
+        }
+        Poll::Ready(item)
+    })
+}
+
+/// Ends the stream with [`ResponseChunk::Truncated`] once any response exceeds the limit.
+/// The underlying response is dropped at that point, which aborts the request.
+#[cfg(feature = "streams")]
+fn limit_stream_length<S: Stream<Item = crate::Result<ResponseChunk>> + Unpin>(
+    stream: S,
+    limit: Option<StreamLengthLimit>,
+) -> LengthLimitedStream<S> {
+    LengthLimitedStream {
+        inner: Some(stream),
+        limit,
+        lengths: Vec::new(),
+        pending: None,
+    }
+}
+
+#[cfg(feature = "streams")]
+struct LengthLimitedStream<S> {
+    inner: Option<S>,
+    limit: Option<StreamLengthLimit>,
+    lengths: Vec<usize>,
+    pending: Option<ResponseChunk>,
+}
+
+#[cfg(feature = "streams")]
+impl<S: Stream<Item = crate::Result<ResponseChunk>> + Unpin> Stream for LengthLimitedStream<S> {
+    type Item = crate::Result<ResponseChunk>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Self::Item>> {
+        use std::task::Poll;
+
+        let this = &mut *self;
+        if let Some(chunk) = this.pending.take() {
+            return Poll::Ready(Some(Ok(chunk)));
+        }
+        let Some(inner) = this.inner.as_mut() else {
+            return Poll::Ready(None);
+        };
+        let item = match std::pin::Pin::new(inner).poll_next(cx) {
+            Poll::Ready(item) => item,
+            Poll::Pending => return Poll::Pending,
+        };
+        let (limit, delta, response_index) = match (this.limit, item) {
+            (
+                Some(limit),
+                Some(Ok(ResponseChunk::Content {
+                    delta,
+                    response_index,
+                })),
+            ) => (limit, delta, response_index),
+            (_, item) => return Poll::Ready(item),
+        };
+
+        if this.lengths.len() <= response_index {
+            this.lengths.resize(response_index + 1, 0);
+        }
+        let (max, length) = match limit {
+            StreamLengthLimit::Characters(max) => (max, delta.chars().count()),
+            StreamLengthLimit::Chunks(max) => (max, 1),
+        };
+        let accumulated = this.lengths[response_index] + length;
+        if accumulated <= max {
+            this.lengths[response_index] = accumulated;
+            return Poll::Ready(Some(Ok(ResponseChunk::Content {
+                delta,
+                response_index,
+            })));
+        }
+
+        // dropping the response aborts the request
+        this.inner = None;
+        let truncated = ResponseChunk::Truncated { response_index };
+        let remaining = max - this.lengths[response_index];
+        if remaining == 0 || matches!(limit, StreamLengthLimit::Chunks(_)) {
+            return Poll::Ready(Some(Ok(truncated)));
+        }
+        this.pending = Some(truncated);
+        Poll::Ready(Some(Ok(ResponseChunk::Content {
+            delta: delta.chars().take(remaining).collect(),
+            response_index,
+        })))
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "streams")]
+mod tests {
+    use futures_util::StreamExt;
+    use serde_json::json;
+
+    use crate::config::StreamLengthLimit;
+    use crate::transport::{HttpTransport, JsonTransport};
+    use crate::types::ResponseChunk;
+
+    /// Serves each connection to the returned endpoint with the response produced from the raw request
+    async fn mock_server<F>(respond: F) -> url::Url
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                serve_connection(socket, &respond).await;
+            }
+        });
+        url.parse().unwrap()
+    }
+
+    /// Reads the whole request from the connection, and writes the response produced from it
+    async fn serve_connection<S, F>(mut socket: S, respond: &F)
+    where
+        S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+        F: Fn(&str) -> String,
+    {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // the whole request has to be read, or closing the socket resets the connection
+        let mut request = Vec::new();
+        let mut buffer = [0; 4096];
+        loop {
+            let read = socket.read(&mut buffer).await.unwrap();
+            request.extend_from_slice(&buffer[..read]);
+            let text = String::from_utf8_lossy(&request);
+            let Some(header_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|line| {
+                    line.to_ascii_lowercase()
+                        .strip_prefix("content-length:")
+                        .map(|len| len.trim().parse::<usize>().unwrap())
+                })
+                .unwrap_or_default();
+            if read == 0 || request.len() >= header_end + 4 + content_length {
+                break;
+            }
+        }
+        let response = respond(&String::from_utf8_lossy(&request));
+        socket.write_all(response.as_bytes()).await.unwrap();
+    }
+
+    /// A raw HTTP response with a JSON body
+    fn http_response(status: &str, body: &serde_json::Value) -> String {
+        let body = body.to_string();
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn completion_body(content: &str) -> serde_json::Value {
+        json!({ "choices": [{ "index": 0, "message": { "role": "assistant", "content": content } }] })
+    }
+
+    struct PromptRefresh;
+
+    #[async_trait::async_trait]
+    impl crate::auth::CredentialRefresh for PromptRefresh {
+        async fn refresh_key(&self) -> crate::Result<Option<String>> {
+            Ok(Some("new-key".to_owned()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() -> crate::Result<()> {
+        let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let served = attempts.clone();
+        let api_url =
+            mock_server(
+                move |_| match served.fetch_add(1, std::sync::atomic::Ordering::Relaxed) {
+                    0 | 1 => http_response("503 Service Unavailable", &json!({})),
+                    _ => http_response("200 OK", &completion_body("Hi")),
+                },
+            )
+            .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            retry: Some(crate::config::RetryPolicy {
+                base_delay: std::time::Duration::from_millis(1),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+        assert_eq!(attempts.load(std::sync::atomic::Ordering::Relaxed), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_info() -> crate::Result<()> {
+        let api_url = mock_server(|request| {
+            let (content_type, body) = if request.contains("\"stream\":true") {
+                ("text/event-stream", "data: [DONE]\n\n".to_owned())
+            } else {
+                ("application/json", completion_body("Hi").to_string())
+            };
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {content_type}\r\nx-ratelimit-limit-requests: 500\r\n\
+                x-ratelimit-remaining-tokens: 1200\r\nx-ratelimit-reset-requests: 120ms\r\n\
+                Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let expected = crate::types::RateLimitInfo {
+            limit_requests: Some(500),
+            remaining_tokens: Some(1200),
+            reset_requests: Some(std::time::Duration::from_millis(120)),
+            ..Default::default()
+        };
+        let response = client.send_message("Hello").await?;
+        assert_eq!(response.rate_limit, Some(expected));
+
+        let chunks = client
+            .send_message_streaming("Hello")
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(
+            &chunks[..],
+            [Ok(ResponseChunk::RateLimit(info)), Ok(ResponseChunk::Done)] if *info == expected
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() -> crate::Result<()> {
+        let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let served = attempts.clone();
+        let api_url = mock_server(move |_| {
+            served.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            http_response(
+                "500 Internal Server Error",
+                &json!({ "error": { "message": "Internal error", "type": "server_error" } }),
+            )
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            circuit_breaker: Some(crate::config::CircuitBreakerConfig {
+                failure_threshold: 2,
+                ..Default::default()
             }),
-            ServerResponse::Completion(completion) => Ok(completion),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        for _ in 0..2 {
+            assert!(matches!(
+                client.send_message("Hello").await,
+                Err(crate::err::Error::BackendError { .. })
+            ));
+        }
+        assert!(matches!(
+            client.clone().send_message("Hello").await,
+            Err(crate::err::Error::CircuitOpen { .. })
+        ));
+        assert_eq!(attempts.load(std::sync::atomic::Ordering::Relaxed), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() -> crate::Result<()> {
+        // accepts connections and never replies
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            let mut sockets = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                sockets.push(socket);
+            }
+        });
+        let config = crate::config::ModelConfiguration {
+            api_url: api_url.parse().unwrap(),
+            use_system_proxy: false,
+            timeout: std::time::Duration::from_secs(300),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let options = crate::config::RequestOptions::default()
+            .with_timeout(std::time::Duration::from_millis(50));
+        match client.send_message_with_options("Hello", &options).await {
+            Err(crate::err::Error::ClientError(err)) => assert!(err.is_timeout()),
+            other => panic!("unexpected result: {other:?}"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout() -> crate::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        // streams chunks slowly, outliving the total timeout
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let api_url = format!(
+            "http://{}/v1/chat/completions",
+            listener.local_addr().unwrap()
+        );
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            serve_connection(&mut socket, &|_| {
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n"
+                    .to_owned()
+            })
+            .await;
+            let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": "Hi" } }] });
+            for event in [format!("data: {chunk}\n\n"), "data: [DONE]\n\n".to_owned()] {
+                tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+                socket.write_all(event.as_bytes()).await.unwrap();
+            }
+        });
+        let config = crate::config::ModelConfiguration {
+            api_url: api_url.parse().unwrap(),
+            use_system_proxy: false,
+            timeout: std::time::Duration::from_millis(150),
+            read_timeout: Some(std::time::Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let chunks = client
+            .send_message_streaming("Hello")
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(
+            &chunks[..],
+            [Ok(ResponseChunk::Content { delta, .. }), Ok(ResponseChunk::Done)] if delta == "Hi"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_resume() -> crate::Result<()> {
+        let chunk = |content: &str| {
+            let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": content } }] });
+            format!("data: {chunk}\n\n")
+        };
+        let api_url = mock_server(move |request| {
+            if request.contains(r#"{"role":"assistant","content":"Hel"}"#) {
+                let body = chunk("lo") + "data: [DONE]\n\n";
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\
+                    Connection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                // the connection drops before the promised body is sent
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: 1000\r\n\
+                    Connection: close\r\n\r\n{}",
+                    chunk("Hel")
+                )
+            }
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            stream_resume_attempts: 1,
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let chunks = client
+            .send_message_streaming("Hello")
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(
+            &chunks[..],
+            [
+                Ok(ResponseChunk::Content { delta: first, .. }),
+                Ok(ResponseChunk::Resumed),
+                Ok(ResponseChunk::Content { delta: second, .. }),
+                Ok(ResponseChunk::Done)
+            ] if first == "Hel" && second == "lo"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_length_limit_across_resume() -> crate::Result<()> {
+        let chunk = |content: &str| {
+            let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": content } }] });
+            format!("data: {chunk}\n\n")
+        };
+        let api_url = mock_server(move |request| {
+            if request.contains(r#"{"role":"assistant","content":"Hel"}"#) {
+                let body = chunk("lo") + "data: [DONE]\n\n";
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\
+                    Connection: close\r\n\r\n{body}",
+                    body.len()
+                )
+            } else {
+                // the connection drops before the promised body is sent
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: 1000\r\n\
+                    Connection: close\r\n\r\n{}",
+                    chunk("Hel")
+                )
+            }
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            stream_resume_attempts: 1,
+            stream_length_limit: Some(StreamLengthLimit::Characters(4)),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let chunks = client
+            .send_message_streaming("Hello")
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(
+            &chunks[..],
+            [
+                Ok(ResponseChunk::Content { delta: first, .. }),
+                Ok(ResponseChunk::Resumed),
+                Ok(ResponseChunk::Content { delta: second, .. }),
+                Ok(ResponseChunk::Truncated { response_index: 0 })
+            ] if first == "Hel" && second == "l"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_malformed_stream_event() -> crate::Result<()> {
+        let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": "Hi" } }] });
+        let body = format!("data: {{\"choices\": 1}}\n\ndata: {chunk}\n\ndata: [DONE]\n\n");
+        let response: reqwest::Response = http::Response::new(body).into();
+        let chunks = super::ChatGPT::parse_streaming_response(response)?
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(
+            &chunks[..],
+            [
+                Err(crate::err::Error::ParsingError(error)),
+                Ok(ResponseChunk::Content { delta, .. }),
+                Ok(ResponseChunk::Done)
+            ] if error.contains(r#"{"choices": 1}"#) && delta == "Hi"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_event_parsing() -> crate::Result<()> {
+        // keep-alive comments, CRLF delimiters, data split across lines and named events
+        let body = ": ping\r\n\r\n\
+            event: ping\r\ndata: {}\r\n\r\n\
+            data: {\"choices\": [{\"index\": 0,\r\ndata: \"delta\": {\"content\": \"Hi\"}}]}\r\n\r\n\
+            event: error\ndata: {\"error\": {\"message\": \"Overloaded\", \"type\": \"server_error\"}}\n\n\
+            data: [DONE]\n\n";
+        let response: reqwest::Response = http::Response::new(body).into();
+        let chunks = super::ChatGPT::parse_streaming_response(response)?
+            .collect::<Vec<_>>()
+            .await;
+        assert!(matches!(
+            &chunks[..],
+            [
+                Ok(ResponseChunk::Content { delta, .. }),
+                Err(crate::err::Error::BackendError { message, .. }),
+                Ok(ResponseChunk::Done)
+            ] if delta == "Hi" && message == "Overloaded"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_to_writer() -> crate::Result<()> {
+        let api_url = mock_server(|_| {
+            let chunk = |content: &str| {
+                let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": content } }] });
+                format!("data: {chunk}\n\n")
+            };
+            let body = chunk("Hel") + &chunk("lo") + "data: [DONE]\n\n";
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\
+                Connection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let mut output = Vec::new();
+        let response = client
+            .stream_message_to_writer("Hello", &mut output)
+            .await?;
+        assert_eq!(output, b"Hello");
+        assert_eq!(response.message().content, "Hello");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_streaming_channel() -> crate::Result<()> {
+        let api_url = mock_server(|_| {
+            let chunk = json!({ "choices": [{ "index": 0, "delta": { "content": "Hi" } }] });
+            let body = format!("data: {chunk}\n\ndata: [DONE]\n\n");
+            format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nContent-Length: {}\r\n\
+                Connection: close\r\n\r\n{body}",
+                body.len()
+            )
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let (mut receiver, handle) = client.send_message_streaming_channel("Hello").await?;
+        let mut chunks = Vec::new();
+        while let Some(chunk) = receiver.recv().await {
+            chunks.push(chunk?);
+        }
+        handle.await.unwrap();
+        assert!(matches!(
+            &chunks[..],
+            [ResponseChunk::Content { delta, .. }, ResponseChunk::Done] if delta == "Hi"
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stream_idle_timeout() {
+        let chunks = futures_util::stream::iter([Ok(ResponseChunk::Done)])
+            .chain(futures_util::stream::pending());
+        let stream = super::idle_timeout_stream(chunks, Some(std::time::Duration::from_millis(20)));
+        assert!(matches!(
+            &stream.collect::<Vec<_>>().await[..],
+            [
+                Ok(ResponseChunk::Done),
+                Err(crate::err::Error::StreamStalled)
+            ]
+        ));
+    }
+
+    #[test]
+    fn test_parse_reset_duration() {
+        use std::time::Duration;
+
+        use super::parse_reset_duration;
+
+        assert_eq!(
+            parse_reset_duration("20ms"),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(parse_reset_duration("1m30s"), Some(Duration::from_secs(90)));
+        assert_eq!(
+            parse_reset_duration("1.5s"),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse_reset_duration("soon"), None);
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited() -> crate::Result<()> {
+        let rate_limited = "HTTP/1.1 429 Too Many Requests\r\nRetry-After: 7\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+        let api_url = mock_server(move |_| rate_limited.to_owned()).await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        match client.send_message("Hello").await {
+            Err(crate::err::Error::RateLimited { retry_after }) => {
+                assert_eq!(retry_after, Some(std::time::Duration::from_secs(7)))
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+
+        let attempts = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let served = attempts.clone();
+        let api_url = mock_server(move |_| {
+            match served.fetch_add(1, std::sync::atomic::Ordering::Relaxed) {
+                0 => "HTTP/1.1 429 Too Many Requests\r\nx-ratelimit-reset-requests: 5ms\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_owned(),
+                _ => http_response("200 OK", &completion_body("Hi")),
+            }
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            retry: Some(Default::default()),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+
+        // exhausted quota is reported with the error of the API, and never retried
+        let served = attempts.clone();
+        let api_url = mock_server(move |_| {
+            served.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            http_response(
+                "429 Too Many Requests",
+                &json!({ "error": {
+                    "message": "You exceeded your current quota",
+                    "type": "insufficient_quota",
+                    "code": "insufficient_quota"
+                } }),
+            )
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            retry: Some(Default::default()),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        match client.send_message("Hello").await {
+            Err(crate::err::Error::BackendError {
+                message,
+                error_type,
+            }) => {
+                assert_eq!(message, "You exceeded your current quota");
+                assert_eq!(error_type, "insufficient_quota");
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+        assert_eq!(attempts.load(std::sync::atomic::Ordering::Relaxed), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credential_refresh() -> crate::Result<()> {
+        let api_url = mock_server(|request| {
+            if request.contains("Bearer new-key") {
+                http_response("200 OK", &completion_body("Hi"))
+            } else {
+                http_response(
+                    "401 Unauthorized",
+                    &json!({ "error": { "message": "Incorrect API key", "type": "invalid_request_error" } }),
+                )
+            }
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("old-key", config)?;
+        assert!(client.send_message("Hello").await.is_err());
+
+        let client = client.with_credential_refresh(PromptRefresh);
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+        // the refreshed key is kept for further requests
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+        Ok(())
+    }
+
+    struct GatewayInterceptor(std::sync::Arc<std::sync::Mutex<Vec<u16>>>);
+
+    #[async_trait::async_trait]
+    impl crate::intercept::Interceptor for GatewayInterceptor {
+        async fn on_request(&self, request: &mut crate::intercept::RequestParts) {
+            request
+                .headers
+                .insert("x-gateway-key", "secret".parse().unwrap());
+        }
+
+        async fn on_response(&self, response: &crate::intercept::ResponseParts) {
+            self.0.lock().unwrap().push(response.status.as_u16());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_transport() -> crate::Result<()> {
+        let transport = JsonTransport(|request: &reqwest::Request| {
+            assert_eq!(request.headers()["authorization"], "Bearer key");
+            completion_body("Hi")
+        });
+        let client = super::ChatGPT::new_with_transport("key", transport, Default::default())?;
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+        Ok(())
+    }
+
+    fn usage_transport() -> impl HttpTransport {
+        JsonTransport(|_: &reqwest::Request| {
+            let mut body = completion_body("Hi");
+            body["usage"] = json!({
+                "prompt_tokens": 10,
+                "completion_tokens": 5,
+                "total_tokens": 15,
+                "prompt_tokens_details": { "cached_tokens": 4 }
+            });
+            body
+        })
+    }
+
+    #[tokio::test]
+    async fn test_usage_tracking() -> crate::Result<()> {
+        let config = crate::config::ModelConfiguration {
+            track_usage: true,
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_transport("key", usage_transport(), config)?;
+        client.send_message("Hello").await?;
+        // clones share the usage
+        client.clone().send_message("Hello").await?;
+        let usage = client.reset_usage();
+        assert_eq!(usage.requests, 2);
+        assert_eq!(usage.total_tokens, 30);
+        assert_eq!(usage.cached_tokens, 8);
+        assert_eq!(client.usage_snapshot(), Default::default());
+
+        let client =
+            super::ChatGPT::new_with_transport("key", usage_transport(), Default::default())?;
+        client.send_message("Hello").await?;
+        assert_eq!(client.usage_snapshot().requests, 0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(unix)]
+    async fn test_unix_socket() -> crate::Result<()> {
+        let path =
+            std::env::temp_dir().join(format!("chatgpt_rs_test_{}.sock", uuid::Uuid::new_v4()));
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        tokio::spawn(async move {
+            let (socket, _) = listener.accept().await.unwrap();
+            serve_connection(socket, &|request: &str| {
+                assert!(request.starts_with("POST /v1/chat/completions"));
+                http_response("200 OK", &completion_body("Hi"))
+            })
+            .await;
+        });
+        let config = crate::config::ModelConfiguration {
+            api_url: "http://gateway/v1/chat/completions".parse().unwrap(),
+            unix_socket: Some(path.clone()),
+            ..Default::default()
+        };
+        let client = super::ChatGPT::new_with_config("", config)?;
+        let response = client.send_message("Hello").await;
+        std::fs::remove_file(&path)?;
+        assert_eq!(response?.message().content, "Hi");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_with_client() -> crate::Result<()> {
+        let api_url = mock_server(|request| {
+            if request.contains("authorization: Bearer key") && request.contains("x-shared: yes") {
+                http_response("200 OK", &completion_body("Hi"))
+            } else {
+                http_response("401 Unauthorized", &json!({}))
+            }
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            ..Default::default()
+        };
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("x-shared", "yes".parse().unwrap());
+        let http = reqwest::Client::builder()
+            .no_proxy()
+            .default_headers(headers)
+            .build()?;
+        let client = super::ChatGPT::with_client(http, "key", config)?;
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "middleware")]
+    async fn test_middleware_client() -> crate::Result<()> {
+        let api_url = mock_server(|request| {
+            if request.contains("authorization: Bearer key") {
+                http_response("200 OK", &completion_body("Hi"))
+            } else {
+                http_response("401 Unauthorized", &json!({}))
+            }
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            ..Default::default()
+        };
+        let middleware =
+            reqwest_middleware::ClientBuilder::new(reqwest::Client::builder().no_proxy().build()?)
+                .build();
+        let client = super::ChatGPT::new_with_middleware("key", middleware, config)?;
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interceptor() -> crate::Result<()> {
+        let api_url = mock_server(|request| {
+            if request.contains("x-gateway-key: secret") && request.contains("\"Hello\"") {
+                http_response("200 OK", &completion_body("Hi"))
+            } else {
+                http_response("400 Bad Request", &json!({}))
+            }
+        })
+        .await;
+        let config = crate::config::ModelConfiguration {
+            api_url,
+            use_system_proxy: false,
+            ..Default::default()
+        };
+        let statuses = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let client = super::ChatGPT::new_with_config("", config)?
+            .with_interceptor(GatewayInterceptor(statuses.clone()));
+        assert_eq!(client.send_message("Hello").await?.message().content, "Hi");
+        assert_eq!(*statuses.lock().unwrap(), vec![200]);
+        Ok(())
+    }
+
+    fn content(delta: &str) -> crate::Result<ResponseChunk> {
+        Ok(ResponseChunk::Content {
+            delta: delta.to_owned(),
+            response_index: 0,
+        })
+    }
+
+    #[test]
+    fn test_azure_filter_chunks() {
+        let parse = |value| {
+            let mut chunks = Vec::new();
+            super::InboundChunkParser::default()
+                .push_chunks(serde_json::from_value(value).unwrap(), &mut chunks);
+            chunks
+        };
+        let prompt_filter = parse(json!({
+            "choices": [],
+            "prompt_filter_results": [{
+                "prompt_index": 0,
+                "content_filter_results": { "hate": { "filtered": false, "severity": "safe" } }
+            }]
+        }));
+        assert!(matches!(
+            &prompt_filter[..],
+            [ResponseChunk::PromptFilter { prompt_index: 0, results }] if !results.is_filtered()
+        ));
+
+        let content_filter = parse(json!({
+            "choices": [{
+                "index": 0,
+                "delta": { "content": "Hi" },
+                "content_filter_results": { "violence": { "filtered": true, "severity": "high" } }
+            }]
+        }));
+        assert_eq!(content_filter[0], content("Hi").unwrap());
+        assert!(matches!(
+            &content_filter[1],
+            ResponseChunk::ContentFilter { results, response_index: 0 } if results.is_filtered()
+        ));
+    }
+
+    struct CountingProvider(std::sync::atomic::AtomicUsize);
+
+    #[async_trait::async_trait]
+    impl crate::auth::AuthProvider for CountingProvider {
+        async fn token(&self) -> crate::Result<String> {
+            let count = self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            Ok(format!("token-{count}"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_auth_provider() -> crate::Result<()> {
+        let client = super::ChatGPT::new_with_auth_provider(
+            CountingProvider(Default::default()),
+            Default::default(),
+        )?;
+        for expected in ["Bearer token-0", "Bearer token-1"] {
+            let request = client
+                .authorized(reqwest::Method::POST, client.config.endpoint_url()?)
+                .await?
+                .build()?;
+            assert_eq!(request.headers()["authorization"], expected);
         }
+        Ok(())
+    }
+
+    #[test]
+    fn test_usage_chunk() {
+        let mut chunks = Vec::new();
+        super::InboundChunkParser::default().push_chunks(
+            serde_json::from_value(json!({
+                "choices": [],
+                "usage": { "prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15 }
+            }))
+            .unwrap(),
+            &mut chunks,
+        );
+        assert!(matches!(
+            &chunks[..],
+            [ResponseChunk::Usage(usage)] if usage.total_tokens == 15
+        ));
+
+        let config = crate::config::ModelConfiguration {
+            include_stream_usage: true,
+            ..Default::default()
+        };
+        let request = crate::types::CompletionRequest::from_config(&config, Vec::new());
+        let streamed = serde_json::to_value(request.borrowed_with_stream(true)).unwrap();
+        assert_eq!(streamed["stream_options"], json!({ "include_usage": true }));
+        let sent = serde_json::to_value(request.borrowed_with_stream(false)).unwrap();
+        assert!(sent.get("stream_options").is_none());
+    }
+
+    #[test]
+    fn test_tool_call_chunks() {
+        let mut parser = super::InboundChunkParser::default();
+        let mut chunks = Vec::new();
+        for value in [
+            json!({ "choices": [{ "index": 0, "delta": {
+                "role": "assistant", "content": null,
+                "tool_calls": [{ "index": 0, "id": "call_1", "type": "function",
+                    "function": { "name": "get_weather", "arguments": "" } }]
+            } }] }),
+            json!({ "choices": [{ "index": 0, "delta": {
+                "tool_calls": [{ "index": 0, "function": { "arguments": "{\"city\":" } }]
+            } }] }),
+            json!({ "choices": [{ "index": 0, "delta": {
+                "tool_calls": [{ "index": 0, "function": { "arguments": "\"Paris\"}" } }]
+            } }] }),
+            json!({ "choices": [{ "index": 0, "delta": {
+                "tool_calls": [{ "index": 1, "id": "call_2", "type": "function",
+                    "function": { "name": "get_time", "arguments": "{}" } }]
+            } }] }),
+            json!({ "choices": [{ "index": 0, "delta": {}, "finish_reason": "tool_calls" }] }),
+        ] {
+            parser.push_chunks(serde_json::from_value(value).unwrap(), &mut chunks);
+        }
+        assert!(matches!(
+            &chunks[..],
+            [
+                ResponseChunk::BeginResponse { .. },
+                ResponseChunk::ToolCallBegin { call_index: 0, .. },
+                ResponseChunk::ToolCallArgumentsDelta { call_index: 0, .. },
+                ResponseChunk::ToolCallArgumentsDelta { call_index: 0, .. },
+                ResponseChunk::ToolCallEnd {
+                    call_index: 0,
+                    response_index: 0
+                },
+                ResponseChunk::ToolCallBegin { call_index: 1, .. },
+                ResponseChunk::ToolCallArgumentsDelta { call_index: 1, .. },
+                ResponseChunk::ToolCallEnd {
+                    call_index: 1,
+                    response_index: 0
+                },
+                ResponseChunk::CloseResponse {
+                    response_index: 0,
+                    finish_reason: Some(crate::types::FinishReason::ToolCalls)
+                },
+            ]
+        ));
+
+        #[cfg(feature = "functions")]
+        {
+            let message = &crate::types::ChatMessage::from_response_chunks(chunks)[0];
+            assert_eq!(message.tool_calls.len(), 2);
+            assert_eq!(message.tool_calls[0].id, "call_1");
+            assert_eq!(message.tool_calls[0].function.name, "get_weather");
+            assert_eq!(
+                message.tool_calls[0].function.arguments,
+                "{\"city\":\"Paris\"}"
+            );
+            assert_eq!(message.tool_calls[1].function.arguments, "{}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stream_length_limit() {
+        let chunks = futures::stream::iter(vec![
+            content("Hello"),
+            content(", world"),
+            content("!"),
+            Ok(ResponseChunk::Done),
+        ]);
+        let limited = super::limit_stream_length(chunks, Some(StreamLengthLimit::Characters(8)))
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            limited,
+            vec![
+                content("Hello").unwrap(),
+                content(", w").unwrap(),
+                ResponseChunk::Truncated { response_index: 0 }
+            ]
+        );
+
+        let chunks = futures::stream::iter(vec![
+            content("Hello"),
+            content(", world"),
+            content("!"),
+            Ok(ResponseChunk::Done),
+        ]);
+        let limited = super::limit_stream_length(chunks, Some(StreamLengthLimit::Chunks(2)))
+            .map(|chunk| chunk.unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            limited,
+            vec![
+                content("Hello").unwrap(),
+                content(", world").unwrap(),
+                ResponseChunk::Truncated { response_index: 0 }
+            ]
+        );
     }
 }
@@ -1,8 +1,11 @@
 use std::{fmt::Display, str::FromStr};
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::time::Duration;
 
 #[cfg(feature = "functions")]
-use crate::functions::FunctionValidationStrategy;
+use crate::functions::{FunctionValidationStrategy, ToolChoice};
+use crate::types::LogitBias;
 use derive_builder::Builder;
 use serde::Serialize;
 
@@ -18,19 +21,105 @@ pub struct ModelConfiguration {
     pub top_p: f32,
     /// Controls the maximum number of tokens to generate in the completion
     pub max_tokens: Option<u32>,
+    /// Whether the token limit is sent as `max_completion_tokens` instead of the deprecated `max_tokens`,
+    /// which is rejected by o-series reasoning models.
+    ///
+    /// Detected from the engine with [`ChatGPTEngine::is_reasoning_model()`] when not set (the default)
+    pub use_max_completion_tokens: Option<bool>,
     /// Determines how much to penalize new tokens passed on their existing presence so far
     pub presence_penalty: f32,
     /// Determines how much to penalize new tokens based on their existing frequency so far
     pub frequency_penalty: f32,
     /// The maximum amount of replies
     pub reply_count: u32,
-    /// URL of the /v1/chat/completions endpoint. Can be used to set a proxy
+    /// Sequences where the API stops generating further tokens. Not sent when empty
+    pub stop: Vec<String>,
+    /// Biases applied to the likelihood of specific tokens appearing in the completion. Not sent when empty
+    pub logit_bias: LogitBias,
+    /// Seed for best-effort deterministic sampling. Compare [`CompletionResponse::system_fingerprint`](crate::types::CompletionResponse::system_fingerprint)
+    /// of responses to detect backend changes that affect determinism. Not sent by default
+    pub seed: Option<i64>,
+    /// Whether log probabilities of the output tokens are returned in [`MessageChoice::logprobs`](crate::types::MessageChoice::logprobs)
+    pub logprobs: bool,
+    /// The amount of most likely tokens, from 0 to 20, returned at each position. Requires `logprobs`
+    pub top_logprobs: Option<u8>,
+    /// How much effort o-series reasoning models spend on reasoning before replying. Not sent by default
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Identifier of the end user on whose behalf requests are sent, helps OpenAI to monitor and detect abuse. Not sent by default
+    pub user: Option<String>,
+    /// URL of the /v1/chat/completions endpoint. Can be used to set a proxy. Ignored by the Azure OpenAI backend
     pub api_url: url::Url,
+    /// The API flavour the client talks to, which determines the endpoint URL and authentication.
+    /// See [`ModelConfiguration::azure()`] for Azure OpenAI
+    pub backend: ApiBackend,
     /// Timeout for the http requests sent to avoid potentially permanently hanging requests.
+    ///
+    /// When `read_timeout` is set, only applies to non-streaming requests, so long streams are not cut off.
     pub timeout: Duration,
+    /// Timeout for establishing the connection, so unreachable endpoints fail fast. Not set by default
+    pub connect_timeout: Option<Duration>,
+    /// Timeout for each read from the connection, e.g. between streamed chunks. Not set by default
+    pub read_timeout: Option<Duration>,
+    /// Whether to use proxies from the `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY` environment variables.
+    /// Enabled by default. Ignored if a proxy is provided explicitly when constructing the client.
+    pub use_system_proxy: bool,
+    /// TLS settings of the client, e.g. custom root certificates for TLS-intercepting proxies
+    pub tls: TlsConfiguration,
+    /// Unix domain socket the requests are sent through instead of TCP, e.g. of a local sidecar gateway.
+    /// The host of the API URL is then only used in the `Host` header, and proxies are not used
+    #[cfg(unix)]
+    pub unix_socket: Option<PathBuf>,
+    /// Retries of requests that failed with transient errors. Disabled by default
+    pub retry: Option<RetryPolicy>,
+    /// Failing fast after repeated failures of the endpoint. Disabled by default
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+    /// Whether the client accumulates the requests and token usage of all calls, shared between its clones.
+    /// See [`ChatGPT::usage_snapshot()`](crate::client::ChatGPT::usage_snapshot). Disabled by default
+    pub track_usage: bool,
+    /// The format the model must output, e.g. [`ResponseFormat::JsonObject`] for JSON mode. Not sent by default
+    pub response_format: Option<ResponseFormat>,
+    /// Whether structured replies that fail to deserialize are repaired: markdown code fences and trailing commas are
+    /// stripped, and if the reply still does not match, the error is sent back to the model for one corrective
+    /// request. Disabled by default
+    #[cfg(feature = "structured")]
+    pub repair_structured_output: bool,
+    /// Client-side limit on the length of each streamed response. Once the limit is reached, the request is aborted
+    /// and the stream ends with [`ResponseChunk::Truncated`](crate::types::ResponseChunk::Truncated).
+    ///
+    /// Protects from runaway generations even when `max_tokens` is not set. Disabled by default.
+    #[cfg(feature = "streams")]
+    pub stream_length_limit: Option<StreamLengthLimit>,
+    /// How long a stream may go without producing any chunk before it ends with
+    /// [`Error::StreamStalled`](crate::err::Error::StreamStalled), e.g. when a proxy hangs. Unlike `read_timeout`,
+    /// keep-alive comments sent by the server do not count as progress. Disabled by default.
+    #[cfg(feature = "streams")]
+    pub stream_idle_timeout: Option<Duration>,
+    /// How many times a single-reply stream that dropped mid-response is re-sent with the content streamed so far
+    /// as an assistant prefix, continuing with [`ResponseChunk::Resumed`](crate::types::ResponseChunk::Resumed).
+    /// Streams with tool calls are not resumed. Disabled by default.
+    #[cfg(feature = "streams")]
+    pub stream_resume_attempts: u32,
+    /// Whether streamed responses end with a [`ResponseChunk::Usage`](crate::types::ResponseChunk::Usage) chunk
+    /// containing the token usage of the request. Disabled by default.
+    #[cfg(feature = "streams")]
+    pub include_stream_usage: bool,
+    /// Whether completion requests are checked against the capabilities of their model before being sent, failing
+    /// with [`Error::UnsupportedModelOption`](crate::err::Error::UnsupportedModelOption) instead of a backend error,
+    /// e.g. when sending images to a model without vision. Models missing from the capability table are not checked.
+    /// Disabled by default
+    #[cfg(feature = "models")]
+    pub validate_model_capabilities: bool,
+    /// Whether the prompt tokens of requests, along with their `max_tokens`, are checked against the context window of
+    /// their model before being sent, instead of being rejected by the API. Models missing from the capability table
+    /// are not checked. Disabled by default
+    #[cfg(all(feature = "tokenizer", feature = "models"))]
+    pub context_window_policy: Option<ContextWindowPolicy>,
     /// Strategy for function validation strategy. Whenever ChatGPT fails to call a function correctly, this strategy is applied.
     #[cfg(feature = "functions")]
     pub function_validation: FunctionValidationStrategy,
+    /// Determines whether and which tools the model calls when tools are provided. Not sent by default
+    #[cfg(feature = "functions")]
+    pub tool_choice: Option<ToolChoice>,
 }
 
 impl Default for ModelConfiguration {
@@ -40,19 +129,488 @@ impl Default for ModelConfiguration {
             temperature: 0.5,
             top_p: 1.0,
             max_tokens: None,
+            use_max_completion_tokens: None,
             presence_penalty: 0.0,
             frequency_penalty: 0.0,
             reply_count: 1,
+            stop: Vec::new(),
+            logit_bias: LogitBias::default(),
+            seed: None,
+            logprobs: false,
+            top_logprobs: None,
+            reasoning_effort: None,
+            user: None,
             api_url: url::Url::from_str("https://api.openai.com/v1/chat/completions").unwrap(),
+            backend: ApiBackend::default(),
             timeout: Duration::from_secs(10),
+            connect_timeout: None,
+            read_timeout: None,
+            use_system_proxy: true,
+            tls: TlsConfiguration::default(),
+            #[cfg(unix)]
+            unix_socket: None,
+            retry: None,
+            circuit_breaker: None,
+            track_usage: false,
+            response_format: None,
+            #[cfg(feature = "structured")]
+            repair_structured_output: false,
+            #[cfg(feature = "streams")]
+            stream_length_limit: None,
+            #[cfg(feature = "streams")]
+            stream_idle_timeout: None,
+            #[cfg(feature = "streams")]
+            stream_resume_attempts: 0,
+            #[cfg(feature = "streams")]
+            include_stream_usage: false,
+            #[cfg(feature = "models")]
+            validate_model_capabilities: false,
+            #[cfg(all(feature = "tokenizer", feature = "models"))]
+            context_window_policy: None,
             #[cfg(feature = "functions")]
             function_validation: FunctionValidationStrategy::default(),
+            #[cfg(feature = "functions")]
+            tool_choice: None,
+        }
+    }
+}
+
+impl ModelConfiguration {
+    /// Configuration preset for the OpenAI-compatible server of [llama.cpp](https://github.com/ggerganov/llama.cpp)
+    /// running at the provided base URL, e.g. `http://localhost:8080`.
+    ///
+    /// The server always uses the model it was started with, so the engine only serves as a label.
+    /// The timeout is raised, as local inference is usually much slower.
+    pub fn llama_cpp<S: AsRef<str>>(base_url: S) -> crate::Result<Self> {
+        let mut base_url = base_url.as_ref().to_owned();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let api_url = url::Url::parse(&base_url)
+            .and_then(|url| url.join("v1/chat/completions"))
+            .map_err(|err| crate::err::Error::ParsingError(err.to_string()))?;
+        Ok(Self {
+            engine: ChatGPTEngine::Custom("llama.cpp"),
+            api_url,
+            timeout: Duration::from_secs(300),
+            ..Default::default()
+        })
+    }
+
+    /// Configuration preset for a model deployment of the Azure OpenAI resource, e.g. `my-resource`,
+    /// using the provided API version, e.g. `2024-10-21`.
+    ///
+    /// Azure selects the model by the deployment, so the engine only serves as a label.
+    pub fn azure<R: Into<String>, D: Into<String>, V: Into<String>>(
+        resource: R,
+        deployment: D,
+        api_version: V,
+    ) -> Self {
+        Self {
+            engine: ChatGPTEngine::Custom("azure"),
+            backend: ApiBackend::Azure {
+                resource: resource.into(),
+                deployment: deployment.into(),
+                api_version: api_version.into(),
+            },
+            ..Default::default()
+        }
+    }
+
+    /// The URL requests are sent to, which depends on the backend
+    pub fn endpoint_url(&self) -> crate::Result<url::Url> {
+        match &self.backend {
+            ApiBackend::OpenAi => Ok(self.api_url.clone()),
+            ApiBackend::Azure {
+                resource,
+                deployment,
+                api_version,
+            } => azure_url(
+                resource,
+                api_version,
+                ["deployments", deployment, "chat", "completions"],
+            ),
+        }
+    }
+
+    /// The URL of another endpoint of the API, e.g. `moderations`.
+    ///
+    /// For OpenAI it is resolved relative to [`Self::endpoint_url()`], which is expected to end with
+    /// `chat/completions`. On Azure, only the inference endpoints, e.g. `audio/translations`, are served under the
+    /// deployment, the others, e.g. `files`, are served by the resource itself
+    pub fn endpoint_url_for(&self, path: &str) -> crate::Result<url::Url> {
+        match &self.backend {
+            ApiBackend::OpenAi => {
+                let mut url = self
+                    .api_url
+                    .join(&format!("../{path}"))
+                    .map_err(|err| crate::err::Error::ParsingError(err.to_string()))?;
+                url.set_query(self.api_url.query());
+                Ok(url)
+            }
+            ApiBackend::Azure {
+                resource,
+                deployment,
+                api_version,
+            } => {
+                let segments = path.split('/');
+                let endpoint = path.split('/').next().unwrap_or_default();
+                if AZURE_DEPLOYMENT_ENDPOINTS.contains(&endpoint) {
+                    let deployment = ["deployments", deployment.as_str()];
+                    azure_url(
+                        resource,
+                        api_version,
+                        deployment.into_iter().chain(segments),
+                    )
+                } else {
+                    azure_url(resource, api_version, segments)
+                }
+            }
+        }
+    }
+}
+
+/// The first path segments of the Azure endpoints served under a model deployment
+const AZURE_DEPLOYMENT_ENDPOINTS: [&str; 5] =
+    ["chat", "completions", "embeddings", "images", "audio"];
+
+/// Builds the URL of the path under the `openai` root of the Azure resource
+fn azure_url<'a>(
+    resource: &str,
+    api_version: &str,
+    segments: impl IntoIterator<Item = &'a str>,
+) -> crate::Result<url::Url> {
+    // the resource name becomes a label of the host, and must not be able to change it
+    let invalid = || crate::err::Error::ParsingError(format!("Invalid resource: {resource}"));
+    let is_label = (1..=63).contains(&resource.len())
+        && resource
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+        && !resource.starts_with('-')
+        && !resource.ends_with('-');
+    if !is_label {
+        return Err(invalid());
+    }
+    let mut url = url::Url::parse(&format!("https://{resource}.openai.azure.com/"))
+        .map_err(|err| crate::err::Error::ParsingError(err.to_string()))?;
+    url.path_segments_mut()
+        .map_err(|_| invalid())?
+        .push("openai")
+        .extend(segments);
+    url.query_pairs_mut()
+        .append_pair("api-version", api_version);
+    Ok(url)
+}
+
+/// The API flavour used by the client
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd)]
+pub enum ApiBackend {
+    /// The OpenAI API, or any OpenAI-compatible API at [`ModelConfiguration::api_url`],
+    /// authenticated with an `Authorization: Bearer` header
+    #[default]
+    OpenAi,
+    /// Azure OpenAI, authenticated with an `api-key` header
+    Azure {
+        /// Name of the Azure OpenAI resource, the subdomain of `openai.azure.com`
+        resource: String,
+        /// Name of the model deployment
+        deployment: String,
+        /// The API version, e.g. `2024-10-21`
+        api_version: String,
+    },
+}
+
+/// Policy of retrying requests that failed with transient errors, with exponential backoff
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd)]
+pub struct RetryPolicy {
+    /// Maximum amount of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for each following retry
+    pub base_delay: Duration,
+    /// Upper bound of the delay between attempts
+    pub max_delay: Duration,
+    /// Whether delays are randomized between half and the full delay, so that many clients do not retry at once
+    pub jitter: bool,
+    /// HTTP status codes of failures that are retried. Connection errors and timeouts are always retried
+    pub retryable_status_codes: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            jitter: true,
+            retryable_status_codes: vec![500, 502, 503, 504, 529],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether failures with the status code are retried
+    pub fn retries_status(&self, status: u16) -> bool {
+        self.retryable_status_codes.contains(&status)
+    }
+
+    /// The delay after the provided failed attempt, counted from 1
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        if self.jitter {
+            use std::hash::{BuildHasher, Hasher};
+            // randomly seeded hasher, to avoid depending on a random number generator
+            let random = std::collections::hash_map::RandomState::new()
+                .build_hasher()
+                .finish();
+            delay.mul_f64(0.5 + (random % 1000) as f64 / 2000.0)
+        } else {
+            delay
+        }
+    }
+}
+
+/// Circuit breaker of the client. After the threshold of consecutive failures (connection errors, timeouts or
+/// server errors) is reached, requests fail with [`Error::CircuitOpen`](crate::err::Error::CircuitOpen) without
+/// being sent until the cooldown elapses. The next failure after the cooldown opens the circuit again.
+///
+/// The circuit is shared between clones of the client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd)]
+pub struct CircuitBreakerConfig {
+    /// Amount of consecutive failures that open the circuit
+    pub failure_threshold: u32,
+    /// How long the circuit stays open
+    pub cooldown: Duration,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Options of a single request, overriding the configuration of the client for that request only
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd)]
+pub struct RequestOptions {
+    /// Timeout of the request, overriding [`ModelConfiguration::timeout`]
+    pub timeout: Option<Duration>,
+}
+
+impl RequestOptions {
+    /// Overrides the timeout of the request
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+}
+
+/// TLS settings of the HTTP client
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd)]
+pub struct TlsConfiguration {
+    /// Additional trusted root certificates, e.g. of a corporate proxy or a self-signed local server
+    pub root_certificates: Vec<RootCertificate>,
+    /// Whether the built-in (Mozilla) root certificates are trusted. Enabled by default
+    pub built_in_root_certificates: bool,
+    /// The minimum accepted TLS version. Defaults to the client's default
+    pub min_tls_version: Option<TlsVersion>,
+    /// The client certificate presented to servers that require mutual TLS, e.g. private gateways. Not sent by default
+    pub client_identity: Option<ClientIdentity>,
+    /// Disables certificate validation entirely.
+    ///
+    /// **WARNING**: any certificate will be trusted, including expired and forged ones.
+    /// Only use this for testing against local servers.
+    pub danger_accept_invalid_certs: bool,
+}
+
+impl Default for TlsConfiguration {
+    fn default() -> Self {
+        Self {
+            root_certificates: Vec::new(),
+            built_in_root_certificates: true,
+            min_tls_version: None,
+            client_identity: None,
+            danger_accept_invalid_certs: false,
         }
     }
 }
 
+impl TlsConfiguration {
+    /// Adds a trusted root certificate
+    pub fn with_root_certificate(mut self, certificate: RootCertificate) -> Self {
+        self.root_certificates.push(certificate);
+        self
+    }
+
+    /// Sets the minimum accepted TLS version
+    pub fn with_min_tls_version(mut self, version: TlsVersion) -> Self {
+        self.min_tls_version = Some(version);
+        self
+    }
+
+    /// Sets the client certificate for mutual TLS
+    pub fn with_client_identity(mut self, identity: ClientIdentity) -> Self {
+        self.client_identity = Some(identity);
+        self
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn apply(
+        &self,
+        mut builder: reqwest::ClientBuilder,
+    ) -> crate::Result<reqwest::ClientBuilder> {
+        for certificate in &self.root_certificates {
+            let certificate = match certificate {
+                RootCertificate::Pem(pem) => reqwest::Certificate::from_pem(pem)?,
+                RootCertificate::Der(der) => reqwest::Certificate::from_der(der)?,
+            };
+            builder = builder.add_root_certificate(certificate);
+        }
+        if let Some(version) = self.min_tls_version {
+            builder = builder.min_tls_version(match version {
+                TlsVersion::Tls1_2 => reqwest::tls::Version::TLS_1_2,
+                TlsVersion::Tls1_3 => reqwest::tls::Version::TLS_1_3,
+            });
+        }
+        if let Some(ClientIdentity::Pem(pem)) = &self.client_identity {
+            builder = builder.identity(reqwest::Identity::from_pem(pem)?);
+        }
+        Ok(builder
+            .tls_built_in_root_certs(self.built_in_root_certificates)
+            .danger_accept_invalid_certs(self.danger_accept_invalid_certs))
+    }
+}
+
+/// A trusted root certificate
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd)]
+pub enum RootCertificate {
+    /// A PEM encoded certificate
+    Pem(Vec<u8>),
+    /// A DER encoded certificate
+    Der(Vec<u8>),
+}
+
+/// A client certificate with its private key, used for mutual TLS
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd)]
+pub enum ClientIdentity {
+    /// PEM encoded certificate chain followed by the PKCS#8, PKCS#1 or SEC1 private key
+    Pem(Vec<u8>),
+}
+
+impl std::fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the private key is never printed
+        match self {
+            Self::Pem(_) => f.write_str("Pem(..)"),
+        }
+    }
+}
+
+/// A TLS protocol version
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
+pub enum TlsVersion {
+    /// TLS 1.2
+    Tls1_2,
+    /// TLS 1.3
+    Tls1_3,
+}
+
+/// The format of the model output
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseFormat {
+    /// Plain text, the default of the API
+    Text,
+    /// JSON mode, the model only outputs valid JSON objects.
+    /// The model still has to be instructed to produce JSON, e.g. in the system message
+    JsonObject,
+    /// Structured outputs, the model only outputs JSON matching the provided schema.
+    ///
+    /// Requires the `structured` crate feature
+    #[cfg(feature = "structured")]
+    JsonSchema {
+        /// The schema of the output
+        json_schema: JsonSchemaFormat,
+    },
+}
+
+/// A named JSON schema of structured model output
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[cfg(feature = "structured")]
+pub struct JsonSchemaFormat {
+    /// Name of the schema, may only contain `a-z`, `A-Z`, `0-9`, `_` and `-`
+    pub name: String,
+    /// Describes what the output represents
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// The JSON schema itself
+    pub schema: serde_json::Value,
+    /// Whether the API enforces the schema exactly. Strict mode only supports a subset of JSON schema,
+    /// e.g. it requires all properties to be required, so it is disabled by default
+    pub strict: bool,
+}
+
+#[cfg(feature = "structured")]
+impl PartialOrd for JsonSchemaFormat {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        // JSON values are not ordered, so schemas are compared by their textual representation
+        (
+            &self.name,
+            &self.description,
+            self.strict,
+            self.schema.to_string(),
+        )
+            .partial_cmp(&(
+                &other.name,
+                &other.description,
+                other.strict,
+                other.schema.to_string(),
+            ))
+    }
+}
+
+/// The effort reasoning models spend on reasoning, trading latency and token usage for quality
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ReasoningEffort {
+    /// Faster replies with less reasoning tokens
+    Low,
+    /// The default of the API
+    Medium,
+    /// More thorough reasoning
+    High,
+}
+
+/// A client-side limit on the length of a streamed response
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd)]
+#[cfg(feature = "streams")]
+pub enum StreamLengthLimit {
+    /// Limits the amount of characters in a single response
+    Characters(usize),
+    /// Limits the amount of content chunks in a single response.
+    ///
+    /// The OpenAI API usually streams a single token per chunk, so this roughly limits the amount of tokens there.
+    /// Other backends may stream several tokens per chunk.
+    Chunks(usize),
+}
+
+/// What happens when the history of a request does not fit the context window of its model
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd)]
+#[cfg(all(feature = "tokenizer", feature = "models"))]
+pub enum ContextWindowPolicy {
+    /// The request fails with [`Error::ContextWindowExceeded`](crate::err::Error::ContextWindowExceeded)
+    Error,
+    /// The oldest messages are left out of the request until it fits, keeping the system and developer messages and
+    /// the last message. Results of dropped tool calls are dropped along with them
+    TruncateOldest,
+}
+
 /// The engine version for ChatGPT
-#[derive(Serialize, Debug, Default, Copy, Clone, PartialEq, PartialOrd)]
+#[derive(Serialize, Debug, Default, Copy, Clone, PartialEq, Eq, Hash, PartialOrd)]
 #[allow(non_camel_case_types)]
 pub enum ChatGPTEngine {
     /// Standard engine: `gpt-3.5-turbo`
@@ -80,6 +638,13 @@ impl Display for ChatGPTEngine {
 
 impl AsRef<str> for ChatGPTEngine {
     fn as_ref(&self) -> &'static str {
+        self.as_str()
+    }
+}
+
+impl ChatGPTEngine {
+    /// The model name of this engine, as sent to the API
+    pub fn as_str(&self) -> &'static str {
         match self {
             ChatGPTEngine::Gpt35Turbo => "gpt-3.5-turbo",
             ChatGPTEngine::Gpt35Turbo_0301 => "gpt-3.5-turbo-0301",
@@ -90,4 +655,99 @@ impl AsRef<str> for ChatGPTEngine {
             ChatGPTEngine::Custom(custom) => custom,
         }
     }
+
+    /// Whether this engine is an o-series reasoning model (e.g. `o1` or `o3-mini`), detected by the model name
+    pub fn is_reasoning_model(&self) -> bool {
+        let mut name = self.as_str().chars();
+        name.next() == Some('o') && name.next().is_some_and(|c| c.is_ascii_digit())
+    }
+}
+
+/// Looks up the model in a built-in model table, matching the longest known prefix of the model identifier,
+/// so dated snapshots (e.g. `gpt-4o-2024-08-06`) and fine-tuned models (e.g. `ft:gpt-4o-mini:org::id`)
+/// resolve to their base model
+#[cfg(any(feature = "models", feature = "pricing"))]
+pub(crate) fn lookup_model<T: Copy>(table: &[(&str, T)], model: &str) -> Option<T> {
+    let model = model
+        .strip_prefix("ft:")
+        .map_or(model, |rest| rest.split(':').next().unwrap_or(rest));
+    table
+        .iter()
+        .filter(|(prefix, _)| model.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map(|(_, entry)| *entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{ClientIdentity, ModelConfiguration, RetryPolicy, TlsConfiguration};
+
+    #[test]
+    fn test_invalid_client_identity() {
+        let config = ModelConfiguration {
+            tls: TlsConfiguration::default()
+                .with_client_identity(ClientIdentity::Pem(b"not a certificate".to_vec())),
+            ..Default::default()
+        };
+        assert!(crate::client::ChatGPT::new_with_config("", config).is_err());
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy {
+            jitter: false,
+            max_delay: Duration::from_secs(3),
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(500));
+        assert_eq!(policy.delay(3), Duration::from_secs(2));
+        assert_eq!(policy.delay(10), Duration::from_secs(3));
+
+        let jittered = RetryPolicy::default().delay(2);
+        assert!(jittered >= Duration::from_millis(500) && jittered <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_azure_endpoint_url() -> crate::Result<()> {
+        let config = ModelConfiguration::azure("my-resource", "gpt-4o", "2024-10-21");
+        assert_eq!(
+            config.endpoint_url()?.as_str(),
+            "https://my-resource.openai.azure.com/openai/deployments/gpt-4o/chat/completions?api-version=2024-10-21"
+        );
+        assert_eq!(
+            ModelConfiguration::default().endpoint_url()?,
+            ModelConfiguration::default().api_url
+        );
+        for resource in ["", "evil.com/", "host#", "-resource", "my resource"] {
+            let config = ModelConfiguration::azure(resource, "gpt-4o", "2024-10-21");
+            assert!(config.endpoint_url().is_err(), "{resource}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_endpoint_url_for() -> crate::Result<()> {
+        assert_eq!(
+            ModelConfiguration::default()
+                .endpoint_url_for("moderations")?
+                .as_str(),
+            "https://api.openai.com/v1/moderations"
+        );
+        let config = ModelConfiguration::azure("my-resource", "gpt-4o", "2024-10-21");
+        assert_eq!(
+            config.endpoint_url_for("audio/translations")?.as_str(),
+            "https://my-resource.openai.azure.com/openai/deployments/gpt-4o/audio/translations?api-version=2024-10-21"
+        );
+        assert_eq!(
+            config.endpoint_url_for("moderations")?.as_str(),
+            "https://my-resource.openai.azure.com/openai/moderations?api-version=2024-10-21"
+        );
+        assert_eq!(
+            config.endpoint_url_for("fine_tuning/jobs")?.as_str(),
+            "https://my-resource.openai.azure.com/openai/fine_tuning/jobs?api-version=2024-10-21"
+        );
+        Ok(())
+    }
 }
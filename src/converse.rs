@@ -1,79 +1,574 @@
+#[cfg(all(
+    feature = "tokio",
+    any(feature = "json", feature = "postcard"),
+    not(target_arch = "wasm32")
+))]
 use std::path::Path;
 
+use serde::{Deserialize, Serialize};
+#[cfg(all(
+    feature = "tokio",
+    any(feature = "json", feature = "postcard"),
+    not(target_arch = "wasm32")
+))]
 use tokio::{fs::File, io::AsyncWriteExt};
+use uuid::Uuid;
 
 #[cfg(feature = "functions")]
 use crate::functions::{
-    CallableAsyncFunction, FunctionArgument, FunctionCall, FunctionValidationStrategy, GptFunction,
-    GptFunctionHolder,
+    CallableAsyncFunction, FunctionArgument, FunctionCall, FunctionCallError,
+    FunctionValidationStrategy, GptFunction, GptFunctionHolder, ToolCall, ToolRegistry,
 };
 #[cfg(feature = "functions")]
 use std::collections::HashMap;
-#[cfg(feature = "functions")]
-use thiserror::Error;
 #[cfg(feature = "streams")]
-use {crate::types::ResponseChunk, futures::Stream};
+use {
+    crate::types::{ResponseAccumulator, ResponseChunk},
+    futures::{Stream, StreamExt},
+};
 
+#[cfg(feature = "tokenizer")]
+use crate::config::ChatGPTEngine;
 use crate::{
     client::ChatGPT,
-    types::{ChatMessage, CompletionResponse, Role},
+    types::{ChatMessage, CompletionResponse, FinishReason, Role, TokenUsage},
 };
 
+/// How the history of a conversation is truncated before each request, so that long conversations keep fitting
+/// the context window of the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TruncationStrategy {
+    /// Keeps the last `n` messages, not counting the system, developer and pinned messages
+    LastMessages(usize),
+    /// Keeps the last messages that fit into `n` prompt tokens, including the system and developer messages.
+    ///
+    /// Requires the `tokenizer` crate feature
+    #[cfg(feature = "tokenizer")]
+    LastTokens(usize),
+}
+
+/// Removes the oldest messages until the total weight of the messages fits the budget, returning them in their
+/// original order. The system and developer messages, the pinned messages and the last message are always kept,
+/// and results of tool and function calls are removed along with the calls
+pub(crate) fn drop_oldest_messages(
+    messages: &mut Vec<ChatMessage>,
+    weights: &mut Vec<usize>,
+    budget: usize,
+) -> Vec<ChatMessage> {
+    let mut removed = Vec::new();
+    let mut total: usize = weights.iter().sum();
+    let removable = |message: &ChatMessage| !message.role.is_instruction() && !message.pinned;
+    while total > budget {
+        let last = messages.len().saturating_sub(1);
+        let Some(oldest) = messages[..last].iter().position(removable) else {
+            break;
+        };
+        total -= weights.remove(oldest);
+        removed.push(messages.remove(oldest));
+        // results of the calls of the removed message would be rejected without them
+        while oldest < messages.len() - 1
+            && removable(&messages[oldest])
+            && matches!(messages[oldest].role, Role::Tool | Role::Function)
+        {
+            total -= weights.remove(oldest);
+            removed.push(messages.remove(oldest));
+        }
+    }
+    removed
+}
+
+/// Summarization of the older messages of a conversation once its history grows too long,
+/// see [`Conversation::compact_history()`].
+///
+/// Requires the `tokenizer` crate feature
+#[cfg(feature = "tokenizer")]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CompactionConfig {
+    /// The amount of prompt tokens of the history, above which the older messages are summarized
+    pub threshold: usize,
+    /// The amount of the most recent messages, that are kept as is
+    pub keep_last: usize,
+    /// The engine summarizing the messages, e.g. a cheaper model. The engine of the client is used if not set
+    pub engine: Option<ChatGPTEngine>,
+    /// Instructions of the summarizing model
+    pub prompt: String,
+}
+
+#[cfg(feature = "tokenizer")]
+impl CompactionConfig {
+    /// Constructs a configuration summarizing all but the last 4 messages, once the history exceeds the threshold
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            keep_last: 4,
+            engine: None,
+            prompt: COMPACTION_PROMPT.to_owned(),
+        }
+    }
+
+    /// Sets the amount of the most recent messages, that are kept as is
+    pub fn with_keep_last(mut self, keep_last: usize) -> Self {
+        self.keep_last = keep_last;
+        self
+    }
+
+    /// Sets the engine summarizing the messages
+    pub fn with_engine(mut self, engine: ChatGPTEngine) -> Self {
+        self.engine = Some(engine);
+        self
+    }
+
+    /// Sets the instructions of the summarizing model
+    pub fn with_prompt<S: Into<String>>(mut self, prompt: S) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+}
+
+/// Default instructions of the model summarizing the history
+#[cfg(feature = "tokenizer")]
+const COMPACTION_PROMPT: &str =
+    "Summarize the following conversation concisely. Keep all facts, decisions, \
+    names and open questions, that are needed to continue it. Reply with the summary only.";
+
+/// Follow-up message sent when a reply is cut off by the token limit
+const CONTINUATION_PROMPT: &str =
+    "Continue exactly where you left off. Do not repeat anything you have already written.";
+
+/// The functions or tools a reply is requested with, so that its continuations are requested with them as well
+#[cfg(feature = "functions")]
+#[derive(Clone, Copy)]
+enum ReplyTools<'a> {
+    /// Neither functions nor tools are sent
+    None,
+    /// The functions or tools of the conversation, see [`Conversation::use_tools`]
+    Conversation,
+    /// The tools of the registry
+    Registry(&'a ToolRegistry),
+}
+
 /// Stores a single conversation session, and automatically saves message history
 pub struct Conversation {
     pub(crate) client: ChatGPT,
+    /// Stable identifier of this conversation, preserved when the conversation is saved and restored
+    pub id: Uuid,
     /// All the messages sent and received, starting with the beginning system message
     pub history: Vec<ChatMessage>,
+    /// Maximum amount of follow-up requests sent to continue a reply that was cut off by the token limit.
+    ///
+    /// Continued parts are stitched into a single assistant message. Set to `0` (the default) to disable.
+    pub max_continuations: u32,
+    /// Truncation of the history applied before each request, see [`Self::truncate_history()`].
+    /// Disabled by default
+    ///
+    /// Truncated messages are permanently deleted from [`Self::history`], so they are missing from the saved
+    /// conversation as well. Use [`crate::config::ModelConfiguration::context_window_policy`] to only drop them from
+    /// the requests instead.
+    pub truncation: Option<TruncationStrategy>,
+    /// Summarization of the older messages applied before each request, see [`Self::compact_history()`].
+    /// Disabled by default
+    ///
+    /// Requires the `tokenizer` crate feature
+    #[cfg(feature = "tokenizer")]
+    pub compaction: Option<CompactionConfig>,
+    #[cfg(feature = "tokenizer")]
+    summary_id: Option<Uuid>,
+    usage: TokenUsage,
     /// Set to `true` if you want to automatically send all functions to API with each message.
     ///
     /// Functions are counted as tokens internally, so it is set to `false` by default.
     #[cfg(feature = "functions")]
     pub always_send_functions: bool,
+    /// Set to `true` to send functions as tools, and to process tool calls instead of the deprecated function calls.
+    ///
+    /// Newer models ignore or reject the deprecated functions, so it should be enabled for them.
+    #[cfg(feature = "functions")]
+    pub use_tools: bool,
+    /// Maximum amount of rounds of tool calls processed by [`Self::send_message_with_tools()`] before giving up.
+    #[cfg(feature = "functions")]
+    pub max_tool_iterations: u32,
+    /// Maximum amount of times [`Self::send_message_with_tools()`] asks the model to repeat tool calls with arguments,
+    /// that could not be deserialized, after explaining the error to it.
+    ///
+    /// Set to `0` (the default) to answer such calls with a generic error instead, without failing the request.
+    #[cfg(feature = "functions")]
+    pub max_argument_repairs: u32,
     #[cfg(feature = "functions")]
     functions: HashMap<String, Box<dyn GptFunctionHolder>>,
     #[cfg(feature = "functions")]
     function_descriptors: Vec<serde_json::Value>,
+    #[cfg(feature = "functions")]
+    tool_descriptors: Vec<serde_json::Value>,
 }
 
 impl Conversation {
     /// Constructs a new conversation from an API client and the introductory message
     pub fn new(client: ChatGPT, first_message: String) -> Self {
-        Self {
-            client,
-            history: vec![ChatMessage {
-                role: Role::System,
-                content: first_message,
-                #[cfg(feature = "functions")]
-                function_call: None,
-            }],
-            #[cfg(feature = "functions")]
-            functions: HashMap::with_capacity(4),
-            #[cfg(feature = "functions")]
-            always_send_functions: false,
-            #[cfg(feature = "functions")]
-            function_descriptors: Vec::with_capacity(4),
-        }
+        Self::new_with_history(client, vec![ChatMessage::new(Role::System, first_message)])
     }
 
-    /// Constructs a new conversation from a pre-initialized chat history
+    /// Constructs a new conversation from a pre-initialized chat history.
+    ///
+    /// Messages without an id are assigned one.
     pub fn new_with_history(client: ChatGPT, history: Vec<ChatMessage>) -> Self {
+        Self::new_with_id(client, Uuid::new_v4(), history)
+    }
+
+    /// Constructs a conversation with a known id from a pre-initialized chat history, e.g. when restoring it from storage.
+    ///
+    /// Messages without an id are assigned one.
+    pub fn new_with_id(client: ChatGPT, id: Uuid, mut history: Vec<ChatMessage>) -> Self {
+        for message in &mut history {
+            message.id.get_or_insert_with(Uuid::new_v4);
+        }
         Self {
             client,
+            id,
             history,
+            max_continuations: 0,
+            truncation: None,
+            #[cfg(feature = "tokenizer")]
+            compaction: None,
+            #[cfg(feature = "tokenizer")]
+            summary_id: None,
+            usage: TokenUsage::default(),
             #[cfg(feature = "functions")]
             functions: HashMap::with_capacity(4),
             #[cfg(feature = "functions")]
             always_send_functions: false,
             #[cfg(feature = "functions")]
+            use_tools: false,
+            #[cfg(feature = "functions")]
+            max_tool_iterations: 8,
+            #[cfg(feature = "functions")]
+            max_argument_repairs: 0,
+            #[cfg(feature = "functions")]
             function_descriptors: Vec::with_capacity(4),
+            #[cfg(feature = "functions")]
+            tool_descriptors: Vec::with_capacity(4),
         }
     }
 
-    /// Rollbacks the history by 1 message, removing the last sent and received message.
+    /// Asks the model to continue the first choice of the response while it is cut off by the token limit,
+    /// up to [`Self::max_continuations`] times, and appends the continued parts to it.
+    ///
+    /// Intermediate follow-up messages are not kept in the history. The follow-ups are sent with the same functions or
+    /// tools as the reply.
+    async fn continue_truncated(
+        &mut self,
+        resp: &mut CompletionResponse,
+        #[cfg(feature = "functions")] tools: ReplyTools<'_>,
+    ) -> crate::Result<()> {
+        for _ in 0..self.max_continuations {
+            let Some(choice) = resp.message_choices.first_mut() else {
+                break;
+            };
+            if choice.finish_reason != Some(FinishReason::Length) {
+                break;
+            }
+            self.history.push(choice.message.clone());
+            self.history
+                .push(ChatMessage::new(Role::User, CONTINUATION_PROMPT));
+            #[cfg(feature = "functions")]
+            let continuation = self.request_reply(tools).await;
+            #[cfg(not(feature = "functions"))]
+            let continuation = self.client.send_history(&self.history).await;
+            self.history.truncate(self.history.len() - 2);
+            let continuation = continuation?;
+            let Some(next) = continuation.message_choices.into_iter().next() else {
+                break;
+            };
+            choice.message.content.push_str(&next.message.content);
+            choice.finish_reason = next.finish_reason;
+            resp.usage += continuation.usage;
+        }
+        Ok(())
+    }
+
+    /// Removes the oldest messages from the history according to the [`Self::truncation`] strategy, returning
+    /// them in their original order. Called automatically before each request.
+    ///
+    /// The system and developer messages, the pinned messages and the last message are always kept. Results of tool
+    /// and function calls are removed along with the calls, so pin them along with the calls.
+    pub fn truncate_history(&mut self) -> crate::Result<Vec<ChatMessage>> {
+        let Some(strategy) = self.truncation else {
+            return Ok(Vec::new());
+        };
+        let (mut weights, budget) = match strategy {
+            TruncationStrategy::LastMessages(n) => {
+                let weights = self
+                    .history
+                    .iter()
+                    .map(|message| usize::from(!message.role.is_instruction() && !message.pinned))
+                    .collect::<Vec<_>>();
+                (weights, n)
+            }
+            #[cfg(feature = "tokenizer")]
+            TruncationStrategy::LastTokens(n) => {
+                let weights = crate::tokenizer::message_token_counts(
+                    &self.history,
+                    self.client.config.engine,
+                )?;
+                // every reply is primed with 3 tokens
+                (weights, n.saturating_sub(3))
+            }
+        };
+        Ok(drop_oldest_messages(
+            &mut self.history,
+            &mut weights,
+            budget,
+        ))
+    }
+
+    /// Summarizes the older messages into a single system message that replaces them, if the history exceeds the
+    /// token threshold of the [`Self::compaction`] configuration. The summary of a previous compaction is summarized
+    /// along with them. Called automatically before each request.
+    ///
+    /// The system and developer messages, the pinned messages and the last messages are kept as is. Returns `true` if
+    /// the history was compacted. The token usage of the summarization is added to the total usage of the conversation.
+    ///
+    /// Requires the `tokenizer` crate feature
+    #[cfg(feature = "tokenizer")]
+    pub async fn compact_history(&mut self) -> crate::Result<bool> {
+        let Some(compaction) = &self.compaction else {
+            return Ok(false);
+        };
+        if self.token_count()? <= compaction.threshold {
+            return Ok(false);
+        }
+        let summary_id = self.summary_id;
+        let summarized = |message: &ChatMessage| {
+            (!message.role.is_instruction() && !message.pinned)
+                || (summary_id.is_some() && message.id == summary_id)
+        };
+        let candidates: Vec<usize> = (0..self.history.len())
+            .filter(|&index| summarized(&self.history[index]))
+            .collect();
+        let mut count = candidates.len().saturating_sub(compaction.keep_last);
+        // results of tool and function calls are summarized along with the calls
+        while count < candidates.len()
+            && matches!(
+                self.history[candidates[count]].role,
+                Role::Tool | Role::Function
+            )
+        {
+            count += 1;
+        }
+        // the last message is the one being replied to
+        count = count.min(candidates.len().saturating_sub(1));
+        if count == 0 || (count == 1 && summary_id.is_some()) {
+            return Ok(false);
+        }
+        let transcript = candidates[..count]
+            .iter()
+            .map(|&index| {
+                let message = &self.history[index];
+                format!("{:?}: {}", message.role, message.content)
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let mut config = self.client.config.clone();
+        if let Some(engine) = compaction.engine {
+            config.engine = engine;
+        }
+        config.response_format = None;
+        let messages = [
+            ChatMessage::new(Role::System, compaction.prompt.as_str()),
+            ChatMessage::new(Role::User, transcript),
+        ];
+        let request = crate::types::CompletionRequest::from_config(&config, &messages[..]);
+        let response = self.client.send_request(&request).await?;
+        self.usage += response.usage.clone();
+        let summary = ChatMessage::new(
+            Role::System,
+            format!(
+                "Summary of the earlier conversation:\n{}",
+                response.message().content
+            ),
+        );
+        let position = candidates[0];
+        for &index in candidates[..count].iter().rev() {
+            self.history.remove(index);
+        }
+        self.history.insert(position, summary);
+        let id = *self.history[position].id.get_or_insert_with(Uuid::new_v4);
+        self.summary_id = Some(id);
+        Ok(true)
+    }
+
+    /// Compacts and truncates the history before a request
+    async fn prepare_history(&mut self) -> crate::Result<()> {
+        #[cfg(feature = "tokenizer")]
+        self.compact_history().await?;
+        self.truncate_history()?;
+        Ok(())
+    }
+
+    /// Rolls back the last exchange, removing the last user message along with all replies to it, including tool
+    /// calls and their results.
+    ///
+    /// Returns the last removed message, i.e. the final reply if there is one. The history is kept as is if it
+    /// contains no user messages. Use [`Self::last_user_message()`] beforehand to send the user message again,
+    /// e.g. for a "regenerate" button.
     pub fn rollback(&mut self) -> Option<ChatMessage> {
-        let last = self.history.pop();
-        self.history.pop();
-        last
+        self.undo(1).pop()
+    }
+
+    /// The last user message of the history, which starts the exchange removed by [`Self::rollback()`]
+    pub fn last_user_message(&self) -> Option<&ChatMessage> {
+        self.history
+            .iter()
+            .rev()
+            .find(|message| message.role == Role::User)
+    }
+
+    /// Removes the last `n` exchanges from the history, each starting with a user message and including all replies to it.
+    ///
+    /// The system message is always kept. Returns the removed messages in their original order.
+    pub fn undo(&mut self, n: usize) -> Vec<ChatMessage> {
+        let mut cut = self.history.len();
+        for _ in 0..n {
+            match self.history[..cut]
+                .iter()
+                .rposition(|message| message.role == Role::User)
+            {
+                Some(index) => cut = index,
+                None => break,
+            }
+        }
+        self.history.split_off(cut)
+    }
+
+    /// Amount of messages in the history, including the system message
+    pub fn len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Returns `true` if the history contains no messages at all
+    pub fn is_empty(&self) -> bool {
+        self.history.is_empty()
+    }
+
+    /// Amount of exchanges in the history, i.e. the amount of user messages
+    pub fn turn_count(&self) -> usize {
+        self.history
+            .iter()
+            .filter(|message| message.role == Role::User)
+            .count()
+    }
+
+    /// Estimates the amount of prompt tokens the history takes with the engine of the client.
+    ///
+    /// Requires the `tokenizer` crate feature
+    #[cfg(feature = "tokenizer")]
+    pub fn token_count(&self) -> crate::Result<usize> {
+        crate::tokenizer::count_tokens(&self.history, self.client.config.engine)
+    }
+
+    /// Appends a message to the history without sending anything to the API, assigning it an id if it does not have one.
+    ///
+    /// Returns the id of the message.
+    pub fn push_message(&mut self, mut message: ChatMessage) -> Uuid {
+        let id = *message.id.get_or_insert_with(Uuid::new_v4);
+        self.history.push(message);
+        id
+    }
+
+    /// Appends a reply to the history, annotated with the token usage of the request that generated it,
+    /// which is also added to the total usage of the conversation
+    fn push_reply(&mut self, mut message: ChatMessage, usage: &TokenUsage) -> Uuid {
+        if *usage != TokenUsage::default() {
+            self.usage += usage.clone();
+            message.usage = Some(usage.clone());
+        }
+        self.push_message(message)
+    }
+
+    /// Total token usage of all requests sent by this conversation, including continuations and the replies that
+    /// were removed from the history since. Zeroed if the backend does not report usage
+    pub fn total_usage(&self) -> &TokenUsage {
+        &self.usage
+    }
+
+    /// Estimates the cost of all requests sent by this conversation in US dollars, see [`Self::total_usage()`].
+    /// Returns `None` if the engine of the client is missing from the pricing table.
+    ///
+    /// Requires the `pricing` crate feature
+    #[cfg(feature = "pricing")]
+    pub fn estimated_cost(&self) -> Option<f64> {
+        crate::pricing::ModelPricing::of(self.client.config.engine.as_str())
+            .map(|pricing| pricing.cost(&self.usage))
+    }
+
+    /// Appends a message from a specified role to the history without sending anything to the API.
+    ///
+    /// Can be used to replay exchanges that happened elsewhere, or to inject canned messages into the history.
+    pub fn push_role_message<S: Into<String>>(&mut self, role: Role, message: S) {
+        self.push_message(ChatMessage::new(role, message));
+    }
+
+    /// Finds a message in the history by its id
+    pub fn message(&self, id: Uuid) -> Option<&ChatMessage> {
+        self.history.iter().find(|message| message.id == Some(id))
+    }
+
+    /// Pins the message with the provided id, so that it is kept by the truncation and compaction of the history.
+    /// Returns `false` if the history has no such message
+    pub fn pin(&mut self, id: Uuid) -> bool {
+        self.set_pinned(id, true)
+    }
+
+    /// Unpins the message with the provided id. Returns `false` if the history has no such message
+    pub fn unpin(&mut self, id: Uuid) -> bool {
+        self.set_pinned(id, false)
+    }
+
+    fn set_pinned(&mut self, id: Uuid, pinned: bool) -> bool {
+        match self
+            .history
+            .iter_mut()
+            .find(|message| message.id == Some(id))
+        {
+            Some(message) => {
+                message.pinned = pinned;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Appends a user message to the history without sending it to the API.
+    pub fn push_user_message<S: Into<String>>(&mut self, message: S) {
+        self.push_role_message(Role::User, message)
+    }
+
+    /// Appends an assistant message to the history without requesting it from the API.
+    pub fn push_assistant_message<S: Into<String>>(&mut self, message: S) {
+        self.push_role_message(Role::Assistant, message)
+    }
+
+    /// Appends a developer message to the history without sending it to the API.
+    ///
+    /// Developer messages replace system messages for o-series reasoning models.
+    pub fn push_developer_message<S: Into<String>>(&mut self, message: S) {
+        self.push_role_message(Role::Developer, message)
+    }
+
+    /// Appends the result of a tool call to the history without sending it to the API.
+    #[cfg(feature = "functions")]
+    pub fn push_tool_result<I: Into<String>, S: Into<String>>(
+        &mut self,
+        tool_call_id: I,
+        content: S,
+    ) {
+        self.push_message(ChatMessage::tool_result(tool_call_id, content));
+    }
+
+    /// Sets the identifier of the end user this conversation is held with, sent with each request for abuse monitoring.
+    ///
+    /// Overrides [`ModelConfiguration::user`](crate::config::ModelConfiguration::user) of the client for this conversation.
+    pub fn set_user<S: Into<String>>(&mut self, user: S) {
+        self.client.config.user = Some(user.into());
     }
 
     /// Adds a function that can later be called by ChatGPT
@@ -85,48 +580,182 @@ impl Conversation {
         &mut self,
         prebuilt: GptFunction<A, C>,
     ) -> crate::Result<()> {
-        self.function_descriptors
-            .push(serde_json::to_value(&prebuilt.descriptor).map_err(crate::err::Error::from)?);
+        let descriptor =
+            serde_json::to_value(&prebuilt.descriptor).map_err(crate::err::Error::from)?;
+        self.tool_descriptors.push(serde_json::json!({
+            "type": "function",
+            "function": descriptor,
+        }));
+        self.function_descriptors.push(descriptor);
         self.functions
             .insert(prebuilt.descriptor.name.to_owned(), Box::new(prebuilt));
         Ok(())
     }
 
     /// Sends a message from a specified role to the ChatGPT API and returns the completion response.
-    #[cfg_attr(feature = "functions", async_recursion::async_recursion)]
-    pub async fn send_role_message<S: Into<String> + Send + Sync>(
+    #[cfg_attr(
+        all(feature = "functions", not(target_arch = "wasm32")),
+        async_recursion::async_recursion
+    )]
+    #[cfg_attr(
+        all(feature = "functions", target_arch = "wasm32"),
+        async_recursion::async_recursion(?Send)
+    )]
+    pub async fn send_role_message<S>(
         &mut self,
         role: Role,
         message: S,
-    ) -> crate::Result<CompletionResponse> {
-        self.history.push(ChatMessage {
-            role,
-            content: message.into(),
-            #[cfg(feature = "functions")]
-            function_call: None,
-        });
+    ) -> crate::Result<CompletionResponse>
+    where
+        S: Into<String> + Send + Sync,
+    {
+        self.push_message(ChatMessage::new(role, message));
+        self.complete().await
+    }
 
+    /// Requests a completion of the current history, then stores the reply and processes function calls in it
+    #[cfg_attr(
+        all(feature = "functions", not(target_arch = "wasm32")),
+        async_recursion::async_recursion
+    )]
+    #[cfg_attr(
+        all(feature = "functions", target_arch = "wasm32"),
+        async_recursion::async_recursion(?Send)
+    )]
+    async fn complete(&mut self) -> crate::Result<CompletionResponse> {
+        self.prepare_history().await?;
         #[cfg(feature = "functions")]
-        let resp = if self.always_send_functions {
-            self.client
-                .send_history_functions(&self.history, &self.function_descriptors)
-                .await?
-        } else {
-            self.client.send_history(&self.history).await?
-        };
+        {
+            let tools = if self.always_send_functions {
+                ReplyTools::Conversation
+            } else {
+                ReplyTools::None
+            };
+            let resp = self.request_reply(tools).await?;
+            self.store_response(resp, tools).await
+        }
         #[cfg(not(feature = "functions"))]
-        let resp = self.client.send_history(&self.history).await?;
-        let msg = &resp.message_choices[0].message;
-        self.history.push(msg.clone());
-        if let Some(function_response) = self.process_possible_function_response(msg).await {
-            self.history
-                .push(function_response.message_choices[0].message.clone());
-            Ok(function_response)
+        {
+            let resp = self.client.send_history(&self.history).await?;
+            self.store_response(resp).await
+        }
+    }
+
+    /// Stores the reply from the response, continuing it if it was truncated.
+    ///
+    /// If the reply calls a function, returns the response received after calling it instead. It is already stored at that point.
+    /// Fails if sending the function results back fails, even though the results are already stored in the history.
+    async fn store_response(
+        &mut self,
+        mut resp: CompletionResponse,
+        #[cfg(feature = "functions")] tools: ReplyTools<'_>,
+    ) -> crate::Result<CompletionResponse> {
+        #[cfg(feature = "functions")]
+        self.continue_truncated(&mut resp, tools).await?;
+        #[cfg(not(feature = "functions"))]
+        self.continue_truncated(&mut resp).await?;
+        let msg = resp.message_choices[0].message.clone();
+        self.push_reply(msg.clone(), &resp.usage);
+        if let Some(function_response) = self.process_possible_function_response(&msg).await {
+            function_response
         } else {
             Ok(resp)
         }
     }
 
+    /// Sends the message with the tools of the registry, then calls the tools requested by the model and sends their
+    /// results back, until the model replies without calling any tools. Returns that final response.
+    ///
+    /// All the tool calls and their results are stored in the history. Fails with
+    /// [`Error::ToolIterationsExceeded`](crate::err::Error::ToolIterationsExceeded) if the model keeps calling tools
+    /// after [`Self::max_tool_iterations`] rounds, and with
+    /// [`Error::InvalidToolArguments`](crate::err::Error::InvalidToolArguments) if it keeps calling them with invalid
+    /// arguments after [`Self::max_argument_repairs`] retries.
+    ///
+    /// Requires the `functions` crate feature.
+    #[cfg(feature = "functions")]
+    pub async fn send_message_with_tools<S: Into<String>>(
+        &mut self,
+        message: S,
+        registry: &ToolRegistry,
+    ) -> crate::Result<CompletionResponse> {
+        self.push_message(ChatMessage::new(Role::User, message));
+        let mut iterations = 0;
+        let mut repairs = 0;
+        loop {
+            self.prepare_history().await?;
+            let tools = ReplyTools::Registry(registry);
+            let mut resp = self.request_reply(tools).await?;
+            self.continue_truncated(&mut resp, tools).await?;
+            let msg = resp.message_choices[0].message.clone();
+            self.push_reply(msg.clone(), &resp.usage);
+            if msg.tool_calls.is_empty() {
+                return Ok(resp);
+            }
+            if iterations == self.max_tool_iterations {
+                // the API rejects unanswered tool calls, which would break the next message
+                let error = crate::err::Error::ToolIterationsExceeded(iterations);
+                for call in &msg.tool_calls {
+                    self.push_message(ChatMessage::tool_result(call.id.clone(), error.to_string()));
+                }
+                return Err(error);
+            }
+            iterations += 1;
+            let mut invalid = None;
+            for call in &msg.tool_calls {
+                let message = match registry.call_validated(call).await {
+                    Ok(message) => message,
+                    Err(err) if self.max_argument_repairs > 0 => {
+                        let content = format!(
+                            "Invalid arguments: {err}. Call `{}` again with arguments matching its parameters.",
+                            call.function.name
+                        );
+                        invalid = Some((call.function.name.clone(), err));
+                        ChatMessage::tool_result(call.id.clone(), content)
+                    }
+                    Err(_) => {
+                        let error = FunctionCallError::InvalidArguments;
+                        ChatMessage::tool_result(call.id.clone(), error.to_string())
+                    }
+                };
+                self.push_message(message);
+            }
+            if let Some((name, source)) = invalid {
+                if repairs == self.max_argument_repairs {
+                    return Err(crate::err::Error::InvalidToolArguments { name, source });
+                }
+                repairs += 1;
+            }
+        }
+    }
+
+    /// Sends the history along with the functions or tools of the reply
+    #[cfg(feature = "functions")]
+    async fn request_reply(&self, tools: ReplyTools<'_>) -> crate::Result<CompletionResponse> {
+        match tools {
+            ReplyTools::None => self.client.send_history(&self.history).await,
+            ReplyTools::Conversation => self.send_history_with_functions().await,
+            ReplyTools::Registry(registry) => {
+                self.client
+                    .send_history_tools(&self.history, registry.descriptors())
+                    .await
+            }
+        }
+    }
+
+    #[cfg(feature = "functions")]
+    async fn send_history_with_functions(&self) -> crate::Result<CompletionResponse> {
+        if self.use_tools {
+            self.client
+                .send_history_tools(&self.history, &self.tool_descriptors)
+                .await
+        } else {
+            self.client
+                .send_history_functions(&self.history, &self.function_descriptors)
+                .await
+        }
+    }
+
     /// Sends the message to the ChatGPT API and returns the completion response.
     ///
     /// Execution speed depends on API response times.
@@ -137,6 +766,28 @@ impl Conversation {
         self.send_role_message(Role::User, message).await
     }
 
+    /// Sends the message to the ChatGPT API and requests `count` candidate replies, without recording any of them.
+    ///
+    /// Record the preferred reply with [`Self::choose_reply()`] before sending the next message.
+    pub async fn send_message_multiple<S: Into<String>>(
+        &mut self,
+        message: S,
+        count: u32,
+    ) -> crate::Result<CompletionResponse> {
+        self.push_message(ChatMessage::new(Role::User, message));
+        self.prepare_history().await?;
+        self.client.sample_history(&self.history, count, None).await
+    }
+
+    /// Records the reply of the choice with the provided index to the history, returning its id.
+    /// The reply is annotated with the token usage of the whole response.
+    ///
+    /// Returns `None` if the response has no such choice. Function and tool calls in the reply are not processed.
+    pub fn choose_reply(&mut self, response: &CompletionResponse, index: usize) -> Option<Uuid> {
+        let message = response.choice(index)?.clone();
+        Some(self.push_reply(message, &response.usage))
+    }
+
     /// Sends a message with all functions to the ChatGPT API and returns the completion response.
     ///
     /// **NOTE**: Functions are counted as tokens internally.
@@ -145,25 +796,10 @@ impl Conversation {
         &mut self,
         message: S,
     ) -> crate::Result<CompletionResponse> {
-        self.history.push(ChatMessage {
-            role: Role::User,
-            content: message.into(),
-            #[cfg(feature = "functions")]
-            function_call: None,
-        });
-        let resp = self
-            .client
-            .send_history_functions(&self.history, &self.function_descriptors)
-            .await?;
-        let msg = &resp.message_choices[0].message;
-        self.history.push(msg.clone());
-        if let Some(function_response) = self.process_possible_function_response(msg).await {
-            self.history
-                .push(function_response.message_choices[0].message.clone());
-            Ok(function_response)
-        } else {
-            Ok(resp)
-        }
+        self.push_message(ChatMessage::new(Role::User, message));
+        self.prepare_history().await?;
+        let resp = self.request_reply(ReplyTools::Conversation).await?;
+        self.store_response(resp, ReplyTools::Conversation).await
     }
 
     /// Sends a message with specified role to the ChatGPT API and returns the completion response as stream.
@@ -171,7 +807,8 @@ impl Conversation {
     /// Note, that this method will not automatically save the received message to history, as
     /// it is returned in streamed chunks. You will have to collect them into chat message yourself.
     ///
-    /// You can use [`ChatMessage::from_response_chunks`] for this
+    /// You can use [`Self::record_response_chunks`] for this, or [`Self::send_message_streaming_recorded`]
+    /// to record the reply automatically
     ///
     /// Requires the `streams` crate feature.
     #[cfg(feature = "streams")]
@@ -180,12 +817,8 @@ impl Conversation {
         role: Role,
         message: S,
     ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>>> {
-        self.history.push(ChatMessage {
-            role,
-            content: message.into(),
-            #[cfg(feature = "functions")]
-            function_call: None,
-        });
+        self.push_message(ChatMessage::new(role, message));
+        self.prepare_history().await?;
         let stream = self.client.send_history_streaming(&self.history).await?;
         Ok(stream)
     }
@@ -195,7 +828,8 @@ impl Conversation {
     /// Note, that this method will not automatically save the received message to history, as
     /// it is returned in streamed chunks. You will have to collect them into chat message yourself.
     ///
-    /// You can use [`ChatMessage::from_response_chunks`] for this
+    /// You can use [`Self::record_response_chunks`] for this, or [`Self::send_message_streaming_recorded`]
+    /// to record the reply automatically
     ///
     /// Requires the `streams` crate feature.
     #[cfg(feature = "streams")]
@@ -206,36 +840,152 @@ impl Conversation {
         self.send_role_message_streaming(Role::User, message).await
     }
 
+    /// Sends the message to the ChatGPT API and returns the completion response as stream, that records the
+    /// first reply choice to the history once it is fully received.
+    ///
+    /// Tool and function calls requested by the model are recorded as part of the assistant message, so the
+    /// history stays valid for the follow-up tool responses. Nothing is recorded if the stream fails.
+    ///
+    /// The conversation is borrowed until the stream is dropped.
+    ///
+    /// Requires the `streams` crate feature.
+    #[cfg(feature = "streams")]
+    pub async fn send_message_streaming_recorded<S: Into<String>>(
+        &mut self,
+        message: S,
+    ) -> crate::Result<impl Stream<Item = crate::Result<ResponseChunk>> + Unpin + '_> {
+        self.push_message(ChatMessage::new(Role::User, message));
+        self.prepare_history().await?;
+        let stream = self.client.send_history_streaming(&self.history).await?;
+        let state = (stream, Some(self), ResponseAccumulator::default(), false);
+        Ok(Box::pin(futures::stream::unfold(
+            state,
+            |(mut stream, mut conversation, mut accumulator, mut failed)| async move {
+                let chunk = stream.next().await;
+                match &chunk {
+                    Some(Ok(chunk)) => accumulator.push(chunk),
+                    Some(Err(_)) => failed = true,
+                    None => {}
+                }
+                if !failed && matches!(chunk, None | Some(Ok(ResponseChunk::Done))) {
+                    if let Some(conversation) = conversation.take() {
+                        conversation.choose_reply(accumulator.response(), 0);
+                    }
+                }
+                chunk.map(|chunk| (chunk, (stream, conversation, accumulator, failed)))
+            },
+        )))
+    }
+
+    /// Records a streamed reply to the history, including the tool and function calls requested by the model.
+    ///
+    /// Only the first reply choice is recorded. Returns the id of the recorded message, or `None` if the chunks
+    /// contained no reply.
+    ///
+    /// Requires the `streams` crate feature.
+    #[cfg(feature = "streams")]
+    pub fn record_response_chunks(&mut self, chunks: &[ResponseChunk]) -> Option<Uuid> {
+        let mut accumulator = ResponseAccumulator::default();
+        for chunk in chunks {
+            accumulator.push(chunk);
+        }
+        self.choose_reply(accumulator.response(), 0)
+    }
+
     /// Saves the history to a local JSON file, that can be restored to a conversation at runtime later.
-    #[cfg(feature = "json")]
+    #[cfg(all(feature = "tokio", feature = "json", not(target_arch = "wasm32")))]
     pub async fn save_history_json<P: AsRef<Path>>(&self, to: P) -> crate::Result<()> {
         let path = to.as_ref();
         if path.exists() {
             tokio::fs::remove_file(path).await?;
         }
         let mut file = File::create(path).await?;
-        file.write_all(&serde_json::to_vec(&self.history)?).await?;
+        file.write_all(self.history_to_json_string()?.as_bytes())
+            .await?;
         Ok(())
     }
 
     /// Saves the history to a local postcard file, that can be restored to a conversation at runtime later.
-    #[cfg(feature = "postcard")]
+    #[cfg(all(feature = "tokio", feature = "postcard", not(target_arch = "wasm32")))]
     pub async fn save_history_postcard<P: AsRef<Path>>(&self, to: P) -> crate::Result<()> {
         let path = to.as_ref();
         if path.exists() {
             tokio::fs::remove_file(path).await?;
         }
         let mut file = File::create(path).await?;
-        file.write_all(&postcard::to_allocvec(&self.history)?)
-            .await?;
+        file.write_all(&self.to_postcard_bytes()?).await?;
         Ok(())
     }
 
+    /// Serializes the conversation to a JSON string without touching the filesystem, e.g. to store it in a database.
+    ///
+    /// It can be restored with [`ChatGPT::restore_conversation_from_str()`].
+    #[cfg(feature = "json")]
+    pub fn history_to_json_string(&self) -> crate::Result<String> {
+        Ok(serde_json::to_string(&self.to_state())?)
+    }
+
+    /// Serializes the conversation to JSON bytes without touching the filesystem, e.g. to write it
+    /// with the IO of any async runtime.
+    ///
+    /// It can be restored with [`ChatGPT::restore_conversation_from_json_bytes()`].
+    #[cfg(feature = "json")]
+    pub fn to_json_bytes(&self) -> crate::Result<Vec<u8>> {
+        Ok(serde_json::to_vec(&self.to_state())?)
+    }
+
+    /// Serializes the conversation to postcard bytes without touching the filesystem, e.g. to store it in a database.
+    ///
+    /// It can be restored with [`ChatGPT::restore_conversation_from_bytes()`].
+    #[cfg(feature = "postcard")]
+    pub fn to_postcard_bytes(&self) -> crate::Result<Vec<u8>> {
+        SavedConversation::to_postcard(&self.to_state())
+    }
+
+    /// Captures the serializable state of this conversation. See [`ConversationState`] for details.
+    pub fn to_state(&self) -> ConversationState {
+        ConversationState {
+            id: self.id,
+            history: self.history.clone(),
+            max_continuations: self.max_continuations,
+            usage: self.usage.clone(),
+            #[cfg(feature = "tokenizer")]
+            summary_id: self.summary_id,
+            #[cfg(not(feature = "tokenizer"))]
+            summary_id: None,
+            #[cfg(feature = "functions")]
+            always_send_functions: self.always_send_functions,
+            #[cfg(not(feature = "functions"))]
+            always_send_functions: false,
+            #[cfg(feature = "functions")]
+            use_tools: self.use_tools,
+            #[cfg(not(feature = "functions"))]
+            use_tools: false,
+        }
+    }
+
+    /// Restores a conversation from its state. Messages without an id are assigned one.
+    pub fn from_state(client: ChatGPT, state: ConversationState) -> Self {
+        let mut conversation = Self::new_with_id(client, state.id, state.history);
+        conversation.max_continuations = state.max_continuations;
+        conversation.usage = state.usage;
+        #[cfg(feature = "tokenizer")]
+        {
+            conversation.summary_id = state.summary_id;
+        }
+        #[cfg(feature = "functions")]
+        {
+            conversation.always_send_functions = state.always_send_functions;
+            conversation.use_tools = state.use_tools;
+        }
+        conversation
+    }
+
     #[cfg(not(feature = "functions"))]
     async fn process_possible_function_response(
         &mut self,
         _message: &ChatMessage,
-    ) -> Option<CompletionResponse> {
+    ) -> Option<crate::Result<CompletionResponse>> {
         None
     }
 
@@ -243,40 +993,54 @@ impl Conversation {
     async fn process_possible_function_response(
         &mut self,
         message: &ChatMessage,
-    ) -> Option<CompletionResponse> {
-        if let Some(call) = &message.function_call {
-            if let Some(Ok(result)) = self.process_function(call).await {
-                Some(result)
-            } else {
-                None
-            }
+    ) -> Option<crate::Result<CompletionResponse>> {
+        if !message.tool_calls.is_empty() {
+            Some(self.process_tool_calls(&message.tool_calls).await)
+        } else if let Some(call) = &message.function_call {
+            self.process_function(call).await
         } else {
             None
         }
     }
 
+    /// Calls the requested tools, and sends their results back to the model.
+    ///
+    /// A result is sent for every call, even if it failed, as the API rejects unanswered tool calls.
+    #[cfg(feature = "functions")]
+    async fn process_tool_calls(
+        &mut self,
+        calls: &[ToolCall],
+    ) -> crate::Result<CompletionResponse> {
+        for call in calls {
+            let message = match self.invoke_function(&call.function).await {
+                // serializing a JSON value can't fail, so every call is answered
+                Ok(result) => ChatMessage::tool_result(call.id.clone(), result.to_string()),
+                Err(err) => ChatMessage::tool_result(call.id.clone(), err.to_string()),
+            };
+            self.push_message(message);
+        }
+        self.complete().await
+    }
+
+    #[cfg(feature = "functions")]
+    async fn invoke_function(
+        &self,
+        call: &FunctionCall,
+    ) -> Result<serde_json::Value, FunctionCallError> {
+        crate::functions::invoke_function(&self.functions, call).await
+    }
+
     // TODO: streamed function processing is technically possible
     #[cfg(feature = "functions")]
     async fn process_function(
         &mut self,
         call: &FunctionCall,
     ) -> Option<crate::Result<CompletionResponse>> {
-        let call_result = if let Some(fnc) = self.functions.get(&call.name) {
-            // TODO: better error handling?
-            // TODO: maybe replace check for SerdeJsonError with a special error?
-            fnc.try_invoke(&call.arguments).await.map_err(|err| {
-                if let crate::err::Error::SerdeJsonError(_) = err {
-                    FunctionCallError::InvalidArguments
-                } else {
-                    FunctionCallError::InnerError(err.to_string())
-                }
-            })
-        } else {
-            Err(FunctionCallError::InvalidFunction)
-        };
+        let call_result = self.invoke_function(call).await;
         if let Ok(result) = call_result {
-            let result = serde_json::to_string(&result);
-            return Some(self.send_role_message(Role::Function, result.ok()?).await);
+            let message = ChatMessage::function_result(call.name.clone(), &result);
+            self.push_message(message.ok()?);
+            return Some(self.complete().await);
         }
 
         if self.client.config.function_validation == FunctionValidationStrategy::Strict {
@@ -291,13 +1055,757 @@ impl Conversation {
     }
 }
 
-#[cfg(feature = "functions")]
-#[derive(Debug, Clone, Error)]
-enum FunctionCallError {
-    #[error("Invalid function call: invalid arguments given to this function")]
-    InvalidArguments,
-    #[error("Invalid function call: this function does not exist")]
-    InvalidFunction,
-    #[error("Exception encountered when calling function: {0}")]
-    InnerError(String),
+/// The serializable state of a conversation, that can be embedded into other structs and stored in any serde format.
+///
+/// Functions are not a part of the state, and have to be added again after restoring the conversation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ConversationState {
+    /// Stable identifier of the conversation
+    pub id: Uuid,
+    /// All the messages sent and received, starting with the beginning system message
+    pub history: Vec<ChatMessage>,
+    /// See [`Conversation::max_continuations`]
+    #[serde(default)]
+    pub max_continuations: u32,
+    /// Whether all functions are sent to API with each message. Ignored without the `functions` crate feature
+    #[serde(default)]
+    pub always_send_functions: bool,
+    /// Whether functions are sent as tools. Ignored without the `functions` crate feature
+    #[serde(default)]
+    pub use_tools: bool,
+    /// See [`Conversation::total_usage()`]
+    #[serde(default)]
+    pub usage: TokenUsage,
+    /// Id of the message with the summary of the last compaction of the history, see
+    /// [`Conversation::compact_history()`]. Ignored without the `tokenizer` crate feature
+    #[serde(default)]
+    pub summary_id: Option<Uuid>,
+}
+
+impl ConversationState {
+    /// The content of the beginning system (or developer) message, if the history starts with one
+    pub fn system_prompt(&self) -> Option<&str> {
+        self.history
+            .first()
+            .filter(|message| message.role.is_instruction())
+            .map(|message| message.content.as_str())
+    }
+}
+
+/// Magic bytes starting conversations saved in postcard format. Legacy saves, containing only the message history,
+/// can't start with them, as no role has the index of the second byte
+#[cfg(feature = "postcard")]
+const POSTCARD_MAGIC: [u8; 4] = *b"CGPT";
+
+/// Version of the layout of [`ConversationState`] in postcard format. Postcard does not describe the fields it
+/// contains, so it has to be increased whenever a field is added, keeping the decoding of the previous versions
+#[cfg(feature = "postcard")]
+const POSTCARD_VERSION: u32 = 1;
+
+/// Messages of the legacy postcard format, containing only the message history
+#[cfg(feature = "postcard")]
+#[derive(Deserialize)]
+struct LegacyChatMessage {
+    role: Role,
+    content: String,
+}
+
+/// Saved conversations, including the legacy format containing only the message history
+#[cfg(any(feature = "json", feature = "postcard"))]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum SavedConversation {
+    Conversation(ConversationState),
+    History(Vec<ChatMessage>),
+}
+
+#[cfg(any(feature = "json", feature = "postcard"))]
+impl SavedConversation {
+    /// Decodes a conversation in postcard format, either a versioned [`ConversationState`] or a legacy history
+    #[cfg(feature = "postcard")]
+    pub fn from_postcard(bytes: &[u8]) -> crate::Result<Self> {
+        let Some(bytes) = bytes.strip_prefix(&POSTCARD_MAGIC) else {
+            let history: Vec<LegacyChatMessage> = postcard::from_bytes(bytes)?;
+            return Ok(Self::History(
+                history
+                    .into_iter()
+                    .map(|message| ChatMessage::new(message.role, message.content))
+                    .collect(),
+            ));
+        };
+        let (version, state): (u32, &[u8]) = postcard::take_from_bytes(bytes)?;
+        match version {
+            POSTCARD_VERSION => Ok(Self::Conversation(postcard::from_bytes(state)?)),
+            _ => Err(crate::err::Error::ParsingError(format!(
+                "Unsupported version {version} of the saved conversation"
+            ))),
+        }
+    }
+
+    /// Encodes the state of a conversation in the current postcard format
+    #[cfg(feature = "postcard")]
+    pub fn to_postcard(state: &ConversationState) -> crate::Result<Vec<u8>> {
+        Ok(postcard::to_allocvec(&(
+            POSTCARD_MAGIC,
+            POSTCARD_VERSION,
+            state,
+        ))?)
+    }
+
+    pub fn into_conversation(self, client: ChatGPT) -> Conversation {
+        match self {
+            Self::Conversation(state) => Conversation::from_state(client, state),
+            Self::History(history) => Conversation::new_with_history(client, history),
+        }
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "json")]
+mod tests {
+    use crate::client::ChatGPT;
+    use crate::converse::{Conversation, TruncationStrategy};
+    use crate::transport::{request_json, JsonTransport};
+    use crate::types::{CompletionResponse, Role};
+
+    #[test]
+    fn test_conversation_ids_survive_saving() -> crate::Result<()> {
+        let client = ChatGPT::new("")?;
+        let conversation = client.new_conversation();
+        let restored =
+            client.restore_conversation_from_str(&conversation.history_to_json_string()?)?;
+        assert_eq!(restored.id, conversation.id);
+        assert_eq!(restored.history, conversation.history);
+        Ok(())
+    }
+
+    #[test]
+    fn test_json_bytes_round_trip() -> crate::Result<()> {
+        let client = ChatGPT::new("")?;
+        let mut conversation = client.new_conversation();
+        conversation.push_user_message("Hello");
+        let restored =
+            client.restore_conversation_from_json_bytes(&conversation.to_json_bytes()?)?;
+        assert_eq!(restored.id, conversation.id);
+        assert_eq!(restored.history, conversation.history);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "postcard")]
+    fn test_postcard_bytes_round_trip() -> crate::Result<()> {
+        let client = ChatGPT::new("")?;
+        let mut conversation = client.new_conversation();
+        conversation.push_user_message("Hello");
+        let restored =
+            client.restore_conversation_from_bytes(&conversation.to_postcard_bytes()?)?;
+        assert_eq!(restored.id, conversation.id);
+        assert_eq!(restored.history, conversation.history);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversation_state() -> crate::Result<()> {
+        let client = ChatGPT::new("")?;
+        let mut conversation = client.new_conversation_directed("Be brief");
+        conversation.max_continuations = 2;
+        let state = conversation.to_state();
+        assert_eq!(state.system_prompt(), Some("Be brief"));
+
+        let restored = Conversation::from_state(client, state);
+        assert_eq!(restored.id, conversation.id);
+        assert_eq!(restored.max_continuations, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_developer_conversation() -> crate::Result<()> {
+        let conversation = ChatGPT::new("")?.new_conversation_developer("Think step by step");
+        assert_eq!(conversation.history[0].role, Role::Developer);
+        assert_eq!(
+            conversation.to_state().system_prompt(),
+            Some("Think step by step")
+        );
+        assert_eq!(
+            serde_json::to_value(Role::Developer)?,
+            serde_json::json!("developer")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversation_user() -> crate::Result<()> {
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.set_user("user-42");
+        let request = conversation
+            .client
+            .completion_request(&conversation.history);
+        assert_eq!(serde_json::to_value(&request)?["user"], "user-42");
+        Ok(())
+    }
+
+    #[test]
+    fn test_choose_reply() -> crate::Result<()> {
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.push_user_message("Name a color");
+        let response: CompletionResponse = serde_json::from_value(serde_json::json!({
+            "choices": [
+                { "index": 0, "message": { "role": "assistant", "content": "Red" } },
+                { "index": 1, "message": { "role": "assistant", "content": "Blue" } }
+            ]
+        }))?;
+        assert_eq!(response.messages().count(), 2);
+        let id = conversation.choose_reply(&response, 1).unwrap();
+        assert_eq!(conversation.message(id).unwrap().content, "Blue");
+        assert!(conversation.choose_reply(&response, 2).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_history() -> crate::Result<()> {
+        let mut conversation = ChatGPT::new("")?.new_conversation_directed("Be brief");
+        for content in ["1", "2", "3", "4", "5"] {
+            conversation.push_user_message(content);
+        }
+        assert!(conversation.truncate_history()?.is_empty());
+        conversation.truncation = Some(TruncationStrategy::LastMessages(2));
+        let removed = conversation.truncate_history()?;
+        assert_eq!(removed.len(), 3);
+        assert_eq!(removed[0].content, "1");
+        let contents: Vec<_> = conversation
+            .history
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents, ["Be brief", "4", "5"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_pinned_messages() -> crate::Result<()> {
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.push_user_message("Example question");
+        let example = conversation.history[1].id.unwrap();
+        for content in ["1", "2", "3"] {
+            conversation.push_user_message(content);
+        }
+        assert!(conversation.pin(example));
+        conversation.truncation = Some(TruncationStrategy::LastMessages(1));
+        assert_eq!(conversation.truncate_history()?.len(), 2);
+        let contents: Vec<_> = conversation
+            .history
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(contents[1..], ["Example question", "3"]);
+
+        assert!(conversation.unpin(example));
+        conversation.push_user_message("4");
+        conversation.truncate_history()?;
+        assert!(conversation.message(example).is_none());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "functions")]
+    fn test_truncate_tool_results() -> crate::Result<()> {
+        use crate::types::ChatMessage;
+
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.push_user_message("Weather?");
+        conversation.push_message(ChatMessage::new(Role::Assistant, ""));
+        conversation.push_message(ChatMessage::tool_result("call_1", "Sunny"));
+        conversation.push_message(ChatMessage::tool_result("call_2", "Warm"));
+        conversation.push_message(ChatMessage::new(Role::Assistant, "Sunny and warm"));
+        conversation.push_user_message("Thanks");
+        conversation.truncation = Some(TruncationStrategy::LastMessages(4));
+        // the results are removed along with the calls
+        assert_eq!(conversation.truncate_history()?.len(), 4);
+        assert_eq!(conversation.history.len(), 3);
+        assert_eq!(conversation.history[1].content, "Sunny and warm");
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "tokenizer")]
+    fn test_truncate_history_tokens() -> crate::Result<()> {
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.push_user_message("hello ".repeat(100));
+        conversation.push_user_message("hello");
+        let tokens = conversation.token_count()?;
+        conversation.truncation = Some(TruncationStrategy::LastTokens(tokens - 1));
+        assert_eq!(conversation.truncate_history()?.len(), 1);
+        assert!(conversation.token_count()? < 50);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokenizer")]
+    async fn test_compact_history() -> crate::Result<()> {
+        use crate::config::ChatGPTEngine;
+        use crate::converse::CompactionConfig;
+
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sent = requests.clone();
+        let transport = JsonTransport(move |request: &reqwest::Request| {
+            let body = request_json(request);
+            let content = if body["model"] == "gpt-4o-mini" {
+                "The user greeted"
+            } else {
+                "Hi"
+            };
+            sent.lock().unwrap().push(body);
+            serde_json::json!({ "choices": [
+                { "index": 0, "message": { "role": "assistant", "content": content } }
+            ] })
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let mut conversation = client.new_conversation_directed("Be brief");
+        conversation.compaction = Some(
+            CompactionConfig::new(30)
+                .with_keep_last(2)
+                .with_engine(ChatGPTEngine::Custom("gpt-4o-mini")),
+        );
+        for message in ["Hello", "How are you?", "What is Rust?"] {
+            conversation.send_message(message).await?;
+        }
+        // the older messages are summarized before the last request, keeping the last 2 of them
+        let history: Vec<_> = conversation
+            .history
+            .iter()
+            .map(|message| (message.role, message.content.as_str()))
+            .collect();
+        assert_eq!(history[0], (Role::System, "Be brief"));
+        assert_eq!(
+            history[1],
+            (
+                Role::System,
+                "Summary of the earlier conversation:\nThe user greeted"
+            )
+        );
+        assert_eq!(
+            history[2..],
+            [
+                (Role::Assistant, "Hi"),
+                (Role::User, "What is Rust?"),
+                (Role::Assistant, "Hi")
+            ]
+        );
+        let requests = requests.lock().unwrap();
+        let summarization = requests
+            .iter()
+            .find(|request| request["model"] == "gpt-4o-mini")
+            .unwrap();
+        assert!(summarization["messages"][1]["content"]
+            .as_str()
+            .unwrap()
+            .starts_with("User: Hello"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversation_usage() -> crate::Result<()> {
+        let client = ChatGPT::new("")?;
+        let mut conversation = client.new_conversation();
+        let response: CompletionResponse = serde_json::from_value(serde_json::json!({
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": "Hi" } }],
+            "usage": { "prompt_tokens": 10, "completion_tokens": 2, "total_tokens": 12 }
+        }))?;
+        conversation.push_user_message("Hello");
+        let first = conversation.choose_reply(&response, 0).unwrap();
+        conversation.push_user_message("Hello again");
+        conversation.choose_reply(&response, 0).unwrap();
+        assert_eq!(
+            conversation
+                .message(first)
+                .unwrap()
+                .usage
+                .as_ref()
+                .unwrap()
+                .total_tokens,
+            12
+        );
+        assert_eq!(conversation.total_usage().total_tokens, 24);
+
+        // the total usage includes removed replies, and is saved along with the conversation
+        conversation.undo(1);
+        let restored =
+            client.restore_conversation_from_str(&conversation.history_to_json_string()?)?;
+        assert_eq!(restored.total_usage().prompt_tokens, 20);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(feature = "streams", feature = "functions"))]
+    fn test_record_response_chunks() -> crate::Result<()> {
+        use crate::types::ResponseChunk;
+
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.push_user_message("What's the weather in Paris?");
+        let chunks = [
+            ResponseChunk::BeginResponse {
+                role: Role::Assistant,
+                response_index: 0,
+            },
+            ResponseChunk::ToolCallBegin {
+                id: Some("call_1".into()),
+                name: "get_weather".into(),
+                call_index: 0,
+                response_index: 0,
+            },
+            ResponseChunk::ToolCallArgumentsDelta {
+                delta: "{\"city\":\"Paris\"}".into(),
+                call_index: 0,
+                response_index: 0,
+            },
+            ResponseChunk::ToolCallEnd {
+                call_index: 0,
+                response_index: 0,
+            },
+            ResponseChunk::CloseResponse {
+                response_index: 0,
+                finish_reason: Some(crate::types::FinishReason::ToolCalls),
+            },
+            ResponseChunk::Done,
+        ];
+        let id = conversation.record_response_chunks(&chunks).unwrap();
+        let message = conversation.message(id).unwrap();
+        assert_eq!(message.role, Role::Assistant);
+        assert_eq!(message.tool_calls.len(), 1);
+        assert_eq!(message.tool_calls[0].id, "call_1");
+        assert_eq!(message.tool_calls[0].function.name, "get_weather");
+        assert_eq!(
+            message.tool_calls[0].function.arguments,
+            "{\"city\":\"Paris\"}"
+        );
+        assert_eq!(conversation.history.len(), 3);
+
+        assert!(conversation.record_response_chunks(&[]).is_none());
+        Ok(())
+    }
+
+    /// Replies with the assistant message built from the sent messages, checking that all tool calls are answered
+    #[cfg(feature = "functions")]
+    fn scripted_transport(
+        reply: fn(&[serde_json::Value]) -> serde_json::Value,
+    ) -> impl crate::transport::HttpTransport {
+        JsonTransport(move |request: &reqwest::Request| {
+            let request = request_json(request);
+            assert_eq!(request["tools"][0]["function"]["name"], "get_weather");
+            let messages = request["messages"].as_array().unwrap();
+            for (index, message) in messages.iter().enumerate() {
+                for call in message["tool_calls"].as_array().into_iter().flatten() {
+                    assert!(messages[index + 1..]
+                        .iter()
+                        .any(|result| result["tool_call_id"] == call["id"]));
+                }
+            }
+            serde_json::json!({ "choices": [{ "index": 0, "message": reply(messages) }] })
+        })
+    }
+
+    #[cfg(feature = "functions")]
+    fn weather_registry() -> crate::functions::ToolRegistry {
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        struct WeatherArguments {
+            city: String,
+        }
+
+        let mut registry = crate::functions::ToolRegistry::new();
+        registry.register(
+            "get_weather",
+            "Gets the weather in a city",
+            |arguments: WeatherArguments| async move { Ok(format!("Sunny in {}", arguments.city)) },
+        );
+        registry
+    }
+
+    #[cfg(feature = "functions")]
+    fn weather_call(arguments: &str) -> serde_json::Value {
+        serde_json::json!({ "role": "assistant", "content": null, "tool_calls": [{
+            "id": "call_1", "type": "function",
+            "function": { "name": "get_weather", "arguments": arguments }
+        }] })
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "functions")]
+    async fn test_send_message_with_tools() -> crate::Result<()> {
+        // calls the weather tool until it receives a tool result, then repeats it
+        let transport = scripted_transport(|messages| {
+            let last = messages.last().unwrap();
+            if last["role"] == "tool" {
+                serde_json::json!({ "role": "assistant", "content": last["content"] })
+            } else {
+                weather_call("{\"city\":\"Paris\"}")
+            }
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let mut conversation = client.new_conversation();
+        let registry = weather_registry();
+        let response = conversation
+            .send_message_with_tools("What's the weather in Paris?", &registry)
+            .await?;
+        assert_eq!(response.message().content, "\"Sunny in Paris\"");
+        let roles = conversation
+            .history
+            .iter()
+            .map(|message| message.role)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roles,
+            [
+                Role::System,
+                Role::User,
+                Role::Assistant,
+                Role::Tool,
+                Role::Assistant
+            ]
+        );
+        assert_eq!(
+            conversation.history[3].tool_call_id.as_deref(),
+            Some("call_1")
+        );
+
+        conversation.max_tool_iterations = 0;
+        let result = conversation
+            .send_message_with_tools("And in Paris again?", &registry)
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::err::Error::ToolIterationsExceeded(0))
+        ));
+        // the pending call is answered, so the conversation can go on
+        let last = conversation.history.last().unwrap();
+        assert_eq!(last.role, Role::Tool);
+        assert_eq!(last.tool_call_id.as_deref(), Some("call_1"));
+        conversation.max_tool_iterations = 1;
+        conversation
+            .send_message_with_tools("And in Paris again?", &registry)
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "functions")]
+    async fn test_failed_tool_result_request() -> crate::Result<()> {
+        use crate::functions::{CallableAsyncFunction, FunctionDescriptor, GptFunction};
+
+        #[derive(serde::Deserialize, schemars::JsonSchema)]
+        struct WeatherArguments {
+            city: String,
+        }
+
+        struct GetWeather;
+
+        #[async_trait::async_trait]
+        impl CallableAsyncFunction<WeatherArguments> for GetWeather {
+            async fn invoke(arguments: WeatherArguments) -> crate::Result<serde_json::Value> {
+                Ok(format!("Sunny in {}", arguments.city).into())
+            }
+        }
+
+        // calls the weather function, then fails the request that sends its result back
+        let transport = JsonTransport(|request: &reqwest::Request| {
+            let request = request_json(request);
+            if request["messages"].as_array().unwrap().last().unwrap()["role"] == "tool" {
+                serde_json::json!({ "error": { "message": "Overloaded", "type": "server_error" } })
+            } else {
+                let reply = weather_call("{\"city\":\"Paris\"}");
+                serde_json::json!({ "choices": [{ "index": 0, "message": reply }] })
+            }
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let mut conversation = client.new_conversation();
+        conversation.use_tools = true;
+        conversation.always_send_functions = true;
+        conversation.add_function(GptFunction {
+            descriptor: FunctionDescriptor {
+                name: "get_weather",
+                description: "Gets the weather in a city",
+                parameters: std::marker::PhantomData::<WeatherArguments>,
+            },
+            callable: std::marker::PhantomData::<GetWeather>,
+        })?;
+        let result = conversation
+            .send_message_functions("What's the weather in Paris?")
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::err::Error::BackendError { message, .. }) if message == "Overloaded"
+        ));
+        // the result is stored, so the conversation can go on
+        let last = conversation.history.last().unwrap();
+        assert_eq!(last.role, Role::Tool);
+        assert_eq!(last.content, "\"Sunny in Paris\"");
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "functions")]
+    async fn test_continuation_sends_tools() -> crate::Result<()> {
+        // cuts the reply off, and checks that its continuation is requested with the tools as well
+        let transport = JsonTransport(|request: &reqwest::Request| {
+            let request = request_json(request);
+            assert_eq!(request["tools"][0]["function"]["name"], "get_weather");
+            let last = request["messages"].as_array().unwrap().last().unwrap();
+            let (content, finish_reason) = if last["content"] == super::CONTINUATION_PROMPT {
+                (" world", "stop")
+            } else {
+                ("Hello", "length")
+            };
+            serde_json::json!({ "choices": [{
+                "index": 0, "finish_reason": finish_reason,
+                "message": { "role": "assistant", "content": content }
+            }] })
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let mut conversation = client.new_conversation();
+        conversation.max_continuations = 1;
+        let response = conversation
+            .send_message_with_tools("Say hello world", &weather_registry())
+            .await?;
+        assert_eq!(response.message().content, "Hello world");
+        assert_eq!(conversation.history.len(), 3);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "functions")]
+    async fn test_tool_argument_repair() -> crate::Result<()> {
+        // forgets the city until it is told about the invalid arguments
+        let transport = scripted_transport(|messages| {
+            let last = messages.last().unwrap();
+            match last["content"].as_str() {
+                Some(content) if content.starts_with("Invalid arguments") => {
+                    weather_call("{\"city\":\"Paris\"}")
+                }
+                Some(content) if last["role"] == "tool" => {
+                    serde_json::json!({ "role": "assistant", "content": content })
+                }
+                _ => weather_call("{}"),
+            }
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let mut conversation = client.new_conversation();
+        conversation.max_argument_repairs = 1;
+        let registry = weather_registry();
+        let response = conversation
+            .send_message_with_tools("What's the weather in Paris?", &registry)
+            .await?;
+        assert_eq!(response.message().content, "\"Sunny in Paris\"");
+        assert_eq!(conversation.history.len(), 7);
+
+        // always forgets the city
+        let transport = scripted_transport(|_| weather_call("{}"));
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let mut conversation = client.new_conversation();
+        conversation.max_argument_repairs = 1;
+        let result = conversation
+            .send_message_with_tools("What's the weather in Paris?", &registry)
+            .await;
+        assert!(matches!(
+            result,
+            Err(crate::err::Error::InvalidToolArguments { name, .. }) if name == "get_weather"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_undo() -> crate::Result<()> {
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        for turn in ["first", "second", "third"] {
+            conversation.push_user_message(turn);
+            conversation.push_assistant_message(turn);
+        }
+        assert_eq!(conversation.len(), 7);
+        assert_eq!(conversation.turn_count(), 3);
+        let removed = conversation.undo(2);
+        assert_eq!(removed.len(), 4);
+        assert_eq!(removed[0].content, "second");
+        assert_eq!(conversation.history.len(), 3);
+
+        // undoing more exchanges than there are keeps the system message
+        conversation.undo(5);
+        assert_eq!(conversation.history.len(), 1);
+        assert_eq!(conversation.history[0].role, Role::System);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rollback() -> crate::Result<()> {
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.push_user_message("Hello");
+        conversation.push_assistant_message("Hi");
+        conversation.push_user_message("Weather?");
+        conversation.push_assistant_message("It is sunny");
+        assert_eq!(
+            conversation.last_user_message().unwrap().content,
+            "Weather?"
+        );
+        let reply = conversation.rollback().unwrap();
+        assert_eq!(reply.content, "It is sunny");
+        assert_eq!(conversation.history.len(), 3);
+        assert_eq!(conversation.last_user_message().unwrap().content, "Hello");
+
+        conversation.rollback();
+        // the system message is kept
+        assert!(conversation.rollback().is_none());
+        assert!(conversation.last_user_message().is_none());
+        assert_eq!(conversation.history.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "functions")]
+    fn test_rollback_tool_calls() -> crate::Result<()> {
+        use crate::types::ChatMessage;
+
+        let mut conversation = ChatGPT::new("")?.new_conversation();
+        conversation.push_user_message("Hello");
+        conversation.push_assistant_message("Hi");
+        conversation.push_user_message("Weather?");
+        conversation.push_message(ChatMessage::new(Role::Assistant, ""));
+        conversation.push_message(ChatMessage::tool_result("call_1", "Sunny"));
+        conversation.push_assistant_message("It is sunny");
+        let reply = conversation.rollback().unwrap();
+        assert_eq!(reply.content, "It is sunny");
+        assert_eq!(conversation.history.len(), 3);
+        assert_eq!(conversation.history[2].content, "Hi");
+        Ok(())
+    }
+
+    #[test]
+    fn test_legacy_history_restoring() -> crate::Result<()> {
+        let legacy = include_str!("../examples/example_conversation.json");
+        let restored = ChatGPT::new("")?.restore_conversation_from_str(legacy)?;
+        assert!(!restored.history.is_empty());
+        assert!(restored.history.iter().all(|message| message.id.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "postcard")]
+    fn test_legacy_postcard_restoring() -> crate::Result<()> {
+        // the legacy format is the plain message history, without a version
+        let legacy =
+            postcard::to_allocvec(&vec![(Role::System, "Be brief"), (Role::User, "Hello")])?;
+        let restored = ChatGPT::new("")?.restore_conversation_from_bytes(&legacy)?;
+        assert_eq!(restored.history.len(), 2);
+        assert_eq!(restored.history[1].role, Role::User);
+        assert_eq!(restored.history[1].content, "Hello");
+        assert!(restored.history.iter().all(|message| message.id.is_some()));
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(all(feature = "postcard", feature = "functions"))]
+    fn test_example_postcard_restoring() -> crate::Result<()> {
+        let saved = include_bytes!("../examples/example_conversation.bin");
+        let restored = ChatGPT::new("")?.restore_conversation_from_bytes(saved)?;
+        assert_eq!(restored.history.len(), 5);
+        assert!(restored.history[0].role.is_instruction());
+        assert_eq!(restored.to_postcard_bytes()?, saved);
+        Ok(())
+    }
 }
@@ -1,4 +1,4 @@
-use std::{env::VarError, string::FromUtf8Error};
+use std::{env::VarError, string::FromUtf8Error, time::Duration};
 
 use reqwest::header::InvalidHeaderValue;
 use thiserror::Error;
@@ -16,9 +16,19 @@ pub enum Error {
     #[error("Parsing error has occurred: {0}")]
     ParsingError(String),
     /// A serde-provoked JSON error has occurred
-    #[cfg(any(feature = "json", feature = "functions"))]
+    #[cfg(any(
+        feature = "json",
+        feature = "functions",
+        feature = "structured",
+        feature = "streams",
+        feature = "responses"
+    ))]
     #[error("Failed to (de)serialize data: {0}")]
     SerdeJsonError(#[from] serde_json::Error),
+    /// A simd-json-provoked error has occurred
+    #[cfg(feature = "simd-json")]
+    #[error("Failed to deserialize data: {0}")]
+    SimdJsonError(#[from] simd_json::Error),
     /// A postcard-provoked error has occurred
     #[error("Failed to (de)serialize data: {0}")]
     #[cfg(feature = "postcard")]
@@ -34,9 +44,104 @@ pub enum Error {
         /// The type of error
         error_type: String,
     },
-    /// A Tokio IO error happened
+    /// The API rejected the request because of rate limits, and it was not retried
+    #[error("Rate limit exceeded, retry after {retry_after:?}")]
+    RateLimited {
+        /// How long to wait before retrying, if reported by the API
+        retry_after: Option<Duration>,
+    },
+    /// The request was not sent, because the endpoint failed repeatedly and the circuit breaker is open
+    #[error("The circuit breaker is open after repeated failures, retry after {retry_after:?}")]
+    CircuitOpen {
+        /// Time until the circuit breaker allows requests again
+        retry_after: Duration,
+    },
+    /// The stream produced no chunks within the idle timeout of the configuration
+    #[cfg(feature = "streams")]
+    #[error("The stream stalled, no data was received within the idle timeout")]
+    StreamStalled,
+    /// The auth provider failed to provide a token
+    #[error("Failed to obtain an auth token: {0}")]
+    AuthError(String),
+    /// An IO error happened
     #[error("Error happened during an IO operation: {0}")]
-    IOError(#[from] tokio::io::Error),
+    IOError(#[from] std::io::Error),
+    /// An error occurred while loading the tokenizer for a model
+    #[cfg(feature = "tokenizer")]
+    #[error("Failed to load tokenizer: {0}")]
+    TokenizerError(String),
+    /// An error occurred while decoding or encoding an image
+    #[cfg(feature = "vision")]
+    #[error("Failed to process image: {0}")]
+    ImageError(#[from] image::ImageError),
+    /// The model reply could not be deserialized into the requested structured output type
+    #[cfg(feature = "structured")]
+    #[error("Failed to deserialize structured output: {source}")]
+    StructuredOutputError {
+        /// The deserialization error
+        source: serde_json::Error,
+        /// The raw text of the model reply
+        raw: String,
+    },
+    /// A function that was not added was called
+    #[cfg(feature = "functions")]
+    #[error("Function `{0}` does not exist")]
+    UnknownFunction(String),
+    /// The arguments of a function call could not be deserialized into the arguments of the function
+    #[cfg(feature = "functions")]
+    #[error("Invalid function arguments: {0}")]
+    InvalidFunctionArguments(serde_json::Error),
+    /// The model kept calling tools after the maximum amount of tool call rounds
+    #[cfg(feature = "functions")]
+    #[error("The model kept calling tools after {0} rounds of tool calls")]
+    ToolIterationsExceeded(u32),
+    /// The model kept calling a tool with invalid arguments, after being asked to repair them
+    #[cfg(feature = "functions")]
+    #[error("The model kept calling `{name}` with invalid arguments: {source}")]
+    InvalidToolArguments {
+        /// Name of the called function
+        name: String,
+        /// The deserialization error of the last call
+        source: serde_json::Error,
+    },
+    /// A run of an assistant ended without completing
+    #[cfg(feature = "assistants")]
+    #[error("The run ended with status `{status}`: {message}")]
+    RunFailed {
+        /// The status of the run
+        status: String,
+        /// Description of the failure, if reported by the API
+        message: String,
+    },
+    /// The request uses an option the selected model does not support, detected by the pre-flight check
+    #[cfg(feature = "models")]
+    #[error("Model `{model}` does not support {option}")]
+    UnsupportedModelOption {
+        /// The model of the request
+        model: String,
+        /// The unsupported option
+        option: String,
+    },
+    /// The prompt of the request, along with its `max_tokens`, does not fit the context window of the model
+    #[cfg(all(feature = "tokenizer", feature = "models"))]
+    #[error("The request takes {tokens} tokens, exceeding the context window of {context_window} tokens")]
+    ContextWindowExceeded {
+        /// The prompt tokens of the request, along with its `max_tokens`
+        tokens: usize,
+        /// The context window of the model
+        context_window: u32,
+    },
+    /// No completion choices were sampled, either because none were requested or the backend returned none
+    #[error("No completion choices were sampled")]
+    NoChoices,
+    /// The request was aborted, because its cancellation token was cancelled
+    #[cfg(feature = "cancellation")]
+    #[error("The request was cancelled")]
+    Cancelled,
+    /// A middleware of the client failed to process a request
+    #[cfg(feature = "middleware")]
+    #[error("A middleware failed to process a request: {0}")]
+    MiddlewareError(reqwest_middleware::Error),
     /// Most likely env var not provided
     #[error("Error while trying to access an environment variable: {0}")]
     VarError(#[from] VarError),
@@ -0,0 +1,191 @@
+use bytes::Bytes;
+use reqwest::{multipart::Form, Method};
+use serde::{Deserialize, Serialize};
+
+use crate::client::{endpoint_bytes, parse_endpoint_response, ChatGPT, Deletion, List};
+use crate::upload::FileUpload;
+
+/// The intended use of an uploaded file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+pub enum FilePurpose {
+    /// Files used by assistants and their tools
+    #[serde(rename = "assistants")]
+    Assistants,
+    /// Input files of the batch API
+    #[serde(rename = "batch")]
+    Batch,
+    /// Training and validation files of fine-tuning jobs
+    #[serde(rename = "fine-tune")]
+    FineTune,
+    /// Images used for vision fine-tuning
+    #[serde(rename = "vision")]
+    Vision,
+    /// Files used as model inputs
+    #[serde(rename = "user_data")]
+    UserData,
+    /// Data sets of evaluations
+    #[serde(rename = "evals")]
+    Evals,
+}
+
+impl FilePurpose {
+    /// The name of the purpose, as sent to the API
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Assistants => "assistants",
+            Self::Batch => "batch",
+            Self::FineTune => "fine-tune",
+            Self::Vision => "vision",
+            Self::UserData => "user_data",
+            Self::Evals => "evals",
+        }
+    }
+}
+
+/// A file stored by the API
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct FileObject {
+    /// Identifier of the file, referenced by other endpoints
+    pub id: String,
+    /// Size of the file in bytes
+    #[serde(default)]
+    pub bytes: u64,
+    /// Unix timestamp of the upload
+    #[serde(default)]
+    pub created_at: u64,
+    /// Name of the uploaded file
+    pub filename: String,
+    /// The intended use of the file. Files created by the API have additional purposes, e.g. `batch_output`
+    pub purpose: String,
+}
+
+impl ChatGPT {
+    /// Uploads a file for the provided purpose, e.g. fine-tuning or batch requests.
+    ///
+    /// The file is streamed as the request is sent, see [`FileUpload`].
+    ///
+    /// Requires the `files` crate feature
+    pub async fn upload_file(
+        &self,
+        file: FileUpload,
+        purpose: FilePurpose,
+    ) -> crate::Result<FileObject> {
+        let form = Form::new()
+            .text("purpose", purpose.as_str())
+            .part("file", file.into_part()?);
+        self.send_multipart_request("files", form).await
+    }
+
+    /// Lists the uploaded files, only including the files with the provided purpose if it is set.
+    ///
+    /// Requires the `files` crate feature
+    pub async fn list_files(&self, purpose: Option<FilePurpose>) -> crate::Result<Vec<FileObject>> {
+        let mut url = self.config.endpoint_url_for("files")?;
+        if let Some(purpose) = purpose {
+            url.query_pairs_mut()
+                .append_pair("purpose", purpose.as_str());
+        }
+        let response = self.fetch_endpoint(Method::GET, url).await?;
+        let list: List<FileObject> = parse_endpoint_response(response).await?;
+        Ok(list.data)
+    }
+
+    /// Retrieves the information about the file with the provided id.
+    ///
+    /// Requires the `files` crate feature
+    pub async fn retrieve_file(&self, id: &str) -> crate::Result<FileObject> {
+        let response = self
+            .fetch_endpoint(Method::GET, self.resource_url("files", &[id])?)
+            .await?;
+        parse_endpoint_response(response).await
+    }
+
+    /// Downloads the contents of the file with the provided id.
+    ///
+    /// Requires the `files` crate feature
+    pub async fn file_content(&self, id: &str) -> crate::Result<Bytes> {
+        let response = self
+            .fetch_endpoint(Method::GET, self.resource_url("files", &[id, "content"])?)
+            .await?;
+        endpoint_bytes(response).await
+    }
+
+    /// Deletes the file with the provided id. Returns `true` if it was deleted.
+    ///
+    /// Requires the `files` crate feature
+    pub async fn delete_file(&self, id: &str) -> crate::Result<bool> {
+        let response = self
+            .fetch_endpoint(Method::DELETE, self.resource_url("files", &[id])?)
+            .await?;
+        let deletion: Deletion = parse_endpoint_response(response).await?;
+        Ok(deletion.deleted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FilePurpose;
+    use crate::client::ChatGPT;
+    use crate::transport::json_response;
+    use crate::upload::FileUpload;
+
+    fn file_object(id: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": id, "object": "file", "bytes": 5, "created_at": 1,
+            "filename": "train.jsonl", "purpose": "fine-tune"
+        })
+    }
+
+    /// Serves a single stored file with the id `file-1`
+    struct FilesTransport;
+
+    #[async_trait::async_trait]
+    impl crate::transport::HttpTransport for FilesTransport {
+        async fn send(&self, request: reqwest::Request) -> crate::Result<reqwest::Response> {
+            let path = request.url().path().to_owned();
+            let query = request.url().query().map(str::to_owned);
+            let (status, body) = match (request.method().as_str(), path.as_str()) {
+                ("POST", "/v1/files") => (200, file_object("file-1").to_string()),
+                ("GET", "/v1/files") => {
+                    assert_eq!(query.as_deref(), Some("purpose=fine-tune"));
+                    let list =
+                        serde_json::json!({ "object": "list", "data": [file_object("file-1")] });
+                    (200, list.to_string())
+                }
+                ("GET", "/v1/files/file-1") => (200, file_object("file-1").to_string()),
+                ("GET", "/v1/files/file-1/content") => (200, "{}\n{}\n".to_owned()),
+                ("DELETE", "/v1/files/file-1") => {
+                    let deletion = serde_json::json!({ "id": "file-1", "deleted": true });
+                    (200, deletion.to_string())
+                }
+                _ => {
+                    let error = serde_json::json!({ "error": {
+                        "message": "No such File object", "type": "invalid_request_error"
+                    } });
+                    (404, error.to_string())
+                }
+            };
+            Ok(json_response(status, body))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_files() -> crate::Result<()> {
+        let client = ChatGPT::new_with_transport("", FilesTransport, Default::default())?;
+        let file = FileUpload::from_bytes(&b"{}\n{}\n"[..], "train.jsonl");
+        let uploaded = client.upload_file(file, FilePurpose::FineTune).await?;
+        assert_eq!(uploaded.id, "file-1");
+
+        let files = client.list_files(Some(FilePurpose::FineTune)).await?;
+        assert_eq!(files, std::slice::from_ref(&uploaded));
+        assert_eq!(client.retrieve_file("file-1").await?, uploaded);
+        assert_eq!(client.file_content("file-1").await?, "{}\n{}\n");
+        assert!(client.delete_file("file-1").await?);
+
+        assert!(matches!(
+            client.file_content("file-2").await,
+            Err(crate::err::Error::BackendError { message, .. }) if message == "No such File object"
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,395 @@
+#[cfg(feature = "streams")]
+use std::{collections::HashSet, collections::VecDeque, time::Duration};
+
+#[cfg(feature = "streams")]
+use futures::Stream;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{parse_endpoint_response, ChatGPT, List};
+
+/// A request creating a fine-tuning job
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize)]
+pub struct FineTuningJobRequest {
+    /// The base model to fine-tune, e.g. `gpt-4o-mini-2024-07-18`
+    pub model: String,
+    /// Id of the uploaded JSONL file with the training data
+    pub training_file: String,
+    /// Id of the uploaded JSONL file with the validation data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validation_file: Option<String>,
+    /// Up to 64 characters added to the name of the fine-tuned model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suffix: Option<String>,
+    /// Seed making the job reproducible
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    /// Hyperparameters of the training, chosen by the API if `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hyperparameters: Option<Hyperparameters>,
+}
+
+impl FineTuningJobRequest {
+    /// Constructs a request fine-tuning the model on the uploaded training file
+    pub fn new<M: Into<String>, F: Into<String>>(model: M, training_file: F) -> Self {
+        Self {
+            model: model.into(),
+            training_file: training_file.into(),
+            validation_file: None,
+            suffix: None,
+            seed: None,
+            hyperparameters: None,
+        }
+    }
+
+    /// Sets the uploaded file with the validation data
+    pub fn with_validation_file<S: Into<String>>(mut self, validation_file: S) -> Self {
+        self.validation_file = Some(validation_file.into());
+        self
+    }
+
+    /// Sets the suffix added to the name of the fine-tuned model
+    pub fn with_suffix<S: Into<String>>(mut self, suffix: S) -> Self {
+        self.suffix = Some(suffix.into());
+        self
+    }
+
+    /// Sets the seed of the job
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Sets the hyperparameters of the training
+    pub fn with_hyperparameters(mut self, hyperparameters: Hyperparameters) -> Self {
+        self.hyperparameters = Some(hyperparameters);
+        self
+    }
+}
+
+/// Hyperparameters of a fine-tuning job. Hyperparameters that are not set are chosen by the API
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize)]
+pub struct Hyperparameters {
+    /// Amount of passes through the training data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n_epochs: Option<u32>,
+    /// Amount of examples in each batch
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub batch_size: Option<u32>,
+    /// Scaling factor of the learning rate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub learning_rate_multiplier: Option<f64>,
+}
+
+/// A fine-tuning job
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct FineTuningJob {
+    /// Identifier of the job
+    pub id: String,
+    /// The base model that is fine-tuned
+    pub model: String,
+    /// Unix timestamp of the creation of the job
+    #[serde(default)]
+    pub created_at: u64,
+    /// Unix timestamp of the end of the job, if it is finished
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    /// Name of the fine-tuned model, once the job succeeded
+    #[serde(default)]
+    pub fine_tuned_model: Option<String>,
+    /// Current status of the job
+    pub status: FineTuningStatus,
+    /// Id of the file with the training data
+    pub training_file: String,
+    /// Id of the file with the validation data
+    #[serde(default)]
+    pub validation_file: Option<String>,
+    /// Ids of the files with the results of the job, e.g. the training metrics
+    #[serde(default)]
+    pub result_files: Vec<String>,
+    /// Amount of billable tokens processed by the job, once it is finished
+    #[serde(default)]
+    pub trained_tokens: Option<u64>,
+    /// The reason of the failure, if the job failed
+    #[serde(default)]
+    pub error: Option<FineTuningError>,
+}
+
+/// The status of a fine-tuning job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FineTuningStatus {
+    /// The training files are being validated
+    ValidatingFiles,
+    /// The job is waiting to be started
+    Queued,
+    /// The model is being trained
+    Running,
+    /// The fine-tuned model is ready
+    Succeeded,
+    /// The job failed, see [`FineTuningJob::error`]
+    Failed,
+    /// The job was cancelled
+    Cancelled,
+}
+
+impl FineTuningStatus {
+    /// Returns `true` if the job will not change its status anymore
+    pub fn is_finished(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Cancelled)
+    }
+}
+
+/// The reason a fine-tuning job failed
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct FineTuningError {
+    /// Machine-readable code of the error
+    #[serde(default)]
+    pub code: String,
+    /// Description of the error
+    #[serde(default)]
+    pub message: String,
+    /// The invalid parameter, if the error was caused by one
+    #[serde(default)]
+    pub param: Option<String>,
+}
+
+/// A progress or status message of a fine-tuning job
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct FineTuningEvent {
+    /// Identifier of the event
+    pub id: String,
+    /// Unix timestamp of the event
+    #[serde(default)]
+    pub created_at: u64,
+    /// Severity of the event, e.g. `info` or `warn`
+    #[serde(default)]
+    pub level: String,
+    /// The message of the event
+    pub message: String,
+}
+
+impl ChatGPT {
+    /// Creates a fine-tuning job. The training files need to be uploaded with the `fine-tune` purpose beforehand.
+    ///
+    /// Requires the `fine_tuning` crate feature
+    pub async fn create_fine_tuning_job(
+        &self,
+        request: &FineTuningJobRequest,
+    ) -> crate::Result<FineTuningJob> {
+        self.send_endpoint_request("fine_tuning/jobs", request)
+            .await
+    }
+
+    /// Lists the fine-tuning jobs of the organization, the most recent first.
+    ///
+    /// Jobs created before the job with the id `after` are listed if it is set, up to `limit` jobs.
+    ///
+    /// Requires the `fine_tuning` crate feature
+    pub async fn list_fine_tuning_jobs(
+        &self,
+        after: Option<&str>,
+        limit: Option<u32>,
+    ) -> crate::Result<Vec<FineTuningJob>> {
+        let url = self.page_url("fine_tuning/jobs", &[], after, limit)?;
+        let response = self.fetch_endpoint(Method::GET, url).await?;
+        let list: List<FineTuningJob> = parse_endpoint_response(response).await?;
+        Ok(list.data)
+    }
+
+    /// Retrieves the fine-tuning job with the provided id.
+    ///
+    /// Requires the `fine_tuning` crate feature
+    pub async fn retrieve_fine_tuning_job(&self, id: &str) -> crate::Result<FineTuningJob> {
+        let url = self.resource_url("fine_tuning/jobs", &[id])?;
+        let response = self.fetch_endpoint(Method::GET, url).await?;
+        parse_endpoint_response(response).await
+    }
+
+    /// Cancels the fine-tuning job with the provided id, returning the job with its updated status.
+    ///
+    /// Requires the `fine_tuning` crate feature
+    pub async fn cancel_fine_tuning_job(&self, id: &str) -> crate::Result<FineTuningJob> {
+        let url = self.resource_url("fine_tuning/jobs", &[id, "cancel"])?;
+        let response = self.fetch_endpoint(Method::POST, url).await?;
+        parse_endpoint_response(response).await
+    }
+
+    /// Lists the events of the fine-tuning job with the provided id, the most recent first.
+    ///
+    /// Events preceding the event with the id `after` are listed if it is set, up to `limit` events.
+    ///
+    /// Requires the `fine_tuning` crate feature
+    pub async fn list_fine_tuning_events(
+        &self,
+        id: &str,
+        after: Option<&str>,
+        limit: Option<u32>,
+    ) -> crate::Result<Vec<FineTuningEvent>> {
+        let url = self.page_url("fine_tuning/jobs", &[id, "events"], after, limit)?;
+        let response = self.fetch_endpoint(Method::GET, url).await?;
+        let list: List<FineTuningEvent> = parse_endpoint_response(response).await?;
+        Ok(list.data)
+    }
+
+    /// Streams the events of the fine-tuning job with the provided id in chronological order, polling the job
+    /// every `poll_interval` until it is finished.
+    ///
+    /// The stream ends after the last event of the finished job, or after the first error.
+    ///
+    /// Requires the `fine_tuning` and `streams` crate features
+    #[cfg(feature = "streams")]
+    pub fn fine_tuning_events<'a>(
+        &'a self,
+        id: &'a str,
+        poll_interval: Duration,
+    ) -> impl Stream<Item = crate::Result<FineTuningEvent>> + 'a {
+        let state = EventPolling {
+            seen: HashSet::new(),
+            pending: VecDeque::new(),
+            finished: false,
+        };
+        futures::stream::unfold(state, move |mut state| async move {
+            loop {
+                if let Some(event) = state.pending.pop_front() {
+                    return Some((Ok(event), state));
+                }
+                if state.finished {
+                    return None;
+                }
+                // the job is polled before its events, so that the events of a finished job are all listed
+                let polled = match self.retrieve_fine_tuning_job(id).await {
+                    Ok(job) => self
+                        .list_fine_tuning_events(id, None, Some(100))
+                        .await
+                        .map(|events| (job, events)),
+                    Err(err) => Err(err),
+                };
+                let (job, events) = match polled {
+                    Ok(polled) => polled,
+                    Err(err) => {
+                        state.finished = true;
+                        return Some((Err(err), state));
+                    }
+                };
+                state.finished = job.status.is_finished();
+                for event in events.into_iter().rev() {
+                    if state.seen.insert(event.id.clone()) {
+                        state.pending.push_back(event);
+                    }
+                }
+                if state.pending.is_empty() && !state.finished {
+                    crate::client::sleep(poll_interval).await;
+                }
+            }
+        })
+    }
+}
+
+/// The state of [`ChatGPT::fine_tuning_events()`]
+#[cfg(feature = "streams")]
+struct EventPolling {
+    seen: HashSet<String>,
+    pending: VecDeque<FineTuningEvent>,
+    finished: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{FineTuningJobRequest, FineTuningStatus, Hyperparameters};
+    use crate::client::ChatGPT;
+    use crate::transport::{request_json, HttpTransport, JsonTransport};
+
+    fn job(status: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": "ftjob-1", "object": "fine_tuning.job", "model": "gpt-4o-mini-2024-07-18",
+            "created_at": 1, "status": status, "training_file": "file-1",
+            "fine_tuned_model": (status == "succeeded").then_some("ft:gpt-4o-mini:org::1"),
+            "result_files": [], "hyperparameters": { "n_epochs": "auto" }
+        })
+    }
+
+    fn events(polls: usize) -> serde_json::Value {
+        let event = |id: &str, message: &str| serde_json::json!({ "id": id, "created_at": 1, "level": "info", "message": message });
+        let mut events = vec![event("ftevent-1", "Job started")];
+        if polls > 1 {
+            events.insert(0, event("ftevent-2", "Job succeeded"));
+        }
+        serde_json::json!({ "object": "list", "data": events, "has_more": false })
+    }
+
+    /// Serves a job that is running on the first poll, and succeeded on the following ones
+    fn job_transport() -> impl HttpTransport {
+        let polls = AtomicUsize::new(0);
+        JsonTransport(move |request: &reqwest::Request| {
+            let path = request.url().path();
+            match (request.method().as_str(), path) {
+                ("POST", "/v1/fine_tuning/jobs") => {
+                    let body = request_json(request);
+                    assert_eq!(body["training_file"], "file-1");
+                    assert_eq!(
+                        body["hyperparameters"],
+                        serde_json::json!({ "n_epochs": 3 })
+                    );
+                    job("validating_files")
+                }
+                ("GET", "/v1/fine_tuning/jobs") => {
+                    assert_eq!(request.url().query(), Some("after=ftjob-0&limit=1"));
+                    serde_json::json!({ "object": "list", "data": [job("queued")] })
+                }
+                ("GET", "/v1/fine_tuning/jobs/ftjob-1") => {
+                    let polls = polls.fetch_add(1, Ordering::SeqCst);
+                    job(if polls == 0 { "running" } else { "succeeded" })
+                }
+                ("GET", "/v1/fine_tuning/jobs/ftjob-1/events") => {
+                    events(polls.load(Ordering::SeqCst))
+                }
+                ("POST", "/v1/fine_tuning/jobs/ftjob-1/cancel") => job("cancelled"),
+                _ => panic!("Unexpected request to {path}"),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fine_tuning_jobs() -> crate::Result<()> {
+        let client = ChatGPT::new_with_transport("", job_transport(), Default::default())?;
+        let request = FineTuningJobRequest::new("gpt-4o-mini-2024-07-18", "file-1")
+            .with_hyperparameters(Hyperparameters {
+                n_epochs: Some(3),
+                ..Default::default()
+            });
+        let job = client.create_fine_tuning_job(&request).await?;
+        assert_eq!(job.status, FineTuningStatus::ValidatingFiles);
+
+        let jobs = client
+            .list_fine_tuning_jobs(Some("ftjob-0"), Some(1))
+            .await?;
+        assert_eq!(jobs[0].status, FineTuningStatus::Queued);
+        let job = client.cancel_fine_tuning_job("ftjob-1").await?;
+        assert!(job.status.is_finished());
+        Ok(())
+    }
+
+    #[cfg(feature = "streams")]
+    #[tokio::test]
+    async fn test_fine_tuning_events() -> crate::Result<()> {
+        use futures::TryStreamExt;
+
+        let client = ChatGPT::new_with_transport("", job_transport(), Default::default())?;
+        let events: Vec<_> = client
+            .fine_tuning_events("ftjob-1", std::time::Duration::from_millis(1))
+            .map_ok(|event| event.message)
+            .try_collect()
+            .await?;
+        assert_eq!(events, ["Job started", "Job succeeded"]);
+
+        let job = client.retrieve_fine_tuning_job("ftjob-1").await?;
+        assert_eq!(
+            job.fine_tuned_model.as_deref(),
+            Some("ft:gpt-4o-mini:org::1")
+        );
+        Ok(())
+    }
+}
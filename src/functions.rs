@@ -1,6 +1,9 @@
+mod registry;
 mod traits;
 mod types;
 
+pub use registry::ToolRegistry;
+pub(crate) use registry::{invoke_function, FunctionCallError};
 pub use traits::*;
 pub use types::*;
 
@@ -18,7 +21,7 @@ pub use gpt_fn_macros::*;
 
 #[cfg(test)]
 mod tests {
-    use crate::functions::FunctionDescriptor;
+    use crate::functions::{FunctionDescriptor, ToolChoice, ToolDescriptor};
     use schemars::JsonSchema;
     use serde::Deserialize;
     use serde_json::json;
@@ -67,4 +70,31 @@ mod tests {
             value
         );
     }
+
+    #[test]
+    pub fn test_tool_serialization() {
+        #[derive(Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct TestArguments {
+            name: String,
+        }
+
+        let tool = ToolDescriptor::from(FunctionDescriptor {
+            name: "test_tool",
+            description: "Used for testing tool serialization",
+            parameters: PhantomData::<TestArguments>,
+        });
+        let value = serde_json::to_value(tool).unwrap();
+        assert_eq!(value["type"], "function");
+        assert_eq!(value["function"]["name"], "test_tool");
+
+        assert_eq!(
+            serde_json::to_value(ToolChoice::Required).unwrap(),
+            json!("required")
+        );
+        assert_eq!(
+            serde_json::to_value(ToolChoice::Function("test_tool".to_owned())).unwrap(),
+            json!({ "type": "function", "function": { "name": "test_tool" } })
+        );
+    }
 }
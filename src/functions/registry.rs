@@ -0,0 +1,327 @@
+use crate::functions::{
+    CallableAsyncFunction, FunctionArgument, FunctionCall, GptFunction, GptFunctionHolder, ToolCall,
+};
+use crate::types::ChatMessage;
+use async_trait::async_trait;
+use schemars::schema_for;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use thiserror::Error;
+
+/// A set of tools the model can call, that are dispatched to their handlers by the crate.
+///
+/// See [`Conversation::send_message_with_tools()`](crate::converse::Conversation::send_message_with_tools)
+#[derive(Default)]
+pub struct ToolRegistry {
+    functions: HashMap<String, Box<dyn GptFunctionHolder>>,
+    descriptors: Vec<serde_json::Value>,
+}
+
+impl ToolRegistry {
+    /// Constructs an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a function to the registry, replacing a previously added function with the same name
+    pub fn add_function<
+        A: FunctionArgument + Send + Sync + 'static,
+        C: CallableAsyncFunction<A> + Send + Sync + 'static,
+    >(
+        &mut self,
+        prebuilt: GptFunction<A, C>,
+    ) -> crate::Result<()> {
+        let descriptor = serde_json::to_value(&prebuilt.descriptor)?;
+        self.insert(
+            prebuilt.descriptor.name.to_owned(),
+            descriptor,
+            Box::new(prebuilt),
+        );
+        Ok(())
+    }
+
+    /// Adds an async handler to the registry under the provided name, replacing a previously added function with the
+    /// same name.
+    ///
+    /// The arguments are deserialized from the tool call, and the result is serialized back to the model. The schema of
+    /// the arguments is derived with `schemars`, so they need to implement [`JsonSchema`](schemars::JsonSchema) and
+    /// [`Deserialize`](serde::Deserialize)
+    pub fn register<A, R, F, Fut>(
+        &mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        handler: F,
+    ) where
+        A: FunctionArgument + Send + 'static,
+        R: Serialize,
+        F: Fn(A) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = crate::Result<R>> + Send,
+    {
+        let name = name.into();
+        let mut parameters = schema_for!(A);
+        parameters.meta_schema = None;
+        let descriptor = serde_json::json!({
+            "name": name,
+            "description": description.into(),
+            "parameters": parameters,
+        });
+        let handler = HandlerFunction {
+            handler,
+            arguments: PhantomData,
+        };
+        self.insert(name, descriptor, Box::new(handler));
+    }
+
+    fn insert(
+        &mut self,
+        name: String,
+        descriptor: serde_json::Value,
+        function: Box<dyn GptFunctionHolder>,
+    ) {
+        self.descriptors
+            .retain(|existing| existing["function"]["name"] != name.as_str());
+        self.descriptors.push(serde_json::json!({
+            "type": "function",
+            "function": descriptor,
+        }));
+        self.functions.insert(name, function);
+    }
+
+    /// Tool descriptors of all the added functions, as sent to the API
+    pub fn descriptors(&self) -> &[serde_json::Value] {
+        &self.descriptors
+    }
+
+    /// Returns `true` if a function with the provided name was added
+    pub fn contains(&self, name: &str) -> bool {
+        self.functions.contains_key(name)
+    }
+
+    /// Amount of added functions
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    /// Returns `true` if no functions were added
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+
+    /// Calls the function with the provided name, deserializing its arguments from the JSON string.
+    ///
+    /// Fails with [`Error::UnknownFunction`](crate::err::Error::UnknownFunction) if no such function was added, and
+    /// with [`Error::InvalidFunctionArguments`](crate::err::Error::InvalidFunctionArguments) if the arguments are
+    /// invalid
+    pub async fn invoke(&self, call: &FunctionCall) -> crate::Result<serde_json::Value> {
+        match self.functions.get(&call.name) {
+            Some(function) => function.try_invoke(&call.arguments).await,
+            None => Err(crate::err::Error::UnknownFunction(call.name.clone())),
+        }
+    }
+
+    /// Calls the function requested by the tool call, and returns the message with its result.
+    ///
+    /// Failed calls are answered with the error description, as the API rejects unanswered tool calls.
+    pub async fn call(&self, call: &ToolCall) -> ChatMessage {
+        self.call_validated(call).await.unwrap_or_else(|_| {
+            let error = FunctionCallError::InvalidArguments;
+            ChatMessage::tool_result(call.id.clone(), error.to_string())
+        })
+    }
+
+    /// Same as [`Self::call()`], but fails with the deserialization error if the arguments of the call are invalid
+    pub(crate) async fn call_validated(
+        &self,
+        call: &ToolCall,
+    ) -> Result<ChatMessage, serde_json::Error> {
+        let result = match self.invoke(&call.function).await {
+            Ok(result) => result.to_string(),
+            Err(crate::err::Error::InvalidFunctionArguments(err)) => return Err(err),
+            Err(crate::err::Error::UnknownFunction(_)) => {
+                FunctionCallError::InvalidFunction.to_string()
+            }
+            Err(err) => FunctionCallError::InnerError(err.to_string()).to_string(),
+        };
+        Ok(ChatMessage::tool_result(call.id.clone(), result))
+    }
+}
+
+impl std::fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ToolRegistry")
+            .field("functions", &self.functions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+/// A function backed by an async handler, see [`ToolRegistry::register()`]
+struct HandlerFunction<A, F> {
+    handler: F,
+    arguments: PhantomData<fn(A)>,
+}
+
+#[async_trait]
+impl<A, R, F, Fut> GptFunctionHolder for HandlerFunction<A, F>
+where
+    A: FunctionArgument + Send + 'static,
+    R: Serialize,
+    F: Fn(A) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = crate::Result<R>> + Send,
+{
+    async fn try_invoke(&self, args: &str) -> crate::Result<serde_json::Value> {
+        let arguments: A =
+            serde_json::from_str(args).map_err(crate::err::Error::InvalidFunctionArguments)?;
+        let result = (self.handler)(arguments).await?;
+        serde_json::to_value(result).map_err(|err| {
+            crate::err::Error::ParsingError(format!(
+                "Failed to serialize the function result: {err}"
+            ))
+        })
+    }
+}
+
+/// Calls the requested function from the provided set
+pub(crate) async fn invoke_function(
+    functions: &HashMap<String, Box<dyn GptFunctionHolder>>,
+    call: &FunctionCall,
+) -> Result<serde_json::Value, FunctionCallError> {
+    if let Some(fnc) = functions.get(&call.name) {
+        fnc.try_invoke(&call.arguments).await.map_err(|err| {
+            if let crate::err::Error::InvalidFunctionArguments(_) = err {
+                FunctionCallError::InvalidArguments
+            } else {
+                FunctionCallError::InnerError(err.to_string())
+            }
+        })
+    } else {
+        Err(FunctionCallError::InvalidFunction)
+    }
+}
+
+#[derive(Debug, Clone, Error)]
+pub(crate) enum FunctionCallError {
+    #[error("Invalid function call: invalid arguments given to this function")]
+    InvalidArguments,
+    #[error("Invalid function call: this function does not exist")]
+    InvalidFunction,
+    #[error("Exception encountered when calling function: {0}")]
+    InnerError(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ToolRegistry;
+    use crate::err::Error;
+    use crate::functions::{FunctionCall, ToolCall};
+    use crate::types::Role;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct AddArguments {
+        /// First term
+        a: i64,
+        /// Second term
+        b: i64,
+    }
+
+    fn call(name: &str, arguments: &str) -> FunctionCall {
+        FunctionCall {
+            name: name.to_owned(),
+            arguments: arguments.to_owned(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_handler() -> crate::Result<()> {
+        let mut registry = ToolRegistry::new();
+        registry.register("add", "Adds two numbers", |args: AddArguments| async move {
+            Ok(args.a + args.b)
+        });
+        assert!(registry.contains("add"));
+        assert_eq!(registry.descriptors()[0]["type"], "function");
+        assert_eq!(registry.descriptors()[0]["function"]["name"], "add");
+        assert_eq!(
+            registry.descriptors()[0]["function"]["parameters"]["required"],
+            serde_json::json!(["a", "b"])
+        );
+
+        let sum = registry.invoke(&call("add", r#"{"a":2,"b":3}"#)).await?;
+        assert_eq!(sum, serde_json::json!(5));
+        assert!(matches!(
+            registry.invoke(&call("add", r#"{"a":2}"#)).await,
+            Err(Error::InvalidFunctionArguments(_))
+        ));
+        assert!(matches!(
+            registry.invoke(&call("subtract", "{}")).await,
+            Err(Error::UnknownFunction(name)) if name == "subtract"
+        ));
+
+        let message = registry
+            .call(&ToolCall {
+                id: "call_1".to_owned(),
+                tool_type: "function".to_owned(),
+                function: call("add", r#"{"a":1,"b":1}"#),
+            })
+            .await;
+        assert_eq!(message.role, Role::Tool);
+        assert_eq!(message.tool_call_id.as_deref(), Some("call_1"));
+        assert_eq!(message.content, "2");
+
+        // registering a function with the same name replaces it
+        registry.register("add", "Adds two numbers", |_: AddArguments| async move {
+            Ok("replaced")
+        });
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.descriptors().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_handler_json_error() -> crate::Result<()> {
+        let mut registry = ToolRegistry::new();
+        registry.register("parse", "Parses a number", |_: AddArguments| async move {
+            Ok(serde_json::from_str::<i64>("not a number")?)
+        });
+        let tool_call = ToolCall {
+            id: "call_1".to_owned(),
+            tool_type: "function".to_owned(),
+            function: call("parse", r#"{"a":1,"b":1}"#),
+        };
+        // the arguments are valid, so the JSON error of the handler is not reported as invalid arguments
+        let message = registry
+            .call_validated(&tool_call)
+            .await
+            .expect("the arguments are valid");
+        assert!(message
+            .content
+            .starts_with("Exception encountered when calling function"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unserializable_result() {
+        let mut registry = ToolRegistry::new();
+        // maps with non-string keys can not be serialized to JSON
+        registry.register("pairs", "Returns pairs", |_: AddArguments| async move {
+            Ok(std::collections::HashMap::from([((1, 2), 3)]))
+        });
+        let arguments = r#"{"a":1,"b":1}"#;
+        assert!(matches!(
+            registry.invoke(&call("pairs", arguments)).await,
+            Err(Error::ParsingError(_))
+        ));
+        let message = registry
+            .call(&ToolCall {
+                id: "call_1".to_owned(),
+                tool_type: "function".to_owned(),
+                function: call("pairs", arguments),
+            })
+            .await;
+        assert!(message
+            .content
+            .starts_with("Exception encountered when calling function"));
+    }
+}
@@ -58,13 +58,14 @@ impl<A: FunctionArgument + Send + Sync, C: CallableAsyncFunction<A> + Send + Syn
     for GptFunction<A, C>
 {
     async fn try_invoke(&self, args: &str) -> crate::Result<Value> {
-        let args_value: A = serde_json::from_str(args).map_err(crate::err::Error::from)?;
+        let args_value: A =
+            serde_json::from_str(args).map_err(crate::err::Error::InvalidFunctionArguments)?;
         C::invoke(args_value).await
     }
 }
 
 /// Determines how ChatGPT will be calling the functions.
-#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Serialize)]
+#[derive(Debug, Copy, Clone, PartialOrd, PartialEq, Eq, Hash, Serialize)]
 pub enum FunctionCallingMode {
     /// ChatGPT automatically determines if it should call a function
     Auto,
@@ -73,7 +74,7 @@ pub enum FunctionCallingMode {
 }
 
 /// Determines how this client will validate function calls.
-#[derive(Serialize, Debug, Copy, Clone, Default, PartialOrd, PartialEq)]
+#[derive(Serialize, Debug, Copy, Clone, Default, PartialOrd, PartialEq, Eq, Hash)]
 pub enum FunctionValidationStrategy {
     /// Whenever ChatGPT attempts to call an undefined function, or calls a functions with wrong parameters, sends a `System` message correcting it.
     Strict,
@@ -83,10 +84,88 @@ pub enum FunctionValidationStrategy {
 }
 
 /// Represents a function call attempted by ChatGPT API
-#[derive(Debug, Clone, PartialOrd, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct FunctionCall {
     /// Name of the function attempted to call
     pub name: String,
     /// Arguments used to call this function, represented by a stringified JSON Object
     pub arguments: String,
 }
+
+/// A descriptor of a tool that can be called by the model. Only function tools are currently supported
+#[derive(Debug, Clone)]
+pub struct ToolDescriptor<A: FunctionArgument> {
+    /// Descriptor of the function called by this tool
+    pub function: FunctionDescriptor<A>,
+}
+
+impl<A: FunctionArgument> From<FunctionDescriptor<A>> for ToolDescriptor<A> {
+    fn from(function: FunctionDescriptor<A>) -> Self {
+        Self { function }
+    }
+}
+
+impl<A: FunctionArgument> Serialize for ToolDescriptor<A> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut s = serializer.serialize_struct("ToolDescriptor", 2)?;
+        s.serialize_field("type", "function")?;
+        s.serialize_field("function", &self.function)?;
+        s.end()
+    }
+}
+
+/// Determines whether and which tools the model calls
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Hash)]
+pub enum ToolChoice {
+    /// The model automatically determines if it should call a tool. This is the default when tools are provided
+    Auto,
+    /// The model does not call any tools
+    None,
+    /// The model calls at least one tool
+    Required,
+    /// The model calls the function with the provided name
+    Function(String),
+}
+
+impl Serialize for ToolChoice {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            ToolChoice::Auto => serializer.serialize_str("auto"),
+            ToolChoice::None => serializer.serialize_str("none"),
+            ToolChoice::Required => serializer.serialize_str("required"),
+            ToolChoice::Function(name) => {
+                #[derive(Serialize)]
+                struct NamedFunction<'a> {
+                    name: &'a str,
+                }
+
+                let mut s = serializer.serialize_struct("ToolChoice", 2)?;
+                s.serialize_field("type", "function")?;
+                s.serialize_field("function", &NamedFunction { name })?;
+                s.end()
+            }
+        }
+    }
+}
+
+/// Represents a tool call attempted by the model
+#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ToolCall {
+    /// Identifier of this call, that has to be referenced by the message with the result of the call
+    pub id: String,
+    /// Type of the tool, currently always `function`
+    #[serde(rename = "type", default = "function_tool_type")]
+    pub tool_type: String,
+    /// The function called
+    pub function: FunctionCall,
+}
+
+fn function_tool_type() -> String {
+    "function".to_owned()
+}
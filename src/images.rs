@@ -0,0 +1,253 @@
+#[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::client::ChatGPT;
+
+/// Options of an image generation request. Options that are not set are not sent, so the endpoint defaults apply
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Serialize)]
+pub struct ImageOptions {
+    /// The model generating the images, e.g. `dall-e-3` or `gpt-image-1`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Amount of images to generate
+    #[serde(rename = "n", skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    /// Size of the generated images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<ImageSize>,
+    /// Quality of the generated images
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality: Option<ImageQuality>,
+    /// Whether the images are returned as URLs or as base64 data. Not supported by `gpt-image-1`, which always
+    /// returns base64 data
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ImageResponseFormat>,
+}
+
+impl ImageOptions {
+    /// Sets the model generating the images
+    pub fn with_model<S: Into<String>>(mut self, model: S) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Sets the amount of images to generate
+    pub fn with_count(mut self, count: u32) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    /// Sets the size of the generated images
+    pub fn with_size(mut self, size: ImageSize) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Sets the quality of the generated images
+    pub fn with_quality(mut self, quality: ImageQuality) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    /// Sets whether the images are returned as URLs or as base64 data
+    pub fn with_response_format(mut self, response_format: ImageResponseFormat) -> Self {
+        self.response_format = Some(response_format);
+        self
+    }
+}
+
+/// Size of the generated images. Supported sizes depend on the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize)]
+pub enum ImageSize {
+    /// 256x256, `dall-e-2` only
+    #[serde(rename = "256x256")]
+    Square256,
+    /// 512x512, `dall-e-2` only
+    #[serde(rename = "512x512")]
+    Square512,
+    /// 1024x1024, supported by all models
+    #[serde(rename = "1024x1024")]
+    Square1024,
+    /// 1792x1024, `dall-e-3` only
+    #[serde(rename = "1792x1024")]
+    Landscape1792x1024,
+    /// 1024x1792, `dall-e-3` only
+    #[serde(rename = "1024x1792")]
+    Portrait1024x1792,
+    /// 1536x1024, `gpt-image-1` only
+    #[serde(rename = "1536x1024")]
+    Landscape1536x1024,
+    /// 1024x1536, `gpt-image-1` only
+    #[serde(rename = "1024x1536")]
+    Portrait1024x1536,
+    /// Chosen by the model, `gpt-image-1` only
+    #[serde(rename = "auto")]
+    Auto,
+}
+
+/// Quality of the generated images. Supported values depend on the model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageQuality {
+    /// Standard quality, `dall-e-3` only
+    Standard,
+    /// Finer details and greater consistency, `dall-e-3` only
+    Hd,
+    /// `gpt-image-1` only
+    Low,
+    /// `gpt-image-1` only
+    Medium,
+    /// `gpt-image-1` only
+    High,
+    /// Chosen by the model, `gpt-image-1` only
+    Auto,
+}
+
+/// How the generated images are returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageResponseFormat {
+    /// As URLs, that stay valid for an hour after the generation
+    Url,
+    /// As base64 encoded data
+    B64Json,
+}
+
+/// The request to the image generation endpoint
+#[derive(Debug, Serialize)]
+struct ImageRequest<'a> {
+    prompt: &'a str,
+    #[serde(flatten)]
+    options: &'a ImageOptions,
+}
+
+/// The images generated by the image generation endpoint
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct ImageResponse {
+    /// Unix timestamp of the generation
+    #[serde(default)]
+    pub created: u64,
+    /// The generated images
+    pub data: Vec<GeneratedImage>,
+}
+
+/// A single generated image
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct GeneratedImage {
+    /// URL of the image, if it was requested as a URL
+    #[serde(default)]
+    pub url: Option<String>,
+    /// The base64 encoded image, if it was requested as base64 data
+    #[serde(default)]
+    pub b64_json: Option<String>,
+    /// The prompt the image was actually generated from, if the model revised it
+    #[serde(default)]
+    pub revised_prompt: Option<String>,
+}
+
+impl GeneratedImage {
+    /// Decodes the image data, if it was returned as base64 data
+    pub fn decode(&self) -> crate::Result<Option<Vec<u8>>> {
+        use base64::Engine;
+
+        self.b64_json
+            .as_deref()
+            .map(|data| {
+                base64::engine::general_purpose::STANDARD
+                    .decode(data)
+                    .map_err(|err| crate::err::Error::ParsingError(err.to_string()))
+            })
+            .transpose()
+    }
+}
+
+impl ChatGPT {
+    /// Generates images from the prompt with the image generation endpoint.
+    ///
+    /// Requires the `images` crate feature
+    pub async fn generate_image<S: AsRef<str>>(
+        &self,
+        prompt: S,
+        options: &ImageOptions,
+    ) -> crate::Result<ImageResponse> {
+        let request = ImageRequest {
+            prompt: prompt.as_ref(),
+            options,
+        };
+        self.send_endpoint_request("images/generations", &request)
+            .await
+    }
+
+    /// Saves the generated image to a local file, downloading it first if it was returned as a URL.
+    ///
+    /// Requires the `images` and `tokio` crate features
+    #[cfg(all(feature = "tokio", not(target_arch = "wasm32")))]
+    pub async fn save_image<P: AsRef<Path>>(
+        &self,
+        image: &GeneratedImage,
+        path: P,
+    ) -> crate::Result<()> {
+        let bytes = match (image.decode()?, &image.url) {
+            (Some(bytes), _) => bytes.into(),
+            (None, Some(url)) => self.download(url).await?,
+            (None, None) => {
+                return Err(crate::err::Error::ParsingError(
+                    "The generated image contains neither data nor a URL".to_owned(),
+                ))
+            }
+        };
+        tokio::fs::write(path, bytes).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImageOptions, ImageQuality, ImageResponseFormat, ImageSize};
+    use crate::client::ChatGPT;
+    use crate::transport::{request_json, JsonTransport};
+
+    #[tokio::test]
+    async fn test_generate_image() -> crate::Result<()> {
+        // returns a single base64 image, echoing the request as the revised prompt
+        let transport = JsonTransport(|request: &reqwest::Request| {
+            assert_eq!(
+                request.url().as_str(),
+                "https://api.openai.com/v1/images/generations"
+            );
+            serde_json::json!({ "created": 1, "data": [{
+                "b64_json": "aW1hZ2U=",
+                "revised_prompt": request_json(request).to_string()
+            }] })
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let options = ImageOptions::default()
+            .with_model("dall-e-3")
+            .with_size(ImageSize::Landscape1792x1024)
+            .with_quality(ImageQuality::Hd)
+            .with_response_format(ImageResponseFormat::B64Json);
+        let response = client.generate_image("A red fox", &options).await?;
+        let image = &response.data[0];
+        let request: serde_json::Value =
+            serde_json::from_str(image.revised_prompt.as_deref().unwrap())?;
+        assert_eq!(
+            request,
+            serde_json::json!({
+                "prompt": "A red fox",
+                "model": "dall-e-3",
+                "size": "1792x1024",
+                "quality": "hd",
+                "response_format": "b64_json"
+            })
+        );
+        assert_eq!(image.decode()?.as_deref(), Some(&b"image"[..]));
+
+        let path = std::env::temp_dir().join(format!("chatgpt_rs_{}.png", uuid::Uuid::new_v4()));
+        client.save_image(image, &path).await?;
+        assert_eq!(tokio::fs::read(&path).await?, b"image");
+        tokio::fs::remove_file(&path).await?;
+        Ok(())
+    }
+}
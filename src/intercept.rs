@@ -0,0 +1,41 @@
+use async_trait::async_trait;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use url::Url;
+
+/// An outgoing request, that interceptors may modify before it is sent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestParts {
+    /// The endpoint the request is sent to
+    pub url: Url,
+    /// Headers of the request, including the authentication headers
+    pub headers: HeaderMap,
+    /// The serialized JSON body of the request
+    pub body: Vec<u8>,
+}
+
+/// A received response. The body is not included, since it may still be streamed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResponseParts {
+    /// The endpoint the request was sent to
+    pub url: Url,
+    /// HTTP status of the response
+    pub status: StatusCode,
+    /// Headers of the response
+    pub headers: HeaderMap,
+}
+
+/// A hook that is called around each request sent to the API, e.g. to inject headers required by a gateway,
+/// or to log the payloads.
+///
+/// Interceptors are called in the order they were registered, and again for each retry of a request.
+/// See [`ChatGPT::with_interceptor()`](crate::client::ChatGPT::with_interceptor).
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait Interceptor: Send + Sync {
+    /// Called before the request is sent
+    async fn on_request(&self, _request: &mut RequestParts) {}
+
+    /// Called once the response headers are received
+    async fn on_response(&self, _response: &ResponseParts) {}
+}
@@ -0,0 +1,333 @@
+use reqwest::Method;
+use serde::Deserialize;
+
+use crate::client::{parse_endpoint_response, ChatGPT};
+use crate::types::{CompletionRequest, ContentPart};
+
+/// A model available to the account
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct Model {
+    /// Identifier of the model, used as the engine of requests
+    pub id: String,
+    /// Unix timestamp of the release of the model
+    #[serde(default)]
+    pub created: u64,
+    /// The organization owning the model, e.g. `system` or the organization of a fine-tuned model
+    #[serde(default)]
+    pub owned_by: String,
+}
+
+/// Capabilities of a model, known from the built-in capability table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModelCapabilities {
+    /// The maximum amount of tokens of the prompt and the completion combined
+    pub context_window: u32,
+    /// The maximum amount of tokens the model generates in a single completion
+    pub max_output_tokens: u32,
+    /// Whether the model accepts images in messages
+    pub vision: bool,
+    /// Whether the model can call tools and functions
+    pub tools: bool,
+    /// Whether the model accepts custom `temperature` and `top_p`, which o-series reasoning models reject
+    pub sampling: bool,
+}
+
+impl ModelCapabilities {
+    const fn new(context_window: u32, max_output_tokens: u32, vision: bool, tools: bool) -> Self {
+        Self {
+            context_window,
+            max_output_tokens,
+            vision,
+            tools,
+            sampling: true,
+        }
+    }
+
+    const fn reasoning(
+        context_window: u32,
+        max_output_tokens: u32,
+        vision: bool,
+        tools: bool,
+    ) -> Self {
+        Self {
+            sampling: false,
+            ..Self::new(context_window, max_output_tokens, vision, tools)
+        }
+    }
+
+    /// Looks up the capabilities of the model in the built-in table, matching the longest known prefix of the
+    /// model identifier, so dated snapshots (e.g. `gpt-4o-2024-08-06`) and fine-tuned models
+    /// (e.g. `ft:gpt-4o-mini:org::id`) resolve to their base model. Returns `None` for unknown models
+    pub fn of(model: &str) -> Option<Self> {
+        crate::config::lookup_model(CAPABILITIES, model)
+    }
+
+    /// Checks that the request only uses options supported by the model,
+    /// returning [`Error::UnsupportedModelOption`](crate::err::Error::UnsupportedModelOption) otherwise
+    pub fn check(&self, request: &CompletionRequest<'_>) -> crate::Result<()> {
+        let unsupported = |option: &str| {
+            Err(crate::err::Error::UnsupportedModelOption {
+                model: request.model.to_string(),
+                option: option.to_owned(),
+            })
+        };
+        let has_images = request.messages.iter().any(|message| {
+            message
+                .content_parts
+                .iter()
+                .any(|part| matches!(part, ContentPart::Image(_)))
+        });
+        if has_images && !self.vision {
+            return unsupported("images");
+        }
+        #[cfg(feature = "functions")]
+        if (!request.tools.is_empty() || !request.functions.is_empty()) && !self.tools {
+            return unsupported("tools");
+        }
+        if !self.sampling && (request.temperature != 1.0 || request.top_p != 1.0) {
+            return unsupported("custom temperature or top_p");
+        }
+        if !self.sampling && request.max_tokens.is_some() {
+            return unsupported("max_tokens, use max_completion_tokens instead");
+        }
+        let max_tokens = request.max_completion_tokens.or(request.max_tokens);
+        if max_tokens.is_some_and(|max_tokens| max_tokens > self.max_output_tokens) {
+            return unsupported(&format!(
+                "more than {} output tokens",
+                self.max_output_tokens
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Known models by the prefix of their identifier
+const CAPABILITIES: &[(&str, ModelCapabilities)] = &[
+    (
+        "gpt-3.5-turbo",
+        ModelCapabilities::new(16_385, 4_096, false, true),
+    ),
+    (
+        "gpt-3.5-turbo-instruct",
+        ModelCapabilities::new(4_096, 4_096, false, false),
+    ),
+    ("gpt-4", ModelCapabilities::new(8_192, 8_192, false, true)),
+    (
+        "gpt-4-32k",
+        ModelCapabilities::new(32_768, 32_768, false, true),
+    ),
+    (
+        "gpt-4-turbo",
+        ModelCapabilities::new(128_000, 4_096, true, true),
+    ),
+    (
+        "gpt-4-1106-preview",
+        ModelCapabilities::new(128_000, 4_096, false, true),
+    ),
+    (
+        "gpt-4-vision-preview",
+        ModelCapabilities::new(128_000, 4_096, true, false),
+    ),
+    (
+        "gpt-4-0125-preview",
+        ModelCapabilities::new(128_000, 4_096, false, true),
+    ),
+    (
+        "gpt-4o",
+        ModelCapabilities::new(128_000, 16_384, true, true),
+    ),
+    (
+        "gpt-4o-mini",
+        ModelCapabilities::new(128_000, 16_384, true, true),
+    ),
+    (
+        "gpt-4.1",
+        ModelCapabilities::new(1_047_576, 32_768, true, true),
+    ),
+    (
+        "gpt-4.5-preview",
+        ModelCapabilities::new(128_000, 16_384, true, true),
+    ),
+    (
+        "o1",
+        ModelCapabilities::reasoning(200_000, 100_000, true, true),
+    ),
+    (
+        "o1-mini",
+        ModelCapabilities::reasoning(128_000, 65_536, false, false),
+    ),
+    (
+        "o1-preview",
+        ModelCapabilities::reasoning(128_000, 32_768, false, false),
+    ),
+    (
+        "o3",
+        ModelCapabilities::reasoning(200_000, 100_000, true, true),
+    ),
+    (
+        "o3-mini",
+        ModelCapabilities::reasoning(200_000, 100_000, false, true),
+    ),
+    (
+        "o4-mini",
+        ModelCapabilities::reasoning(200_000, 100_000, true, true),
+    ),
+];
+
+/// A model along with its capabilities
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    /// The model, as reported by the API
+    pub model: Model,
+    /// Capabilities of the model from the built-in capability table, `None` if the model is unknown
+    pub capabilities: Option<ModelCapabilities>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelList {
+    data: Vec<Model>,
+}
+
+impl ChatGPT {
+    /// Lists the models available to the account, including fine-tuned models.
+    ///
+    /// Requires the `models` crate feature
+    pub async fn list_models(&self) -> crate::Result<Vec<Model>> {
+        let url = self.resource_url("models", &[])?;
+        let response = self.fetch_endpoint(Method::GET, url).await?;
+        let list: ModelList = parse_endpoint_response(response).await?;
+        Ok(list.data)
+    }
+
+    /// Returns `true` if the engine of the configuration is available to the account.
+    ///
+    /// Requires the `models` crate feature
+    pub async fn engine_available(&self) -> crate::Result<bool> {
+        let engine = self.config.engine.as_str();
+        Ok(self
+            .list_models()
+            .await?
+            .iter()
+            .any(|model| model.id == engine))
+    }
+
+    /// Retrieves the model from the API, along with its capabilities from the built-in capability table.
+    ///
+    /// Requires the `models` crate feature
+    pub async fn model_info(&self, engine: &str) -> crate::Result<ModelInfo> {
+        let url = self.resource_url("models", &[engine])?;
+        let response = self.fetch_endpoint(Method::GET, url).await?;
+        let model: Model = parse_endpoint_response(response).await?;
+        let capabilities = ModelCapabilities::of(&model.id);
+        Ok(ModelInfo {
+            model,
+            capabilities,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelCapabilities;
+    use crate::client::ChatGPT;
+    use crate::config::{ChatGPTEngine, ModelConfiguration};
+    use crate::transport::{HttpTransport, JsonTransport};
+    use crate::types::{ChatMessage, CompletionRequest, ImagePart};
+
+    fn models_transport() -> impl HttpTransport {
+        JsonTransport(|request: &reqwest::Request| {
+            assert_eq!(request.method(), reqwest::Method::GET);
+            match request.url().path() {
+                "/v1/models" => serde_json::json!({ "object": "list", "data": [
+                    { "id": "gpt-4o", "object": "model", "created": 1715367049, "owned_by": "system" },
+                    { "id": "ft:gpt-4o-mini:org::1", "object": "model", "created": 1, "owned_by": "org" }
+                ] }),
+                "/v1/models/ft:gpt-4o-mini:org::1" => serde_json::json!(
+                    { "id": "ft:gpt-4o-mini:org::1", "object": "model", "created": 1, "owned_by": "org" }
+                ),
+                path => panic!("unexpected request to {path}"),
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_list_models() -> crate::Result<()> {
+        let client = ChatGPT::new_with_transport("", models_transport(), Default::default())?;
+        let models = client.list_models().await?;
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[1].owned_by, "org");
+        // the default engine is not listed
+        assert!(!client.engine_available().await?);
+
+        let config = ModelConfiguration {
+            engine: ChatGPTEngine::Custom("gpt-4o"),
+            ..Default::default()
+        };
+        let client = ChatGPT::new_with_transport("", models_transport(), config)?;
+        assert!(client.engine_available().await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_model_info() -> crate::Result<()> {
+        let client = ChatGPT::new_with_transport("", models_transport(), Default::default())?;
+        let info = client.model_info("ft:gpt-4o-mini:org::1").await?;
+        assert_eq!(info.model.owned_by, "org");
+        let capabilities = info.capabilities.unwrap();
+        assert_eq!(capabilities.context_window, 128_000);
+        assert!(capabilities.vision);
+        Ok(())
+    }
+
+    #[test]
+    fn test_capability_lookup() {
+        assert_eq!(
+            ModelCapabilities::of("gpt-4o-2024-08-06"),
+            ModelCapabilities::of("gpt-4o")
+        );
+        assert!(!ModelCapabilities::of("gpt-4-0613").unwrap().vision);
+        assert!(!ModelCapabilities::of("o3-mini").unwrap().sampling);
+        assert!(ModelCapabilities::of("llama3").is_none());
+    }
+
+    struct UnreachableTransport;
+
+    #[async_trait::async_trait]
+    impl crate::transport::HttpTransport for UnreachableTransport {
+        async fn send(&self, _: reqwest::Request) -> crate::Result<reqwest::Response> {
+            panic!("the request must be rejected before being sent")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_preflight_check() -> crate::Result<()> {
+        let config = ModelConfiguration {
+            engine: ChatGPTEngine::Custom("gpt-3.5-turbo"),
+            validate_model_capabilities: true,
+            ..Default::default()
+        };
+        let client = ChatGPT::new_with_transport("", UnreachableTransport, config)?;
+        let history = [ChatMessage::new(crate::types::Role::User, "What is this?")
+            .with_content_part(ImagePart::from_url("https://example.com/image.png"))];
+        let err = client.send_history(&history).await.unwrap_err();
+        assert!(matches!(
+            err,
+            crate::err::Error::UnsupportedModelOption { ref option, .. } if option == "images"
+        ));
+
+        let config = ModelConfiguration {
+            engine: ChatGPTEngine::Custom("o3-mini"),
+            ..Default::default()
+        };
+        let request = CompletionRequest::from_config(&config, Vec::new());
+        let capabilities = ModelCapabilities::of("o3-mini").unwrap();
+        // the default temperature is rejected by reasoning models
+        assert!(capabilities.check(&request).is_err());
+        let request = CompletionRequest {
+            temperature: 1.0,
+            ..request
+        };
+        assert!(capabilities.check(&request).is_ok());
+        Ok(())
+    }
+}
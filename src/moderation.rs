@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+
+use crate::client::ChatGPT;
+
+/// The request to the moderations endpoint
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct ModerationRequest<'a> {
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+}
+
+/// The classification of the inputs sent to the moderations endpoint
+#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+pub struct ModerationResponse {
+    /// Unique ID of the moderation request
+    #[serde(default)]
+    pub id: String,
+    /// The model used for the classification, e.g. `omni-moderation-latest`
+    #[serde(default)]
+    pub model: String,
+    /// Classification of each input, in the order they were sent
+    pub results: Vec<ModerationResult>,
+}
+
+impl ModerationResponse {
+    /// Whether any of the inputs was flagged as potentially harmful
+    pub fn is_flagged(&self) -> bool {
+        self.results.iter().any(|result| result.flagged)
+    }
+}
+
+/// The classification of a single input
+#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+pub struct ModerationResult {
+    /// Whether the input was flagged as potentially harmful in any category
+    pub flagged: bool,
+    /// Whether the input was flagged in each category
+    pub categories: ModerationCategories<bool>,
+    /// Confidence of the model in each category, between 0 and 1
+    pub category_scores: ModerationCategories<f64>,
+}
+
+impl ModerationResult {
+    /// Whether the input was flagged as potentially harmful in any category
+    pub fn is_flagged(&self) -> bool {
+        self.flagged
+    }
+}
+
+/// A value for each of the moderation categories. Categories missing from the response keep the default value
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+#[serde(default)]
+pub struct ModerationCategories<T> {
+    /// Content expressing, inciting or promoting harassing language towards any target
+    pub harassment: T,
+    /// Harassment content that also includes violence or serious harm towards any target
+    #[serde(rename = "harassment/threatening")]
+    pub harassment_threatening: T,
+    /// Content expressing, inciting or promoting hate based on a protected attribute
+    pub hate: T,
+    /// Hateful content that also includes violence or serious harm towards the targeted group
+    #[serde(rename = "hate/threatening")]
+    pub hate_threatening: T,
+    /// Content giving advice or instruction on how to commit illicit acts
+    pub illicit: T,
+    /// Illicit content that also includes references to violence or procuring a weapon
+    #[serde(rename = "illicit/violent")]
+    pub illicit_violent: T,
+    /// Content promoting, encouraging or depicting acts of self-harm
+    #[serde(rename = "self-harm")]
+    pub self_harm: T,
+    /// Content where the speaker expresses that they are engaging or intend to engage in acts of self-harm
+    #[serde(rename = "self-harm/intent")]
+    pub self_harm_intent: T,
+    /// Content encouraging acts of self-harm, or giving instructions on how to commit them
+    #[serde(rename = "self-harm/instructions")]
+    pub self_harm_instructions: T,
+    /// Content meant to arouse sexual excitement, or promoting sexual services
+    pub sexual: T,
+    /// Sexual content that includes an individual who is under 18 years old
+    #[serde(rename = "sexual/minors")]
+    pub sexual_minors: T,
+    /// Content depicting death, violence or physical injury
+    pub violence: T,
+    /// Content depicting death, violence or physical injury in graphic detail
+    #[serde(rename = "violence/graphic")]
+    pub violence_graphic: T,
+}
+
+impl ChatGPT {
+    /// Classifies the input with the moderations endpoint, e.g. to pre-screen user input before sending it to the
+    /// model. Check the result with [`ModerationResponse::is_flagged()`].
+    ///
+    /// Requires the `moderations` crate feature
+    pub async fn moderate<S: Into<String>>(&self, input: S) -> crate::Result<ModerationResponse> {
+        self.moderate_many(&[input.into()], None).await
+    }
+
+    /// Classifies multiple inputs at once with the moderations endpoint, using the provided moderation model,
+    /// or the default model of the endpoint if `None`.
+    ///
+    /// Requires the `moderations` crate feature
+    pub async fn moderate_many(
+        &self,
+        inputs: &[String],
+        model: Option<&str>,
+    ) -> crate::Result<ModerationResponse> {
+        let request = ModerationRequest {
+            input: inputs,
+            model,
+        };
+        self.send_endpoint_request("moderations", &request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModerationResponse;
+    use crate::client::ChatGPT;
+    use crate::transport::{request_json, JsonTransport};
+
+    fn moderation_body(flagged: bool) -> serde_json::Value {
+        serde_json::json!({
+            "id": "modr-1",
+            "model": "omni-moderation-latest",
+            "results": [{
+                "flagged": flagged,
+                "categories": { "violence": flagged, "self-harm/intent": false },
+                "category_scores": { "violence": 0.9, "self-harm/intent": 0.01 }
+            }]
+        })
+    }
+
+    #[test]
+    fn test_moderation_response() -> crate::Result<()> {
+        let response: ModerationResponse = serde_json::from_value(moderation_body(true))?;
+        assert!(response.is_flagged());
+        let result = &response.results[0];
+        assert!(result.categories.violence);
+        assert_eq!(result.category_scores.self_harm_intent, 0.01);
+        assert_eq!(result.category_scores.hate, 0.0);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_moderate() -> crate::Result<()> {
+        let transport = JsonTransport(|request: &reqwest::Request| {
+            assert_eq!(
+                request.url().as_str(),
+                "https://api.openai.com/v1/moderations"
+            );
+            moderation_body(request_json(request)["input"][0] == "I will hurt you")
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        assert!(client.moderate("I will hurt you").await?.is_flagged());
+        assert!(!client.moderate("Hello").await?.is_flagged());
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use reqwest::Method;
+use serde::Deserialize;
+
+use crate::client::{parse_endpoint_response, ChatGPT};
+
+/// The width of the time buckets of usage and costs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub enum BucketWidth {
+    /// Buckets of one minute
+    Minute,
+    /// Buckets of one hour
+    Hour,
+    /// Buckets of one day, the only width supported by the costs endpoint
+    #[default]
+    Day,
+}
+
+impl BucketWidth {
+    /// The value of the width in queries
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Minute => "1m",
+            Self::Hour => "1h",
+            Self::Day => "1d",
+        }
+    }
+}
+
+/// A field the results of buckets are grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum UsageGrouping {
+    /// Group by the project of the requests
+    ProjectId,
+    /// Group by the user of the requests. Only supported by usage
+    UserId,
+    /// Group by the API key of the requests. Only supported by usage
+    ApiKeyId,
+    /// Group by the model of the requests. Only supported by usage
+    Model,
+    /// Group by whether the requests were batched. Only supported by usage
+    Batch,
+    /// Group by the line item, e.g. `gpt-4o, input`. Only supported by costs
+    LineItem,
+}
+
+impl UsageGrouping {
+    /// The value of the grouping in queries
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::ProjectId => "project_id",
+            Self::UserId => "user_id",
+            Self::ApiKeyId => "api_key_id",
+            Self::Model => "model",
+            Self::Batch => "batch",
+            Self::LineItem => "line_item",
+        }
+    }
+}
+
+/// A query of the usage or costs of the organization
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UsageQuery {
+    /// Unix timestamp of the start of the queried range, inclusive
+    pub start_time: u64,
+    /// Unix timestamp of the end of the queried range, exclusive. Defaults to now
+    pub end_time: Option<u64>,
+    /// The width of the returned time buckets
+    pub bucket_width: BucketWidth,
+    /// Fields the results of each bucket are grouped by
+    pub group_by: Vec<UsageGrouping>,
+    /// Only include the usage of these projects. All projects are included when empty
+    pub project_ids: Vec<String>,
+}
+
+impl UsageQuery {
+    /// Constructs a query of daily buckets, starting at the provided Unix timestamp
+    pub fn new(start_time: u64) -> Self {
+        Self {
+            start_time,
+            end_time: None,
+            bucket_width: BucketWidth::default(),
+            group_by: Vec::new(),
+            project_ids: Vec::new(),
+        }
+    }
+
+    /// Ends the queried range at the provided Unix timestamp
+    pub fn with_end_time(mut self, end_time: u64) -> Self {
+        self.end_time = Some(end_time);
+        self
+    }
+
+    /// Sets the width of the returned time buckets
+    pub fn with_bucket_width(mut self, bucket_width: BucketWidth) -> Self {
+        self.bucket_width = bucket_width;
+        self
+    }
+
+    /// Groups the results of each bucket by the provided field
+    pub fn with_group_by(mut self, grouping: UsageGrouping) -> Self {
+        self.group_by.push(grouping);
+        self
+    }
+
+    /// Only includes the usage of the provided project
+    pub fn with_project<S: Into<String>>(mut self, project_id: S) -> Self {
+        self.project_ids.push(project_id.into());
+        self
+    }
+
+    fn apply(&self, url: &mut url::Url, page: Option<&str>) {
+        let mut query = url.query_pairs_mut();
+        query
+            .append_pair("start_time", &self.start_time.to_string())
+            .append_pair("bucket_width", self.bucket_width.as_str());
+        if let Some(end_time) = self.end_time {
+            query.append_pair("end_time", &end_time.to_string());
+        }
+        for grouping in &self.group_by {
+            query.append_pair("group_by", grouping.as_str());
+        }
+        for project_id in &self.project_ids {
+            query.append_pair("project_ids", project_id);
+        }
+        if let Some(page) = page {
+            query.append_pair("page", page);
+        }
+    }
+}
+
+/// The results of a time bucket
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct UsageBucket<T> {
+    /// Unix timestamp of the start of the bucket
+    pub start_time: u64,
+    /// Unix timestamp of the end of the bucket
+    pub end_time: u64,
+    /// The results, one for each group if the query is grouped
+    #[serde(default = "Vec::new")]
+    pub results: Vec<T>,
+}
+
+/// Token usage of chat completions and responses
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Deserialize)]
+pub struct CompletionsUsage {
+    /// The amount of input tokens, including cached tokens
+    #[serde(default)]
+    pub input_tokens: u64,
+    /// The amount of input tokens read from the cache
+    #[serde(default)]
+    pub input_cached_tokens: u64,
+    /// The amount of output tokens
+    #[serde(default)]
+    pub output_tokens: u64,
+    /// The amount of input audio tokens
+    #[serde(default)]
+    pub input_audio_tokens: u64,
+    /// The amount of output audio tokens
+    #[serde(default)]
+    pub output_audio_tokens: u64,
+    /// The amount of requests
+    #[serde(default)]
+    pub num_model_requests: u64,
+    /// The project of the requests, when grouped by project
+    #[serde(default)]
+    pub project_id: Option<String>,
+    /// The user of the requests, when grouped by user
+    #[serde(default)]
+    pub user_id: Option<String>,
+    /// The API key of the requests, when grouped by API key
+    #[serde(default)]
+    pub api_key_id: Option<String>,
+    /// The model of the requests, when grouped by model
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Whether the requests were batched, when grouped by batch
+    #[serde(default)]
+    pub batch: Option<bool>,
+}
+
+/// Costs of the organization
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Costs {
+    /// The amount spent
+    pub amount: CostAmount,
+    /// The line item of the costs, when grouped by line item
+    #[serde(default)]
+    pub line_item: Option<String>,
+    /// The project of the costs, when grouped by project
+    #[serde(default)]
+    pub project_id: Option<String>,
+}
+
+/// An amount of money
+#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+pub struct CostAmount {
+    /// The value of the amount
+    pub value: f64,
+    /// Lowercase ISO-4217 currency code, e.g. `usd`
+    pub currency: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    data: Vec<UsageBucket<T>>,
+    #[serde(default)]
+    next_page: Option<String>,
+}
+
+impl ChatGPT {
+    /// Fetches the token usage of chat completions of the organization, following all pages of the results.
+    ///
+    /// The client must be created with an admin API key.
+    ///
+    /// Requires the `organization` crate feature
+    pub async fn completions_usage(
+        &self,
+        query: &UsageQuery,
+    ) -> crate::Result<Vec<UsageBucket<CompletionsUsage>>> {
+        self.fetch_buckets("organization/usage/completions", query)
+            .await
+    }
+
+    /// Fetches the costs of the organization, following all pages of the results.
+    ///
+    /// The client must be created with an admin API key.
+    ///
+    /// Requires the `organization` crate feature
+    pub async fn costs(&self, query: &UsageQuery) -> crate::Result<Vec<UsageBucket<Costs>>> {
+        self.fetch_buckets("organization/costs", query).await
+    }
+
+    /// Fetches the buckets of all pages of the endpoint
+    async fn fetch_buckets<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &UsageQuery,
+    ) -> crate::Result<Vec<UsageBucket<T>>> {
+        let mut buckets = Vec::new();
+        let mut page = None;
+        loop {
+            let mut url = self.resource_url(path, &[])?;
+            query.apply(&mut url, page.as_deref());
+            let response = self.fetch_endpoint(Method::GET, url).await?;
+            let mut result: Page<T> = parse_endpoint_response(response).await?;
+            buckets.append(&mut result.data);
+            match result.next_page {
+                Some(next) => page = Some(next),
+                None => return Ok(buckets),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{UsageGrouping, UsageQuery};
+    use crate::client::ChatGPT;
+    use crate::transport::JsonTransport;
+
+    #[tokio::test]
+    async fn test_completions_usage() -> crate::Result<()> {
+        let transport = JsonTransport(|request: &reqwest::Request| {
+            assert_eq!(request.method(), reqwest::Method::GET);
+            let url = request.url();
+            assert_eq!(url.path(), "/v1/organization/usage/completions");
+            let query = url.query().unwrap();
+            assert!(query.starts_with("start_time=1730419200&bucket_width=1d&group_by=project_id"));
+            if query.ends_with("page=page_2") {
+                serde_json::json!({ "object": "page", "data": [
+                    { "object": "bucket", "start_time": 1730505600, "end_time": 1730592000, "results": [] }
+                ], "has_more": false, "next_page": null })
+            } else {
+                serde_json::json!({ "object": "page", "data": [
+                    { "object": "bucket", "start_time": 1730419200, "end_time": 1730505600, "results": [{
+                        "object": "organization.usage.completions.result",
+                        "input_tokens": 1000,
+                        "output_tokens": 500,
+                        "input_cached_tokens": 800,
+                        "num_model_requests": 5,
+                        "project_id": "proj_abc"
+                    }] }
+                ], "has_more": true, "next_page": "page_2" })
+            }
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let query = UsageQuery::new(1730419200).with_group_by(UsageGrouping::ProjectId);
+        let buckets = client.completions_usage(&query).await?;
+        assert_eq!(buckets.len(), 2);
+        let usage = &buckets[0].results[0];
+        assert_eq!(usage.input_tokens, 1000);
+        assert_eq!(usage.project_id.as_deref(), Some("proj_abc"));
+        assert!(buckets[1].results.is_empty());
+        Ok(())
+    }
+}
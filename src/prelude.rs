@@ -1,10 +1,28 @@
+pub use crate::auth::{AuthProvider, CredentialRefresh};
 pub use crate::client::ChatGPT;
-pub use crate::config::{ChatGPTEngine, ModelConfiguration, ModelConfigurationBuilder};
-pub use crate::converse::Conversation;
+#[cfg(feature = "structured")]
+pub use crate::config::JsonSchemaFormat;
+pub use crate::config::{
+    ChatGPTEngine, ModelConfiguration, ModelConfigurationBuilder, ResponseFormat,
+};
+#[cfg(feature = "tokenizer")]
+pub use crate::converse::CompactionConfig;
+pub use crate::converse::{Conversation, ConversationState, TruncationStrategy};
 #[cfg(feature = "functions")]
-pub use crate::functions::{gpt_function, FunctionValidationStrategy};
+pub use crate::functions::{
+    gpt_function, FunctionValidationStrategy, ToolChoice, ToolDescriptor, ToolRegistry,
+};
+pub use crate::intercept::Interceptor;
+#[cfg(feature = "structured")]
+pub use crate::structured::StructuredResponse;
+pub use crate::transport::HttpTransport;
+pub use crate::types::{
+    ChatMessage, CompletionRequest, CompletionRequestBuilder, MessageChoice, TokenUsage,
+};
 #[cfg(feature = "streams")]
-pub use crate::types::ResponseChunk;
-pub use crate::types::{ChatMessage, MessageChoice, TokenUsage};
+pub use crate::types::{
+    ChoiceStream, CoalesceBoundary, CoalescedStream, ResponseAccumulator, ResponseChunk,
+};
 pub use crate::Result;
 pub use url::Url;
+pub use uuid::Uuid;
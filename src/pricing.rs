@@ -0,0 +1,205 @@
+use crate::types::{CompletionResponse, TokenUsage};
+
+/// Prices of a model, in US dollars per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct ModelPricing {
+    /// Price of prompt tokens
+    pub input: f64,
+    /// Price of prompt tokens served from the prompt cache
+    pub cached_input: f64,
+    /// Price of completion tokens, including reasoning tokens
+    pub output: f64,
+}
+
+impl ModelPricing {
+    /// Constructs prices from the prices of prompt, cached prompt and completion tokens, per million tokens
+    pub const fn new(input: f64, cached_input: f64, output: f64) -> Self {
+        Self {
+            input,
+            cached_input,
+            output,
+        }
+    }
+
+    /// Looks up the prices of the model in the built-in pricing table, matching the longest known prefix of the
+    /// model identifier, so dated snapshots (e.g. `gpt-4o-2024-08-06`) resolve to their base model. Fine-tuned
+    /// models (e.g. `ft:gpt-4o-mini:org::id`) are billed at the fine-tuned prices of their base model.
+    /// Returns `None` for unknown models.
+    ///
+    /// The table reflects the published prices of standard requests at the time of the release of the crate
+    pub fn of(model: &str) -> Option<Self> {
+        let table = if model.starts_with("ft:") {
+            FINE_TUNED_PRICES
+        } else {
+            PRICES
+        };
+        crate::config::lookup_model(table, model)
+    }
+
+    /// Calculates the cost of the token usage in US dollars, billing cached prompt tokens at the discounted price
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        let cached = usage
+            .prompt_tokens_details
+            .map_or(0, |details| details.cached_tokens)
+            .min(usage.prompt_tokens);
+        self.tokens_cost(
+            usage.prompt_tokens - cached,
+            cached,
+            usage.completion_tokens,
+        )
+    }
+
+    fn tokens_cost(&self, input: u32, cached_input: u32, output: u32) -> f64 {
+        (input as f64 * self.input
+            + cached_input as f64 * self.cached_input
+            + output as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// Known prices by the prefix of the model identifier
+const PRICES: &[(&str, ModelPricing)] = &[
+    ("gpt-3.5-turbo", ModelPricing::new(0.5, 0.5, 1.5)),
+    ("gpt-4", ModelPricing::new(30.0, 30.0, 60.0)),
+    ("gpt-4-32k", ModelPricing::new(60.0, 60.0, 120.0)),
+    ("gpt-4-turbo", ModelPricing::new(10.0, 10.0, 30.0)),
+    ("gpt-4-1106-preview", ModelPricing::new(10.0, 10.0, 30.0)),
+    ("gpt-4-0125-preview", ModelPricing::new(10.0, 10.0, 30.0)),
+    ("gpt-4-vision-preview", ModelPricing::new(10.0, 10.0, 30.0)),
+    ("gpt-4o", ModelPricing::new(2.5, 1.25, 10.0)),
+    ("gpt-4o-mini", ModelPricing::new(0.15, 0.075, 0.6)),
+    ("gpt-4.1", ModelPricing::new(2.0, 0.5, 8.0)),
+    ("gpt-4.1-mini", ModelPricing::new(0.4, 0.1, 1.6)),
+    ("gpt-4.1-nano", ModelPricing::new(0.1, 0.025, 0.4)),
+    ("gpt-4.5-preview", ModelPricing::new(75.0, 37.5, 150.0)),
+    ("o1", ModelPricing::new(15.0, 7.5, 60.0)),
+    ("o1-mini", ModelPricing::new(1.1, 0.55, 4.4)),
+    ("o3", ModelPricing::new(2.0, 0.5, 8.0)),
+    ("o3-mini", ModelPricing::new(1.1, 0.55, 4.4)),
+    ("o4-mini", ModelPricing::new(1.1, 0.275, 4.4)),
+];
+
+/// Known prices of fine-tuned models by the prefix of their base model identifier
+const FINE_TUNED_PRICES: &[(&str, ModelPricing)] = &[
+    ("gpt-3.5-turbo", ModelPricing::new(3.0, 3.0, 6.0)),
+    ("gpt-4o", ModelPricing::new(3.75, 1.875, 15.0)),
+    ("gpt-4o-mini", ModelPricing::new(0.3, 0.15, 1.2)),
+    ("gpt-4.1", ModelPricing::new(3.0, 0.75, 12.0)),
+    ("gpt-4.1-mini", ModelPricing::new(0.8, 0.2, 3.2)),
+    ("gpt-4.1-nano", ModelPricing::new(0.2, 0.05, 0.8)),
+    ("o4-mini", ModelPricing::new(4.0, 1.0, 16.0)),
+];
+
+impl CompletionResponse {
+    /// Estimates the cost of this completion in US dollars from its token usage and the built-in pricing table.
+    /// Returns `None` if the model of the response is missing from the table.
+    ///
+    /// Requires the `pricing` crate feature
+    pub fn estimated_cost(&self) -> Option<f64> {
+        ModelPricing::of(&self.model).map(|pricing| pricing.cost(&self.usage))
+    }
+}
+
+#[cfg(feature = "tokenizer")]
+impl crate::client::ChatGPT {
+    /// Estimates the cost of sending the history with the configuration of the client in US dollars, before sending
+    /// it. The prompt tokens are counted with the tokenizer, and the token limit of every reply, if configured, is
+    /// billed as completion tokens, so the estimate is an upper bound. The limit is read from `max_completion_tokens`
+    /// for reasoning models, and from `max_tokens` otherwise, as it is sent. Returns `None` if the engine is missing
+    /// from the pricing table.
+    ///
+    /// Requires the `pricing` and `tokenizer` crate features
+    pub fn estimate_cost(
+        &self,
+        history: &[crate::types::ChatMessage],
+    ) -> crate::Result<Option<f64>> {
+        let request = self.completion_request(history);
+        let Some(pricing) = ModelPricing::of(&request.model) else {
+            return Ok(None);
+        };
+        let prompt_tokens = self.count_tokens(history)? as u32;
+        let completion_tokens = request
+            .max_completion_tokens
+            .or(request.max_tokens)
+            .unwrap_or_default()
+            * request.reply_count;
+        Ok(Some(pricing.tokens_cost(
+            prompt_tokens,
+            0,
+            completion_tokens,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ModelPricing;
+    use crate::types::{PromptTokensDetails, TokenUsage};
+
+    #[test]
+    fn test_cost() {
+        let pricing = ModelPricing::of("gpt-4o-mini-2024-07-18").unwrap();
+        assert_eq!(pricing, ModelPricing::new(0.15, 0.075, 0.6));
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 1_000_000,
+            total_tokens: 2_000_000,
+            prompt_tokens_details: Some(PromptTokensDetails {
+                cached_tokens: 500_000,
+                ..Default::default()
+            }),
+            completion_tokens_details: None,
+        };
+        assert!((pricing.cost(&usage) - 0.7125).abs() < 1e-9);
+        assert_eq!(
+            ModelPricing::of("ft:gpt-4o-mini-2024-07-18:org::1"),
+            Some(ModelPricing::new(0.3, 0.15, 1.2))
+        );
+        assert_eq!(
+            ModelPricing::of("gpt-4-1106-preview"),
+            Some(ModelPricing::new(10.0, 10.0, 30.0))
+        );
+        assert_eq!(
+            ModelPricing::of("gpt-4-0613"),
+            Some(ModelPricing::new(30.0, 30.0, 60.0))
+        );
+        assert_eq!(
+            ModelPricing::of("gpt-4.5-preview-2025-02-27"),
+            Some(ModelPricing::new(75.0, 37.5, 150.0))
+        );
+        assert!(ModelPricing::of("ft:davinci-002:org::1").is_none());
+    }
+
+    #[cfg(feature = "tokenizer")]
+    #[test]
+    fn test_estimate_cost() -> crate::Result<()> {
+        use crate::config::{ChatGPTEngine, ModelConfiguration};
+        use crate::types::{ChatMessage, Role};
+
+        let config = ModelConfiguration {
+            engine: ChatGPTEngine::Gpt4,
+            max_tokens: Some(1_000),
+            ..Default::default()
+        };
+        let client = crate::client::ChatGPT::new_with_config("", config)?;
+        // 8 prompt tokens and 1000 completion tokens
+        let cost = client.estimate_cost(&[ChatMessage::new(Role::User, "hello")])?;
+        assert!((cost.unwrap() - (8.0 * 30.0 + 1_000.0 * 60.0) / 1_000_000.0).abs() < 1e-9);
+
+        // the limit of reasoning models is sent as `max_completion_tokens`
+        let config = ModelConfiguration {
+            engine: ChatGPTEngine::Custom("o3-mini"),
+            max_tokens: Some(1_000),
+            ..Default::default()
+        };
+        let client = crate::client::ChatGPT::new_with_config("", config)?;
+        let request = client.completion_request(&[]);
+        assert_eq!(
+            (request.max_tokens, request.max_completion_tokens),
+            (None, Some(1_000))
+        );
+        let cost = client.estimate_cost(&[ChatMessage::new(Role::User, "hello")])?;
+        assert!((cost.unwrap() - (8.0 * 1.1 + 1_000.0 * 4.4) / 1_000_000.0).abs() < 1e-9);
+        Ok(())
+    }
+}
@@ -0,0 +1,722 @@
+#[cfg(feature = "streams")]
+use futures::Stream;
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{parse_endpoint_response, ChatGPT};
+#[cfg(feature = "functions")]
+use crate::functions::ToolRegistry;
+use crate::types::Role;
+
+/// A request to the responses endpoint
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResponseRequest {
+    /// The model generating the response
+    pub model: String,
+    /// The input items, e.g. messages and tool outputs
+    pub input: Vec<InputItem>,
+    /// System instructions of the response. Instructions of previous responses are not carried over
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instructions: Option<String>,
+    /// Tools the model can use, including the built-in tools of the API
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<ResponseTool>,
+    /// Id of the previous response, continuing the conversation stored by the API
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_response_id: Option<String>,
+    /// Sampling temperature, the endpoint default if `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    /// Maximum amount of output tokens, including reasoning tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    /// Whether the response is stored by the API, so it can be continued later. The endpoint default if `None`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub store: Option<bool>,
+}
+
+impl ResponseRequest {
+    /// Constructs a request to the provided model without any input
+    pub fn new<S: Into<String>>(model: S) -> Self {
+        Self {
+            model: model.into(),
+            input: Vec::new(),
+            instructions: None,
+            tools: Vec::new(),
+            previous_response_id: None,
+            temperature: None,
+            max_output_tokens: None,
+            store: None,
+        }
+    }
+
+    /// Adds an input item
+    pub fn with_input(mut self, item: InputItem) -> Self {
+        self.input.push(item);
+        self
+    }
+
+    /// Adds a user message to the input
+    pub fn with_user_message<S: Into<String>>(self, message: S) -> Self {
+        self.with_input(InputItem::message(Role::User, message))
+    }
+
+    /// Sets the system instructions
+    pub fn with_instructions<S: Into<String>>(mut self, instructions: S) -> Self {
+        self.instructions = Some(instructions.into());
+        self
+    }
+
+    /// Adds a tool the model can use
+    pub fn with_tool(mut self, tool: ResponseTool) -> Self {
+        self.tools.push(tool);
+        self
+    }
+
+    /// Continues the conversation of the previous response
+    pub fn with_previous_response<S: Into<String>>(mut self, response_id: S) -> Self {
+        self.previous_response_id = Some(response_id.into());
+        self
+    }
+
+    /// Sets the sampling temperature
+    pub fn with_temperature(mut self, temperature: f32) -> Self {
+        self.temperature = Some(temperature);
+        self
+    }
+
+    /// Sets the maximum amount of output tokens
+    pub fn with_max_output_tokens(mut self, max_output_tokens: u32) -> Self {
+        self.max_output_tokens = Some(max_output_tokens);
+        self
+    }
+
+    /// Sets whether the response is stored by the API
+    pub fn with_store(mut self, store: bool) -> Self {
+        self.store = Some(store);
+        self
+    }
+}
+
+/// An input item of a response request
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputItem {
+    /// A message
+    Message {
+        /// The author of the message
+        role: Role,
+        /// Parts of the message
+        content: Vec<InputContent>,
+    },
+    /// A function call of a previous response, when the conversation is not stored by the API
+    FunctionCall {
+        /// Identifier of the call
+        call_id: String,
+        /// Name of the called function
+        name: String,
+        /// Arguments of the call, as a JSON string
+        arguments: String,
+    },
+    /// The output of a function call
+    FunctionCallOutput {
+        /// Identifier of the answered call
+        call_id: String,
+        /// The output of the function
+        output: String,
+    },
+}
+
+impl InputItem {
+    /// A text message
+    pub fn message<S: Into<String>>(role: Role, text: S) -> Self {
+        Self::Message {
+            role,
+            content: vec![InputContent::InputText { text: text.into() }],
+        }
+    }
+
+    /// The output of the function call with the provided id
+    pub fn function_call_output<I: Into<String>, S: Into<String>>(call_id: I, output: S) -> Self {
+        Self::FunctionCallOutput {
+            call_id: call_id.into(),
+            output: output.into(),
+        }
+    }
+}
+
+/// A part of an input message
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputContent {
+    /// Text
+    InputText {
+        /// The text
+        text: String,
+    },
+    /// An image, referenced by a URL or a base64 data URL
+    InputImage {
+        /// URL of the image
+        image_url: String,
+    },
+    /// A file uploaded with the files API, e.g. a PDF
+    InputFile {
+        /// Id of the uploaded file
+        file_id: String,
+    },
+}
+
+/// A tool the model can use while generating a response
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseTool {
+    /// A function called by the model, that is executed by the application
+    Function {
+        /// Name of the function
+        name: String,
+        /// Description of the function
+        #[serde(skip_serializing_if = "Option::is_none")]
+        description: Option<String>,
+        /// JSON schema of the arguments
+        parameters: serde_json::Value,
+    },
+    /// Built-in web search
+    WebSearchPreview,
+    /// Built-in search in vector stores
+    FileSearch {
+        /// Ids of the searched vector stores
+        vector_store_ids: Vec<String>,
+        /// Maximum amount of results, the endpoint default if `None`
+        #[serde(skip_serializing_if = "Option::is_none")]
+        max_num_results: Option<u32>,
+    },
+    /// Built-in execution of Python code in a sandbox
+    CodeInterpreter {
+        /// The sandbox of the code, e.g. `{"type": "auto"}`
+        container: serde_json::Value,
+    },
+}
+
+impl ResponseTool {
+    /// Built-in search in the provided vector stores
+    pub fn file_search<I: IntoIterator<Item = S>, S: Into<String>>(vector_store_ids: I) -> Self {
+        Self::FileSearch {
+            vector_store_ids: vector_store_ids.into_iter().map(Into::into).collect(),
+            max_num_results: None,
+        }
+    }
+
+    /// Built-in code execution in a sandbox created automatically
+    pub fn code_interpreter() -> Self {
+        Self::CodeInterpreter {
+            container: serde_json::json!({ "type": "auto" }),
+        }
+    }
+
+    /// Function tools of all the functions of the registry
+    #[cfg(feature = "functions")]
+    pub fn functions(registry: &ToolRegistry) -> Vec<Self> {
+        registry
+            .descriptors()
+            .iter()
+            .map(|descriptor| {
+                let function = &descriptor["function"];
+                Self::Function {
+                    name: function["name"].as_str().unwrap_or_default().to_owned(),
+                    description: function["description"].as_str().map(str::to_owned),
+                    parameters: function["parameters"].clone(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A response generated by the responses endpoint
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ModelResponse {
+    /// Identifier of the response, that can be continued with [`ResponseRequest::previous_response_id`]
+    pub id: String,
+    /// Unix timestamp of the creation of the response
+    #[serde(default)]
+    pub created_at: u64,
+    /// The model that generated the response
+    #[serde(default)]
+    pub model: String,
+    /// Status of the response
+    pub status: ResponseStatus,
+    /// The output items, e.g. messages and tool calls
+    #[serde(default)]
+    pub output: Vec<OutputItem>,
+    /// Tokens used by the response, once it is finished
+    #[serde(default)]
+    pub usage: Option<ResponseUsage>,
+    /// The reason of the failure, if the response failed
+    #[serde(default)]
+    pub error: Option<ResponseError>,
+}
+
+impl ModelResponse {
+    /// Concatenated text of the output messages
+    pub fn output_text(&self) -> String {
+        self.output
+            .iter()
+            .filter_map(|item| match item {
+                OutputItem::Message { content, .. } => Some(content),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|content| match content {
+                OutputContent::OutputText { text } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Function calls the application has to answer with [`InputItem::function_call_output()`]
+    pub fn function_calls(&self) -> impl Iterator<Item = &OutputItem> {
+        self.output
+            .iter()
+            .filter(|item| matches!(item, OutputItem::FunctionCall { .. }))
+    }
+}
+
+/// The status of a response
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResponseStatus {
+    /// The response is queued for a background generation
+    Queued,
+    /// The response is being generated
+    InProgress,
+    /// The response is complete
+    Completed,
+    /// The generation was cut off, e.g. by the token limit
+    Incomplete,
+    /// The generation failed, see [`ModelResponse::error`]
+    Failed,
+    /// The background generation was cancelled
+    Cancelled,
+}
+
+/// An output item of a response
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputItem {
+    /// A message
+    Message {
+        /// Identifier of the item
+        id: String,
+        /// Parts of the message
+        #[serde(default)]
+        content: Vec<OutputContent>,
+    },
+    /// A function call, that has to be answered by the application
+    FunctionCall {
+        /// Identifier of the call, referenced by its output
+        call_id: String,
+        /// Name of the called function
+        name: String,
+        /// Arguments of the call, as a JSON string
+        arguments: String,
+    },
+    /// A call of the built-in web search
+    WebSearchCall {
+        /// Identifier of the item
+        id: String,
+    },
+    /// A call of the built-in file search
+    FileSearchCall {
+        /// Identifier of the item
+        id: String,
+    },
+    /// Reasoning of the model
+    Reasoning {
+        /// Identifier of the item
+        id: String,
+    },
+    /// An item not supported by the crate yet
+    #[serde(other)]
+    Other,
+}
+
+/// A part of an output message
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputContent {
+    /// Text
+    OutputText {
+        /// The text
+        text: String,
+    },
+    /// A refusal to answer
+    Refusal {
+        /// The explanation of the refusal
+        refusal: String,
+    },
+    /// A part not supported by the crate yet
+    #[serde(other)]
+    Other,
+}
+
+/// Tokens used by a response
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+#[serde(default)]
+pub struct ResponseUsage {
+    /// Tokens of the input
+    pub input_tokens: u32,
+    /// Tokens of the output, including reasoning tokens
+    pub output_tokens: u32,
+    /// Total amount of tokens used
+    pub total_tokens: u32,
+}
+
+impl From<&ResponseUsage> for crate::types::TokenUsage {
+    fn from(usage: &ResponseUsage) -> Self {
+        Self {
+            prompt_tokens: usage.input_tokens,
+            completion_tokens: usage.output_tokens,
+            total_tokens: usage.total_tokens,
+            ..Default::default()
+        }
+    }
+}
+
+/// The reason a response failed
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct ResponseError {
+    /// Machine-readable code of the error
+    #[serde(default)]
+    pub code: String,
+    /// Description of the error
+    #[serde(default)]
+    pub message: String,
+}
+
+/// An event of a streamed response, identified by its name, e.g. `response.output_text.delta`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type")]
+pub enum ResponseEvent {
+    /// The response was created
+    #[serde(rename = "response.created")]
+    Created {
+        /// The response, without any output yet
+        response: ModelResponse,
+    },
+    /// An output item was added
+    #[serde(rename = "response.output_item.added")]
+    OutputItemAdded {
+        /// Index of the item in the output
+        output_index: usize,
+        /// The item, that is not complete yet
+        item: OutputItem,
+    },
+    /// A part of the text of an output message
+    #[serde(rename = "response.output_text.delta")]
+    OutputTextDelta {
+        /// Index of the message in the output
+        output_index: usize,
+        /// The text
+        delta: String,
+    },
+    /// A part of the arguments of a function call
+    #[serde(rename = "response.function_call_arguments.delta")]
+    FunctionCallArgumentsDelta {
+        /// Index of the function call in the output
+        output_index: usize,
+        /// The part of the JSON string
+        delta: String,
+    },
+    /// An output item is complete
+    #[serde(rename = "response.output_item.done")]
+    OutputItemDone {
+        /// Index of the item in the output
+        output_index: usize,
+        /// The complete item
+        item: OutputItem,
+    },
+    /// The response is complete
+    #[serde(rename = "response.completed")]
+    Completed {
+        /// The complete response
+        response: ModelResponse,
+    },
+    /// The response was cut off, e.g. by the token limit
+    #[serde(rename = "response.incomplete")]
+    Incomplete {
+        /// The partial response
+        response: ModelResponse,
+    },
+    /// The generation failed
+    #[serde(rename = "response.failed")]
+    Failed {
+        /// The failed response, see [`ModelResponse::error`]
+        response: ModelResponse,
+    },
+    /// Any other event, e.g. `response.in_progress`. The events not used by the crate yet are skipped
+    #[serde(other)]
+    Other,
+}
+
+/// A response request with the stream flag
+#[cfg(feature = "streams")]
+#[derive(Serialize)]
+struct StreamingRequest<'a> {
+    #[serde(flatten)]
+    request: &'a ResponseRequest,
+    stream: bool,
+}
+
+/// The payload of an `error` event
+#[cfg(feature = "streams")]
+#[derive(Deserialize)]
+struct StreamError {
+    #[serde(default)]
+    code: Option<String>,
+    message: String,
+}
+
+impl ChatGPT {
+    /// Generates a response with the responses endpoint.
+    ///
+    /// Requires the `responses` crate feature
+    pub async fn create_response(&self, request: &ResponseRequest) -> crate::Result<ModelResponse> {
+        let response: ModelResponse = self.send_endpoint_request("responses", request).await?;
+        if let Some(usage) = &response.usage {
+            self.record_usage(&usage.into());
+        }
+        Ok(response)
+    }
+
+    /// Generates a response to the single user message with the engine of the configuration.
+    ///
+    /// Requires the `responses` crate feature
+    pub async fn respond<S: Into<String>>(&self, message: S) -> crate::Result<ModelResponse> {
+        let request =
+            ResponseRequest::new(self.config.engine.to_string()).with_user_message(message);
+        self.create_response(&request).await
+    }
+
+    /// Retrieves the stored response with the provided id.
+    ///
+    /// Requires the `responses` crate feature
+    pub async fn retrieve_response(&self, id: &str) -> crate::Result<ModelResponse> {
+        let url = self.resource_url("responses", &[id])?;
+        let response = self.fetch_endpoint(Method::GET, url).await?;
+        parse_endpoint_response(response).await
+    }
+
+    /// Generates a response with the responses endpoint, and returns its events as a stream.
+    ///
+    /// The stream ends after the [`ResponseEvent::Completed`], [`ResponseEvent::Incomplete`] or
+    /// [`ResponseEvent::Failed`] event. `error` events are returned as errors.
+    ///
+    /// Requires the `responses` and `streams` crate features
+    #[cfg(feature = "streams")]
+    pub async fn create_response_streaming(
+        &self,
+        request: &ResponseRequest,
+    ) -> crate::Result<impl Stream<Item = crate::Result<ResponseEvent>>> {
+        use eventsource_stream::{EventStreamError, Eventsource};
+        use futures_util::StreamExt;
+
+        let url = self.config.endpoint_url_for("responses")?;
+        let body = StreamingRequest {
+            request,
+            stream: true,
+        };
+        let response = self
+            .execute_json(url, &body, true, &Default::default())
+            .await?;
+        if !response.status().is_success() {
+            return Err(crate::client::endpoint_error(response).await);
+        }
+        let stream = response.bytes_stream().eventsource().filter_map(|event| {
+            futures::future::ready(match event {
+                Ok(event) if event.data.is_empty() => None,
+                Ok(event) if event.event == "error" => {
+                    Some(match serde_json::from_str::<StreamError>(&event.data) {
+                        Ok(error) => Err(crate::err::Error::BackendError {
+                            message: error.message,
+                            error_type: error.code.unwrap_or_default(),
+                        }),
+                        Err(err) => Err(err.into()),
+                    })
+                }
+                Ok(event) => match serde_json::from_str(&event.data) {
+                    Ok(ResponseEvent::Other) => None,
+                    parsed => Some(parsed.map_err(crate::err::Error::from)),
+                },
+                Err(EventStreamError::Transport(err)) => {
+                    Some(Err(crate::err::Error::ClientError(err)))
+                }
+                Err(err) => Some(Err(crate::err::Error::ParsingError(err.to_string()))),
+            })
+        });
+        let client = self.clone();
+        let stream = stream.inspect(move |event| {
+            if let Ok(
+                ResponseEvent::Completed { response }
+                | ResponseEvent::Incomplete { response }
+                | ResponseEvent::Failed { response },
+            ) = event
+            {
+                if let Some(usage) = &response.usage {
+                    client.record_usage(&usage.into());
+                }
+            }
+        });
+        Ok(crate::client::idle_timeout_stream(
+            Box::pin(stream),
+            self.config.stream_idle_timeout,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InputItem, OutputItem, ResponseRequest, ResponseStatus, ResponseTool};
+    use crate::client::ChatGPT;
+    use crate::transport::{json_response, request_json};
+
+    fn response(status: &str, text: &str) -> serde_json::Value {
+        serde_json::json!({
+            "id": "resp_1", "object": "response", "created_at": 1, "model": "gpt-4o",
+            "status": status,
+            "output": [
+                { "type": "reasoning", "id": "rs_1", "summary": [] },
+                { "type": "message", "id": "msg_1", "role": "assistant", "status": "completed", "content": [
+                    { "type": "output_text", "text": text, "annotations": [] }
+                ] },
+                {
+                    "type": "function_call", "id": "fc_1", "call_id": "call_1",
+                    "name": "weather", "arguments": "{\"city\":\"Paris\"}"
+                },
+                { "type": "image_generation_call", "id": "ig_1" }
+            ],
+            "usage": { "input_tokens": 5, "output_tokens": 3, "total_tokens": 8 }
+        })
+    }
+
+    /// Echoes the request, streamed if requested
+    struct ResponsesTransport;
+
+    #[async_trait::async_trait]
+    impl crate::transport::HttpTransport for ResponsesTransport {
+        async fn send(&self, request: reqwest::Request) -> crate::Result<reqwest::Response> {
+            assert_eq!(
+                request.url().as_str(),
+                "https://api.openai.com/v1/responses"
+            );
+            let body = request_json(&request);
+            assert_eq!(body["input"][0]["content"][0]["type"], "input_text");
+            assert_eq!(
+                body["tools"][0],
+                serde_json::json!({ "type": "web_search_preview" })
+            );
+            if body["stream"] != true {
+                return Ok(json_response(200, response("completed", "Hello")));
+            }
+            let events = [
+                (
+                    "response.created",
+                    serde_json::json!({ "response": response("in_progress", "") }),
+                ),
+                (
+                    "response.in_progress",
+                    serde_json::json!({ "response": response("in_progress", "") }),
+                ),
+                (
+                    "response.output_text.delta",
+                    serde_json::json!({
+                        "item_id": "msg_1", "output_index": 1, "content_index": 0, "delta": "Hel"
+                    }),
+                ),
+                (
+                    "response.output_text.delta",
+                    serde_json::json!({
+                        "item_id": "msg_1", "output_index": 1, "content_index": 0, "delta": "lo"
+                    }),
+                ),
+                (
+                    "response.completed",
+                    serde_json::json!({ "response": response("completed", "Hello") }),
+                ),
+            ];
+            let sse: String = events
+                .into_iter()
+                .map(|(name, mut data)| {
+                    data["type"] = name.into();
+                    format!("event: {name}\ndata: {data}\n\n")
+                })
+                .collect();
+            Ok(http::Response::builder()
+                .header("content-type", "text/event-stream")
+                .body(sse)
+                .unwrap()
+                .into())
+        }
+    }
+
+    fn request() -> ResponseRequest {
+        ResponseRequest::new("gpt-4o")
+            .with_user_message("Say hello")
+            .with_tool(ResponseTool::WebSearchPreview)
+    }
+
+    #[tokio::test]
+    async fn test_create_response() -> crate::Result<()> {
+        let client = ChatGPT::new_with_transport("", ResponsesTransport, Default::default())?;
+        let response = client.create_response(&request()).await?;
+        assert_eq!(response.status, ResponseStatus::Completed);
+        assert_eq!(response.output_text(), "Hello");
+        assert_eq!(response.output[3], OutputItem::Other);
+        let calls: Vec<_> = response.function_calls().collect();
+        assert!(
+            matches!(calls[..], [OutputItem::FunctionCall { call_id, .. }] if call_id == "call_1")
+        );
+        assert_eq!(response.usage.unwrap().total_tokens, 8);
+
+        let follow_up = ResponseRequest::new("gpt-4o")
+            .with_previous_response(&response.id)
+            .with_input(InputItem::function_call_output("call_1", "Sunny"));
+        assert_eq!(
+            serde_json::to_value(&follow_up)?,
+            serde_json::json!({
+                "model": "gpt-4o",
+                "previous_response_id": "resp_1",
+                "input": [{ "type": "function_call_output", "call_id": "call_1", "output": "Sunny" }]
+            })
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "streams")]
+    #[tokio::test]
+    async fn test_create_response_streaming() -> crate::Result<()> {
+        use super::ResponseEvent;
+        use futures::TryStreamExt;
+
+        let client = ChatGPT::new_with_transport("", ResponsesTransport, Default::default())?;
+        let events: Vec<_> = client
+            .create_response_streaming(&request())
+            .await?
+            .try_collect()
+            .await?;
+        assert_eq!(events.len(), 4);
+        assert!(
+            matches!(&events[0], ResponseEvent::Created { response } if response.status == ResponseStatus::InProgress)
+        );
+        let text: String = events
+            .iter()
+            .filter_map(|event| match event {
+                ResponseEvent::OutputTextDelta { delta, .. } => Some(delta.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "Hello");
+        assert!(
+            matches!(&events[3], ResponseEvent::Completed { response } if response.output_text() == "Hello")
+        );
+        Ok(())
+    }
+}
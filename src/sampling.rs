@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+
+use crate::client::ChatGPT;
+use crate::types::{ChatMessage, CompletionResponse, MessageChoice, Role};
+
+/// Multiple completion choices, ranked by a scorer
+#[derive(Debug, Clone, PartialEq)]
+pub struct RankedResponse {
+    /// The response containing all sampled choices
+    pub response: CompletionResponse,
+    /// Scores of each choice, in the same order as `response.message_choices`
+    pub scores: Vec<f64>,
+    /// Index of the highest scored choice
+    pub best_index: usize,
+}
+
+impl RankedResponse {
+    /// The highest scored message choice
+    pub fn best(&self) -> &MessageChoice {
+        &self.response.message_choices[self.best_index]
+    }
+
+    /// The highest scored message
+    pub fn message(&self) -> &ChatMessage {
+        &self.best().message
+    }
+
+    /// Ranks the choices of the response by their scores
+    pub(crate) fn from_scores(response: CompletionResponse, scores: Vec<f64>) -> Self {
+        let best_index = scores
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+            .unwrap_or_default();
+        Self {
+            response,
+            scores,
+            best_index,
+        }
+    }
+}
+
+/// The consensus answer among multiple sampled completions
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConsensusResponse<T> {
+    /// The response containing all sampled choices
+    pub response: CompletionResponse,
+    /// Answers extracted from each choice, in the same order as `response.message_choices`.
+    /// `None` if no answer could be extracted from the choice.
+    pub answers: Vec<Option<T>>,
+    /// Distinct answers with their vote counts, most voted first. Ties are ordered by first appearance.
+    pub votes: Vec<(T, usize)>,
+}
+
+impl<T> ConsensusResponse<T> {
+    /// The majority answer, if any answer could be extracted
+    pub fn answer(&self) -> Option<&T> {
+        self.votes.first().map(|(answer, _)| answer)
+    }
+
+    /// The share of sampled choices that voted for the majority answer, from 0 to 1
+    pub fn agreement(&self) -> f64 {
+        match self.votes.first() {
+            Some((_, count)) if !self.answers.is_empty() => {
+                *count as f64 / self.answers.len() as f64
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+impl ChatGPT {
+    /// Requests `count` completion choices for the history, overriding the temperature if provided.
+    ///
+    /// Backends that return fewer choices than requested are queried again until enough choices are collected.
+    /// Fails with [`Error::NoChoices`](crate::err::Error::NoChoices) if `count` is zero.
+    pub(crate) async fn sample_history(
+        &self,
+        history: &[ChatMessage],
+        count: u32,
+        temperature: Option<f32>,
+    ) -> crate::Result<CompletionResponse> {
+        if count == 0 {
+            return Err(crate::err::Error::NoChoices);
+        }
+        let mut request = self.completion_request(history);
+        if let Some(temperature) = temperature {
+            request.temperature = temperature;
+        }
+        request.reply_count = count;
+        let mut response = self.send_request(&request).await?;
+        while (response.message_choices.len() as u32) < count {
+            request.reply_count = count - response.message_choices.len() as u32;
+            let extra = self.send_request(&request).await?;
+            if extra.message_choices.is_empty() {
+                break;
+            }
+            response.usage += extra.usage;
+            for mut choice in extra.message_choices {
+                choice.index = response.message_choices.len() as u32;
+                response.message_choices.push(choice);
+            }
+        }
+        Ok(response)
+    }
+
+    /// Requests `count` completion choices for the history, scores each of them with the provided async scorer
+    /// and returns them ranked. The choice with the highest score is available via [`RankedResponse::best()`].
+    /// Fails with [`Error::NoChoices`](crate::err::Error::NoChoices) if no choices were sampled.
+    ///
+    /// See [`Self::judge_score()`] for a scorer backed by a judge model.
+    pub async fn send_history_best_of<F, Fut>(
+        &self,
+        history: &[ChatMessage],
+        count: u32,
+        mut scorer: F,
+    ) -> crate::Result<RankedResponse>
+    where
+        F: FnMut(&ChatMessage) -> Fut,
+        Fut: Future<Output = crate::Result<f64>>,
+    {
+        let response = self.sample_history(history, count, None).await?;
+        if response.message_choices.is_empty() {
+            return Err(crate::err::Error::NoChoices);
+        }
+        let mut scores = Vec::with_capacity(response.message_choices.len());
+        for choice in &response.message_choices {
+            scores.push(scorer(&choice.message).await?);
+        }
+        Ok(RankedResponse::from_scores(response, scores))
+    }
+
+    /// Requests `count` completion choices for a single message, without preserving message history,
+    /// and ranks them with the provided async scorer. See [`Self::send_history_best_of()`] for details.
+    pub async fn send_message_best_of<S, F, Fut>(
+        &self,
+        message: S,
+        count: u32,
+        scorer: F,
+    ) -> crate::Result<RankedResponse>
+    where
+        S: Into<String>,
+        F: FnMut(&ChatMessage) -> Fut,
+        Fut: Future<Output = crate::Result<f64>>,
+    {
+        let history = vec![ChatMessage::new(Role::User, message)];
+        self.send_history_best_of(&history, count, scorer).await
+    }
+
+    /// Samples `samples` completion choices for the history with the provided (usually higher) temperature,
+    /// extracts the final answer from each of them with the parser and returns the consensus answer with vote counts.
+    ///
+    /// The parser should return `None` for replies without a recognizable answer, such replies do not vote.
+    pub async fn send_history_self_consistent<T, F>(
+        &self,
+        history: &[ChatMessage],
+        samples: u32,
+        temperature: f32,
+        mut parser: F,
+    ) -> crate::Result<ConsensusResponse<T>>
+    where
+        T: Eq + Hash + Clone,
+        F: FnMut(&str) -> Option<T>,
+    {
+        let response = self
+            .sample_history(history, samples, Some(temperature))
+            .await?;
+        let answers = response
+            .message_choices
+            .iter()
+            .map(|choice| parser(&choice.message.content))
+            .collect::<Vec<_>>();
+        let votes = tally_votes(&answers);
+        Ok(ConsensusResponse {
+            response,
+            answers,
+            votes,
+        })
+    }
+
+    /// Samples multiple completion choices for a single message, without preserving message history,
+    /// and returns the consensus answer. See [`Self::send_history_self_consistent()`] for details.
+    pub async fn send_message_self_consistent<S, T, F>(
+        &self,
+        message: S,
+        samples: u32,
+        temperature: f32,
+        parser: F,
+    ) -> crate::Result<ConsensusResponse<T>>
+    where
+        S: Into<String>,
+        T: Eq + Hash + Clone,
+        F: FnMut(&str) -> Option<T>,
+    {
+        let history = vec![ChatMessage::new(Role::User, message)];
+        self.send_history_self_consistent(&history, samples, temperature, parser)
+            .await
+    }
+
+    /// Asks the model to grade the answer against the provided criteria, and returns the grade from 0 to 10.
+    ///
+    /// Can be used as a scorer for [`Self::send_history_best_of()`], possibly from a client configured with a different engine.
+    pub async fn judge_score<C: AsRef<str>, A: AsRef<str>>(
+        &self,
+        criteria: C,
+        answer: A,
+    ) -> crate::Result<f64> {
+        let history = vec![
+            ChatMessage::new(
+                Role::System,
+                format!(
+                    "You are a strict grader. Grade the answer provided by the user on the following criteria: {}\n\
+                    Reply with a single number from 0 to 10 and nothing else.",
+                    criteria.as_ref()
+                ),
+            ),
+            ChatMessage::new(Role::User, answer.as_ref()),
+        ];
+        let response = self.send_history(&history).await?;
+        parse_grade(&response.message().content).ok_or_else(|| {
+            crate::err::Error::ParsingError(format!(
+                "Judge model replied with an invalid grade: {}",
+                response.message().content
+            ))
+        })
+    }
+}
+
+fn tally_votes<T: Eq + Hash + Clone>(answers: &[Option<T>]) -> Vec<(T, usize)> {
+    let mut positions: HashMap<&T, usize> = HashMap::new();
+    let mut votes: Vec<(T, usize)> = Vec::new();
+    for answer in answers.iter().flatten() {
+        if let Some(&position) = positions.get(answer) {
+            votes[position].1 += 1;
+        } else {
+            positions.insert(answer, votes.len());
+            votes.push((answer.clone(), 1));
+        }
+    }
+    // stable sort keeps ties in the order of first appearance
+    votes.sort_by(|(_, a), (_, b)| b.cmp(a));
+    votes
+}
+
+fn parse_grade(reply: &str) -> Option<f64> {
+    reply
+        .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .find_map(|part| part.trim_end_matches('.').parse::<f64>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_grade, tally_votes};
+    use crate::client::ChatGPT;
+    use crate::transport::JsonTransport;
+
+    #[test]
+    fn test_parse_grade() {
+        assert_eq!(parse_grade("7"), Some(7.0));
+        assert_eq!(parse_grade("Grade: 8.5."), Some(8.5));
+        assert_eq!(parse_grade("no grade"), None);
+    }
+
+    #[test]
+    fn test_tally_votes() {
+        let votes = tally_votes(&[Some(3), Some(4), None, Some(4), Some(5), Some(3), Some(4)]);
+        assert_eq!(votes, vec![(4, 3), (3, 2), (5, 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_best_of_without_choices() -> crate::Result<()> {
+        let transport = JsonTransport(|_: &reqwest::Request| serde_json::json!({ "choices": [] }));
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let scorer = |_: &crate::types::ChatMessage| async { Ok(1.0) };
+        assert!(matches!(
+            client.send_message_best_of("Hello", 2, scorer).await,
+            Err(crate::err::Error::NoChoices)
+        ));
+        assert!(matches!(
+            client.send_message_best_of("Hello", 0, scorer).await,
+            Err(crate::err::Error::NoChoices)
+        ));
+        Ok(())
+    }
+}
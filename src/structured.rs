@@ -0,0 +1,262 @@
+use std::borrow::Cow;
+
+use schemars::{schema_for, JsonSchema};
+use serde::de::DeserializeOwned;
+
+use crate::client::ChatGPT;
+use crate::config::{JsonSchemaFormat, ResponseFormat};
+use crate::types::{ChatMessage, CompletionResponse, Role};
+
+/// A typed reply, deserialized from the structured output of the model
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructuredResponse<T> {
+    /// The deserialized reply
+    pub value: T,
+    /// The raw response
+    pub response: CompletionResponse,
+}
+
+impl JsonSchemaFormat {
+    /// Derives the schema from the provided type. The schema is named after the type
+    pub fn for_type<T: JsonSchema>() -> crate::Result<Self> {
+        let mut schema = schema_for!(T);
+        schema.meta_schema = None;
+        let name = T::schema_name()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Ok(Self {
+            name,
+            description: None,
+            schema: serde_json::to_value(schema)?,
+            strict: false,
+        })
+    }
+
+    /// Enables or disables strict schema enforcement
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+}
+
+impl ChatGPT {
+    /// Sends whole message history to the API, requesting a structured output with the schema derived from `T`,
+    /// and deserializes the reply into `T`.
+    ///
+    /// If the reply does not match the type, [`Error::StructuredOutputError`](crate::err::Error::StructuredOutputError)
+    /// containing the raw reply is returned. Set
+    /// [`ModelConfiguration::repair_structured_output`](crate::config::ModelConfiguration::repair_structured_output)
+    /// to repair such replies first.
+    ///
+    /// Requires the `structured` crate feature
+    pub async fn send_history_structured<T: JsonSchema + DeserializeOwned>(
+        &self,
+        history: &[ChatMessage],
+    ) -> crate::Result<StructuredResponse<T>> {
+        let format = ResponseFormat::JsonSchema {
+            json_schema: JsonSchemaFormat::for_type::<T>()?,
+        };
+        let response = self
+            .send_history_with_format(history, format.clone())
+            .await?;
+        if !self.config.repair_structured_output {
+            let value = parse_structured(&response.message().content)?;
+            return Ok(StructuredResponse { value, response });
+        }
+        let error = match parse_repaired(&response.message().content) {
+            Ok(value) => return Ok(StructuredResponse { value, response }),
+            Err(crate::err::Error::StructuredOutputError { source, .. }) => source,
+            Err(err) => return Err(err),
+        };
+
+        // a single corrective round-trip, with the error explained to the model
+        let mut corrective = history.to_vec();
+        corrective.push(response.message().clone());
+        corrective.push(ChatMessage::new(
+            Role::User,
+            format!("{REPAIR_PROMPT} The error was: {error}"),
+        ));
+        let mut repaired = self.send_history_with_format(&corrective, format).await?;
+        repaired.usage += response.usage;
+        let value = parse_repaired(&repaired.message().content)?;
+        Ok(StructuredResponse {
+            value,
+            response: repaired,
+        })
+    }
+
+    /// Sends a single message to the API without preserving message history, and deserializes the structured
+    /// reply into `T`. See [`Self::send_history_structured()`] for details.
+    ///
+    /// Requires the `structured` crate feature
+    pub async fn send_message_structured<T: JsonSchema + DeserializeOwned, S: Into<String>>(
+        &self,
+        message: S,
+    ) -> crate::Result<StructuredResponse<T>> {
+        let message = ChatMessage::new(Role::User, message);
+        self.send_history_structured(std::slice::from_ref(&message))
+            .await
+    }
+}
+
+/// Follow-up message sent when a structured reply fails to deserialize
+const REPAIR_PROMPT: &str =
+    "Your reply is not valid JSON matching the requested schema. Reply again with only the corrected JSON.";
+
+/// Same as [`parse_structured()`], but repairs the JSON first. The error still contains the original reply
+fn parse_repaired<T: DeserializeOwned>(raw: &str) -> crate::Result<T> {
+    serde_json::from_str(&repair_json(raw)).map_err(|source| {
+        crate::err::Error::StructuredOutputError {
+            source,
+            raw: raw.to_owned(),
+        }
+    })
+}
+
+/// Strips markdown code fences around the JSON and trailing commas in its objects and arrays
+fn repair_json(raw: &str) -> Cow<'_, str> {
+    let mut json = raw.trim();
+    if let Some(fenced) = json.strip_prefix("```") {
+        // the opening fence may be followed by a language tag, e.g. ```json
+        json = fenced
+            .split_once('\n')
+            .map_or("", |(_, rest)| rest)
+            .trim_end()
+            .trim_end_matches("```")
+            .trim();
+    }
+
+    let mut repaired = String::with_capacity(json.len());
+    let (mut in_string, mut escaped) = (false, false);
+    for (index, c) in json.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ','
+            && matches!(
+                json[index + 1..].trim_start().chars().next(),
+                Some('}' | ']')
+            )
+        {
+            continue;
+        }
+        repaired.push(c);
+    }
+    if repaired == raw {
+        Cow::Borrowed(raw)
+    } else {
+        Cow::Owned(repaired)
+    }
+}
+
+fn parse_structured<T: DeserializeOwned>(raw: &str) -> crate::Result<T> {
+    serde_json::from_str(raw).map_err(|source| crate::err::Error::StructuredOutputError {
+        source,
+        raw: raw.to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    use super::{parse_repaired, parse_structured, repair_json, REPAIR_PROMPT};
+    use crate::client::ChatGPT;
+    use crate::config::{JsonSchemaFormat, ModelConfiguration};
+    use crate::err::Error;
+    use crate::transport::{request_json, HttpTransport, JsonTransport};
+
+    #[derive(Debug, PartialEq, Deserialize, JsonSchema)]
+    struct Weather {
+        city: String,
+        temperature: f64,
+    }
+
+    #[test]
+    fn test_schema_for_type() -> crate::Result<()> {
+        let format = JsonSchemaFormat::for_type::<Vec<Weather>>()?;
+        assert_eq!(format.name, "Array_of_Weather");
+        assert!(format.schema.get("$schema").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_structured() {
+        let weather: Weather = parse_structured(r#"{"city":"Paris","temperature":21.5}"#).unwrap();
+        assert_eq!(weather.city, "Paris");
+
+        match parse_structured::<Weather>("It is sunny in Paris") {
+            Err(Error::StructuredOutputError { raw, .. }) => {
+                assert_eq!(raw, "It is sunny in Paris")
+            }
+            other => panic!("unexpected result: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_repair_json() {
+        let raw = "```json\n{\"city\": \"Paris, France\", \"temperature\": 21.5,}\n```";
+        let weather: Weather = parse_repaired(raw).unwrap();
+        assert_eq!(weather.city, "Paris, France");
+        assert_eq!(
+            repair_json(r#"{"list": [1, 2, ], "text": ",}"}"#),
+            r#"{"list": [1, 2 ], "text": ",}"}"#
+        );
+        assert!(matches!(
+            repair_json(r#"{"city": "Paris"}"#),
+            std::borrow::Cow::Borrowed(_)
+        ));
+    }
+
+    /// Replies with prose, unless it is asked to repair its reply
+    fn prose_transport() -> impl HttpTransport {
+        JsonTransport(|request: &reqwest::Request| {
+            let request = request_json(request);
+            let last = request["messages"].as_array().unwrap().last().unwrap();
+            let content = if last["content"].as_str().unwrap().starts_with(REPAIR_PROMPT) {
+                r#"{"city":"Paris","temperature":21.5}"#
+            } else {
+                "It is sunny in Paris"
+            };
+            serde_json::json!({ "choices": [{ "index": 0, "message": {
+                "role": "assistant", "content": content
+            } }] })
+        })
+    }
+
+    #[tokio::test]
+    async fn test_corrective_request() -> crate::Result<()> {
+        let client = ChatGPT::new_with_transport("", prose_transport(), Default::default())?;
+        assert!(matches!(
+            client
+                .send_message_structured::<Weather, _>("Weather?")
+                .await,
+            Err(Error::StructuredOutputError { .. })
+        ));
+
+        let config = ModelConfiguration {
+            repair_structured_output: true,
+            ..Default::default()
+        };
+        let client = ChatGPT::new_with_transport("", prose_transport(), config)?;
+        let weather = client
+            .send_message_structured::<Weather, _>("Weather?")
+            .await?;
+        assert_eq!(weather.value.city, "Paris");
+        Ok(())
+    }
+}
@@ -0,0 +1,242 @@
+use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer};
+use tiktoken_rs::CoreBPE;
+
+use crate::client::ChatGPT;
+use crate::config::ChatGPTEngine;
+use crate::types::{ChatMessage, ContentPart};
+
+/// Returns the BPE tokenizer used by the provided engine.
+///
+/// Custom and unknown engines fall back to the `cl100k_base` encoding.
+pub fn bpe_for_engine(engine: ChatGPTEngine) -> crate::Result<&'static CoreBPE> {
+    bpe_for_model(engine.as_str())
+}
+
+/// Returns the BPE tokenizer used by the model with the provided identifier
+fn bpe_for_model(model: &str) -> crate::Result<&'static CoreBPE> {
+    let tokenizer = get_tokenizer(model).unwrap_or(Tokenizer::Cl100kBase);
+    tiktoken_rs::bpe_for_tokenizer(tokenizer)
+        .map_err(|err| crate::err::Error::TokenizerError(err.to_string()))
+}
+
+/// Encodes the provided text into token ids of the provided engine. Special tokens are treated as plain text.
+pub fn encode(text: &str, engine: ChatGPTEngine) -> crate::Result<Vec<u32>> {
+    Ok(bpe_for_engine(engine)?.encode_ordinary(text))
+}
+
+/// Counts the prompt tokens the messages take when sent to the provided engine, so the size of the prompt is known
+/// before sending it and `max_tokens` can be sized to fit the context window.
+///
+/// Every message takes 3 tokens of overhead of the chat format, along with its role, content, text parts, name and
+/// tool calls, and every reply is primed with 3 more tokens. Images and audio are not counted.
+pub fn count_tokens(messages: &[ChatMessage], engine: ChatGPTEngine) -> crate::Result<usize> {
+    let bpe = bpe_for_engine(engine)?;
+    // every reply is primed with `<|start|>assistant<|message|>`
+    Ok(messages
+        .iter()
+        .map(|message| message_tokens(bpe, message))
+        .sum::<usize>()
+        + 3)
+}
+
+/// Counts the tokens of each message, without the tokens priming the reply
+pub(crate) fn message_token_counts(
+    messages: &[ChatMessage],
+    engine: ChatGPTEngine,
+) -> crate::Result<Vec<usize>> {
+    let bpe = bpe_for_engine(engine)?;
+    Ok(messages
+        .iter()
+        .map(|message| message_tokens(bpe, message))
+        .collect())
+}
+
+/// Counts the tokens of a single message. Every message is wrapped as `<|start|>{role}\n{content}<|end|>\n`
+fn message_tokens(bpe: &CoreBPE, message: &ChatMessage) -> usize {
+    let count = |text: &str| bpe.encode_ordinary(text).len();
+    // all role names are single tokens
+    let mut tokens = 3 + 1 + count(&message.content);
+    for part in &message.content_parts {
+        if let ContentPart::Text(text) = part {
+            tokens += count(text);
+        }
+    }
+    #[cfg(feature = "functions")]
+    {
+        if let Some(name) = &message.name {
+            tokens += 1 + count(name);
+        }
+        let calls = message
+            .function_call
+            .iter()
+            .chain(message.tool_calls.iter().map(|call| &call.function));
+        for call in calls {
+            tokens += count(&call.name) + count(&call.arguments);
+        }
+    }
+    tokens
+}
+
+/// Applies the policy to the request if it does not fit the context window of its model, returning the messages
+/// that fit if any were dropped
+#[cfg(feature = "models")]
+pub(crate) fn fit_context_window(
+    request: &crate::types::CompletionRequest<'_>,
+    policy: crate::config::ContextWindowPolicy,
+) -> crate::Result<Option<Vec<ChatMessage>>> {
+    let Some(capabilities) = crate::models::ModelCapabilities::of(&request.model) else {
+        return Ok(None);
+    };
+    let bpe = bpe_for_model(&request.model)?;
+    let reserved = request
+        .max_completion_tokens
+        .or(request.max_tokens)
+        .unwrap_or_default() as usize;
+    let mut sizes: Vec<usize> = request
+        .messages
+        .iter()
+        .map(|message| message_tokens(bpe, message))
+        .collect();
+    let context_window = capabilities.context_window;
+    let total = |sizes: &[usize]| sizes.iter().sum::<usize>() + 3 + reserved;
+    let exceeded = |tokens| crate::err::Error::ContextWindowExceeded {
+        tokens,
+        context_window,
+    };
+    if total(&sizes) <= context_window as usize {
+        return Ok(None);
+    }
+    if policy == crate::config::ContextWindowPolicy::Error {
+        return Err(exceeded(total(&sizes)));
+    }
+    let mut messages = request.messages.to_vec();
+    let budget = (context_window as usize).saturating_sub(3 + reserved);
+    crate::converse::drop_oldest_messages(&mut messages, &mut sizes, budget);
+    if total(&sizes) > context_window as usize {
+        return Err(exceeded(total(&sizes)));
+    }
+    Ok(Some(messages))
+}
+
+impl ChatGPT {
+    /// Counts the prompt tokens the messages take when sent to the engine of the client, see [`count_tokens()`].
+    ///
+    /// Requires the `tokenizer` crate feature
+    pub fn count_tokens(&self, messages: &[ChatMessage]) -> crate::Result<usize> {
+        count_tokens(messages, self.config.engine)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::ChatGPTEngine;
+    use crate::types::{ChatMessage, ContentPart, LogitBias, Role};
+
+    #[test]
+    fn test_encode() -> crate::Result<()> {
+        assert_eq!(super::encode("hello", ChatGPTEngine::Gpt4)?, vec![15339]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_user_message() -> crate::Result<()> {
+        let messages = [ChatMessage::new(Role::User, "hello")];
+        assert_eq!(super::count_tokens(&messages, ChatGPTEngine::Gpt4)?, 8);
+        Ok(())
+    }
+
+    #[test]
+    fn test_count_tokens() -> crate::Result<()> {
+        let messages = [
+            ChatMessage::new(Role::System, "You are a helpful assistant."),
+            ChatMessage::new(Role::User, "hello")
+                .with_content_part(ContentPart::Text("world".to_owned())),
+        ];
+        // 6 tokens of the system message, 2 of the user message, 4 of overhead of each message and 3 of the reply
+        assert_eq!(
+            super::count_tokens(&messages, ChatGPTEngine::Gpt4)?,
+            6 + 2 + 2 * 4 + 3
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "functions")]
+    #[test]
+    fn test_count_tool_call_tokens() -> crate::Result<()> {
+        let plain = [ChatMessage::new(Role::Assistant, "")];
+        let mut with_call = plain.clone();
+        with_call[0].tool_calls.push(crate::functions::ToolCall {
+            id: "call_1".to_owned(),
+            tool_type: "function".to_owned(),
+            function: crate::functions::FunctionCall {
+                name: "hello".to_owned(),
+                arguments: "{}".to_owned(),
+            },
+        });
+        assert!(
+            super::count_tokens(&with_call, ChatGPTEngine::Gpt4)?
+                > super::count_tokens(&plain, ChatGPTEngine::Gpt4)?
+        );
+        Ok(())
+    }
+
+    #[cfg(feature = "models")]
+    #[test]
+    fn test_fit_context_window() -> crate::Result<()> {
+        use crate::config::{ContextWindowPolicy, ModelConfiguration};
+        use crate::types::CompletionRequest;
+
+        let config = ModelConfiguration {
+            engine: ChatGPTEngine::Gpt4,
+            max_tokens: Some(8_000),
+            ..Default::default()
+        };
+        let history = vec![
+            ChatMessage::new(Role::System, "You are a helpful assistant."),
+            ChatMessage::new(Role::User, "hello ".repeat(200)),
+            ChatMessage::new(Role::Assistant, "hello"),
+            ChatMessage::new(Role::User, "hello"),
+        ];
+        let request = CompletionRequest::from_config(&config, history);
+        assert!(matches!(
+            super::fit_context_window(&request, ContextWindowPolicy::Error),
+            Err(crate::err::Error::ContextWindowExceeded {
+                context_window: 8_192,
+                ..
+            })
+        ));
+        let messages =
+            super::fit_context_window(&request, ContextWindowPolicy::TruncateOldest)?.unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].role, Role::System);
+        assert_eq!(messages[1].content, "hello");
+
+        // pinned messages are never dropped
+        let mut pinned = request.messages.to_vec();
+        pinned[1] = pinned[1].clone().with_pinned();
+        let request = CompletionRequest {
+            messages: pinned.into(),
+            ..request
+        };
+        assert!(matches!(
+            super::fit_context_window(&request, ContextWindowPolicy::TruncateOldest),
+            Err(crate::err::Error::ContextWindowExceeded { .. })
+        ));
+
+        let request = CompletionRequest {
+            max_tokens: Some(100),
+            ..request
+        };
+        assert!(super::fit_context_window(&request, ContextWindowPolicy::Error)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_ban_words() -> crate::Result<()> {
+        let bias = LogitBias::ban_words(["hello"], ChatGPTEngine::Gpt4)?;
+        assert_eq!(bias.0.get(&15339), Some(&-100.0));
+        // the space-prefixed variant is banned too
+        assert_eq!(bias.0.len(), 2);
+        Ok(())
+    }
+}
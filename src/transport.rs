@@ -0,0 +1,73 @@
+use async_trait::async_trait;
+use reqwest::{Request, Response};
+
+/// The HTTP transport that sends the requests of the client, e.g. to use a different HTTP stack, or to serve
+/// canned responses in tests.
+///
+/// Both regular and streamed responses are read from the returned [`Response`], which can be constructed
+/// from an `http::Response` with any body. See
+/// [`ChatGPT::new_with_transport()`](crate::client::ChatGPT::new_with_transport).
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+pub trait HttpTransport: Send + Sync {
+    /// Sends the request and returns the response, whose body may still be streamed
+    async fn send(&self, request: Request) -> crate::Result<Response>;
+}
+
+/// The default transport
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl HttpTransport for reqwest::Client {
+    async fn send(&self, request: Request) -> crate::Result<Response> {
+        Ok(self.execute(request).await?)
+    }
+}
+
+/// Sends the requests through the middleware stack
+#[cfg(feature = "middleware")]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+impl HttpTransport for reqwest_middleware::ClientWithMiddleware {
+    async fn send(&self, request: Request) -> crate::Result<Response> {
+        self.execute(request).await.map_err(|err| match err {
+            reqwest_middleware::Error::Reqwest(err) => crate::err::Error::ClientError(err),
+            err => crate::err::Error::MiddlewareError(err),
+        })
+    }
+}
+
+/// Replies to every request with the JSON built by the closure, to test the client without a server
+#[cfg(test)]
+pub(crate) struct JsonTransport<F>(pub F);
+
+#[cfg(test)]
+#[async_trait]
+impl<F> HttpTransport for JsonTransport<F>
+where
+    F: Fn(&Request) -> serde_json::Value + Send + Sync,
+{
+    async fn send(&self, request: Request) -> crate::Result<Response> {
+        Ok(json_response(200, (self.0)(&request)))
+    }
+}
+
+/// A response with the status and JSON body
+#[cfg(test)]
+pub(crate) fn json_response<B: ToString>(status: u16, body: B) -> Response {
+    http::Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(body.to_string())
+        .unwrap()
+        .into()
+}
+
+/// The JSON body of the request, or `null` if it has none
+#[cfg(test)]
+pub(crate) fn request_json(request: &Request) -> serde_json::Value {
+    request
+        .body()
+        .and_then(reqwest::Body::as_bytes)
+        .map(|body| serde_json::from_slice(body).unwrap())
+        .unwrap_or_default()
+}
@@ -1,12 +1,31 @@
+#[cfg(feature = "tokenizer")]
+use crate::config::ChatGPTEngine;
+use crate::config::{ModelConfiguration, ReasoningEffort, ResponseFormat};
 #[cfg(feature = "functions")]
-use crate::functions::FunctionCall;
-use serde::{Deserialize, Deserializer, Serialize};
+use crate::functions::{FunctionCall, ToolCall, ToolChoice};
+use derive_builder::Builder;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::AddAssign;
+use std::time::Duration;
+#[cfg(feature = "streams")]
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{Arc, Mutex, PoisonError},
+    task::{Context, Poll, Wake, Waker},
+};
+use uuid::Uuid;
 
 /// A role of a message sender, can be:
 /// - `System`, for starting system message, that sets the tone of model
 /// - `Assistant`, for messages sent by ChatGPT
 /// - `User`, for messages sent by user
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize, Eq, Ord)]
+/// - `Developer`, for instructions to reasoning models, that replaces `System` for them
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize, Eq, Ord, Hash,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum Role {
     /// A system message, automatically sent at the start to set the tone of the model
@@ -14,13 +33,25 @@ pub enum Role {
     /// A message sent by ChatGPT
     Assistant,
     /// A message sent by the user
+    #[default]
     User,
     /// A message related to ChatGPT functions. Does not have much use without the `functions` feature.
     Function,
+    /// A message containing the result of a tool call. Does not have much use without the `functions` feature.
+    Tool,
+    /// Instructions from the developer, that take the place of the system message for o-series reasoning models
+    Developer,
+}
+
+impl Role {
+    /// Whether messages of this role instruct the model, i.e. it is either `System` or `Developer`
+    pub fn is_instruction(self) -> bool {
+        matches!(self, Self::System | Self::Developer)
+    }
 }
 
 /// Container for the sent/received ChatGPT messages
-#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
 pub struct ChatMessage {
     /// Role of message sender
     pub role: Role,
@@ -29,58 +60,377 @@ pub struct ChatMessage {
     pub content: String,
     /// Function call (if present)
     #[cfg(feature = "functions")]
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
     pub function_call: Option<FunctionCall>,
+    /// Tool calls requested by the model
+    #[cfg(feature = "functions")]
+    #[serde(default, deserialize_with = "deserialize_maybe_null_vec")]
+    pub tool_calls: Vec<ToolCall>,
+    /// Identifier of the tool call this message is the result of. Only set for [`Role::Tool`] messages
+    #[cfg(feature = "functions")]
+    #[serde(default)]
+    pub tool_call_id: Option<String>,
+    /// Name of the function this message is the result of. Only set for [`Role::Function`] messages
+    #[cfg(feature = "functions")]
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Additional parts of a multimodal message, e.g. images. They are sent to the API after the text content
+    #[serde(default)]
+    pub content_parts: Vec<ContentPart>,
+    /// The thinking trace returned by reasoning models of some backends (e.g. DeepSeek). It is never sent to the API
+    #[serde(default)]
+    pub reasoning_content: Option<String>,
+    /// Stable identifier of this message, assigned once it is stored in a [`Conversation`](crate::converse::Conversation).
+    /// It is never sent to the API
+    #[serde(default)]
+    pub id: Option<Uuid>,
+    /// Token usage of the request that generated this reply, recorded once it is stored in a
+    /// [`Conversation`](crate::converse::Conversation) if the backend reported it. It is never sent to the API
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// Whether this message is kept by the truncation and compaction of the history of a
+    /// [`Conversation`](crate::converse::Conversation), e.g. few-shot examples. It is never sent to the API
+    #[serde(default)]
+    pub pinned: bool,
 }
 
 fn deserialize_maybe_null<'de, D>(deserializer: D) -> Result<String, D::Error>
     where D: Deserializer<'de> {
+    // binary formats such as postcard are not self-describing, and contain the plain string written on serialization
+    if !deserializer.is_human_readable() {
+        return String::deserialize(deserializer);
+    }
     let buf = Option::<String>::deserialize(deserializer)?;
     Ok(buf.unwrap_or(String::new()))
 }
 
+fn deserialize_maybe_null_vec<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    if !deserializer.is_human_readable() {
+        return Vec::deserialize(deserializer);
+    }
+    Ok(Option::<Vec<T>>::deserialize(deserializer)?.unwrap_or_default())
+}
+
 impl ChatMessage {
+    /// Constructs a new message with the provided role and text content
+    pub fn new<S: Into<String>>(role: Role, content: S) -> Self {
+        Self {
+            role,
+            content: content.into(),
+            #[cfg(feature = "functions")]
+            function_call: None,
+            #[cfg(feature = "functions")]
+            tool_calls: Vec::new(),
+            #[cfg(feature = "functions")]
+            tool_call_id: None,
+            #[cfg(feature = "functions")]
+            name: None,
+            content_parts: Vec::new(),
+            reasoning_content: None,
+            id: None,
+            usage: None,
+            pinned: false,
+        }
+    }
+
+    /// Constructs a message with the result of a tool call, that has to be sent back to the model after it calls a tool.
+    ///
+    /// The content is sent as is, use [`Self::tool_result_json()`] to send serialized data instead
+    #[cfg(feature = "functions")]
+    pub fn tool_result<I: Into<String>, S: Into<String>>(tool_call_id: I, content: S) -> Self {
+        Self {
+            tool_call_id: Some(tool_call_id.into()),
+            ..Self::new(Role::Tool, content)
+        }
+    }
+
+    /// Constructs a message with the result of a tool call, serialized to JSON
+    #[cfg(feature = "functions")]
+    pub fn tool_result_json<I: Into<String>, T: Serialize + ?Sized>(
+        tool_call_id: I,
+        value: &T,
+    ) -> crate::Result<Self> {
+        let content = serde_json::to_string(value)?;
+        Ok(Self::tool_result(tool_call_id, content))
+    }
+
+    /// Constructs a message with the result of a deprecated function call, serialized to JSON.
+    ///
+    /// It has to be sent back to the model after it calls a function
+    #[cfg(feature = "functions")]
+    pub fn function_result<N: Into<String>, T: Serialize + ?Sized>(
+        name: N,
+        value: &T,
+    ) -> crate::Result<Self> {
+        Ok(Self {
+            name: Some(name.into()),
+            ..Self::new(Role::Function, serde_json::to_string(value)?)
+        })
+    }
+
+    /// Pins this message, so that it is kept by the truncation and compaction of the history of a conversation
+    pub fn with_pinned(mut self) -> Self {
+        self.pinned = true;
+        self
+    }
+
+    /// Appends a content part to this message, e.g. an image
+    pub fn with_content_part<P: Into<ContentPart>>(mut self, part: P) -> Self {
+        self.content_parts.push(part.into());
+        self
+    }
+
     /// Converts multiple response chunks into multiple (or a single) chat messages
     #[cfg(feature = "streams")]
     pub fn from_response_chunks(chunks: Vec<ResponseChunk>) -> Vec<Self> {
-        let mut result: Vec<Self> = Vec::new();
-        for chunk in chunks {
-            match chunk {
-                ResponseChunk::Content {
-                    delta,
-                    response_index,
-                } => {
-                    let msg = result
-                        .get_mut(response_index)
-                        .expect("Invalid response chunk sequence!");
-                    msg.content.push_str(&delta);
-                }
-                ResponseChunk::BeginResponse {
-                    role,
-                    response_index: _,
-                } => {
-                    let msg = ChatMessage {
-                        role,
-                        content: String::new(),
-                        #[cfg(feature = "functions")]
-                        function_call: None,
-                    };
-                    result.push(msg);
-                }
-                _ => {}
+        let mut accumulator = ResponseAccumulator::default();
+        for chunk in &chunks {
+            accumulator.push(chunk);
+        }
+        accumulator
+            .finish()
+            .message_choices
+            .into_iter()
+            .map(|choice| choice.message)
+            .collect()
+    }
+}
+
+/// A part of a multimodal message, sent alongside its text content
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentPart {
+    /// An additional piece of text
+    Text(String),
+    /// An image, for models with vision capabilities
+    Image(ImagePart),
+    /// An audio recording, for models with audio input capabilities (e.g. `gpt-4o-audio-preview`)
+    Audio(AudioPart),
+}
+
+impl From<ImagePart> for ContentPart {
+    fn from(value: ImagePart) -> Self {
+        Self::Image(value)
+    }
+}
+
+impl From<AudioPart> for ContentPart {
+    fn from(value: AudioPart) -> Self {
+        Self::Audio(value)
+    }
+}
+
+/// An image sent to models with vision capabilities
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+pub struct ImagePart {
+    /// URL of the image, or the image itself encoded as a base64 `data:` URL
+    pub url: String,
+    /// The fidelity the model uses to process the image
+    #[serde(default)]
+    pub detail: Option<ImageDetail>,
+}
+
+impl ImagePart {
+    /// References an image by its URL. The image will be downloaded by the API backend
+    pub fn from_url<S: Into<String>>(url: S) -> Self {
+        Self {
+            url: url.into(),
+            detail: None,
+        }
+    }
+
+    /// Embeds the image with the provided MIME type (e.g. `image/png`) as a base64 `data:` URL
+    pub fn from_bytes<B: AsRef<[u8]>>(bytes: B, mime_type: &str) -> Self {
+        use base64::Engine;
+
+        Self::from_url(format!(
+            "data:{mime_type};base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(bytes)
+        ))
+    }
+
+    /// Sets the fidelity the model uses to process the image
+    pub fn with_detail(mut self, detail: ImageDetail) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+}
+
+/// The fidelity the model uses to process an image
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageDetail {
+    /// The model chooses the detail level based on the image size
+    #[default]
+    Auto,
+    /// Low resolution image processing, uses less tokens
+    Low,
+    /// High resolution image processing
+    High,
+}
+
+/// An audio recording sent to models with audio input capabilities
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+pub struct AudioPart {
+    /// The base64 encoded audio data
+    pub data: String,
+    /// The format of the audio data
+    pub format: AudioFormat,
+}
+
+impl AudioPart {
+    /// Encodes the raw audio data of the provided format
+    pub fn from_bytes<B: AsRef<[u8]>>(bytes: B, format: AudioFormat) -> Self {
+        use base64::Engine;
+
+        Self {
+            data: base64::engine::general_purpose::STANDARD.encode(bytes),
+            format,
+        }
+    }
+
+    /// Reads a local WAV or MP3 file. The format is detected from the file extension.
+    ///
+    /// Requires the `uploads` crate feature. Not available on WASM
+    #[cfg(all(feature = "uploads", not(target_arch = "wasm32")))]
+    pub async fn from_path<P: AsRef<std::path::Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let format = match crate::upload::mime_from_path(path) {
+            Some("audio/wav") => AudioFormat::Wav,
+            Some("audio/mpeg") => AudioFormat::Mp3,
+            _ => {
+                return Err(crate::err::Error::ParsingError(format!(
+                    "Unsupported audio file format: {}",
+                    path.display()
+                )))
+            }
+        };
+        Ok(Self::from_bytes(tokio::fs::read(path).await?, format))
+    }
+}
+
+/// The format of audio input
+#[derive(
+    Debug, Default, Copy, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioFormat {
+    /// WAV audio
+    #[default]
+    Wav,
+    /// MP3 audio
+    Mp3,
+}
+
+/// Biases applied to the likelihood of specific tokens appearing in the completion.
+///
+/// Maps token ids to a bias value from `-100` (bans the token) to `100` (exclusively selects the token)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LogitBias(pub HashMap<u32, f32>);
+
+impl LogitBias {
+    /// Constructs an empty logit bias map
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the bias for a single token id
+    pub fn with_bias(mut self, token: u32, bias: f32) -> Self {
+        self.0.insert(token, bias);
+        self
+    }
+
+    /// Returns `true` if no biases are set
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Tokenizes the provided words with the tokenizer of the engine and applies the bias to all of their tokens.
+    ///
+    /// Each word is also tokenized with a leading space, as that is how it usually appears mid-sentence.
+    ///
+    /// **NOTE**: words that span multiple tokens bias all of their parts, which may affect other words sharing these tokens.
+    ///
+    /// Requires the `tokenizer` crate feature
+    #[cfg(feature = "tokenizer")]
+    pub fn bias_words<I, S>(words: I, engine: ChatGPTEngine, bias: f32) -> crate::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let bpe = crate::tokenizer::bpe_for_engine(engine)?;
+        let mut result = Self::new();
+        for word in words {
+            let word = word.as_ref();
+            for token in bpe
+                .encode_ordinary(word)
+                .into_iter()
+                .chain(bpe.encode_ordinary(&format!(" {word}")))
+            {
+                result.0.insert(token, bias);
             }
         }
-        result
+        Ok(result)
+    }
+
+    /// Tokenizes the provided words with the tokenizer of the engine and bans all of their tokens from the completion.
+    ///
+    /// See [`Self::bias_words`] for details.
+    ///
+    /// Requires the `tokenizer` crate feature
+    #[cfg(feature = "tokenizer")]
+    pub fn ban_words<I, S>(words: I, engine: ChatGPTEngine) -> crate::Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        Self::bias_words(words, engine, -100.0)
     }
 }
 
-/// A request struct sent to the API to request a message completion
-#[derive(Debug, Clone, PartialEq, Serialize)]
+impl PartialOrd for LogitBias {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        // hash maps are not ordered, so biases are compared sorted by token id
+        let sorted = |bias: &Self| {
+            let mut entries = bias.0.iter().map(|(k, v)| (*k, *v)).collect::<Vec<_>>();
+            entries.sort_by_key(|(token, _)| *token);
+            entries
+        };
+        sorted(self).partial_cmp(&sorted(other))
+    }
+}
+
+impl From<LogitBias> for HashMap<u32, f32> {
+    fn from(value: LogitBias) -> Self {
+        value.0
+    }
+}
+
+impl From<HashMap<u32, f32>> for LogitBias {
+    fn from(value: HashMap<u32, f32>) -> Self {
+        Self(value)
+    }
+}
+
+/// A request struct sent to the API to request a message completion.
+///
+/// Can either borrow or own its data, so requests can be constructed in advance and sent multiple times
+/// with [`ChatGPT::send_request()`](crate::client::ChatGPT::send_request).
+#[derive(Debug, Clone, PartialEq, Serialize, Builder)]
+#[builder(default, setter(into))]
 pub struct CompletionRequest<'a> {
     /// The model to be used, currently `gpt-3.5-turbo`, but may change in future
-    pub model: &'a str,
+    pub model: Cow<'a, str>,
     /// The message history, including the message that requires completion, which should be the last one
-    pub messages: &'a Vec<ChatMessage>,
+    #[serde(serialize_with = "serialize_wire_messages")]
+    pub messages: Cow<'a, [ChatMessage]>,
     /// Whether the message response should be gradually streamed
     pub stream: bool,
     /// The extra randomness of response
@@ -90,6 +440,10 @@ pub struct CompletionRequest<'a> {
     /// Controls the maximum number of tokens to generate in the completion
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    /// Controls the maximum number of tokens to generate in the completion, including reasoning tokens.
+    /// Replaces `max_tokens`, which is rejected by o-series reasoning models
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<u32>,
     /// Determines how much to penalize new tokens based on their existing frequency so far
     pub frequency_penalty: f32,
     /// Determines how much to penalize new tokens pased on their existing presence so far
@@ -97,15 +451,278 @@ pub struct CompletionRequest<'a> {
     /// Determines the amount of output responses
     #[serde(rename = "n")]
     pub reply_count: u32,
+    /// Sequences where the API stops generating further tokens
+    #[serde(skip_serializing_if = "is_empty_slice")]
+    pub stop: Cow<'a, [String]>,
+    /// Biases applied to the likelihood of specific tokens appearing in the completion
+    #[serde(skip_serializing_if = "LogitBias::is_empty")]
+    pub logit_bias: Cow<'a, LogitBias>,
+    /// Seed for best-effort deterministic sampling
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Whether log probabilities of the output tokens are returned
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub logprobs: bool,
+    /// The amount of most likely tokens, from 0 to 20, returned at each position. Requires `logprobs`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_logprobs: Option<u8>,
+    /// How much effort reasoning models spend on reasoning
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reasoning_effort: Option<ReasoningEffort>,
+    /// Identifier of the end user on whose behalf the request is sent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<Cow<'a, str>>,
+    /// Options of streamed responses. Only sent with streamed requests
+    #[cfg(feature = "streams")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stream_options: Option<StreamOptions>,
+    /// The format the model must output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_format: Option<ResponseFormat>,
     /// All functions that can be called by ChatGPT
     #[cfg(feature = "functions")]
-    #[serde(skip_serializing_if = "Vec::is_empty")]
-    pub functions: &'a Vec<serde_json::Value>,
+    #[serde(skip_serializing_if = "is_empty_slice")]
+    pub functions: Cow<'a, [serde_json::Value]>,
+    /// All tools that can be called by the model
+    #[cfg(feature = "functions")]
+    #[serde(skip_serializing_if = "is_empty_slice")]
+    pub tools: Cow<'a, [serde_json::Value]>,
+    /// Determines whether and which tools the model calls
+    #[cfg(feature = "functions")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+}
+
+impl<'a> CompletionRequest<'a> {
+    /// Constructs a request for the message history with the parameters of the provided configuration
+    pub fn from_config<M: Into<Cow<'a, [ChatMessage]>>>(
+        config: &'a ModelConfiguration,
+        messages: M,
+    ) -> Self {
+        let use_max_completion_tokens = config
+            .use_max_completion_tokens
+            .unwrap_or_else(|| config.engine.is_reasoning_model());
+        let (max_tokens, max_completion_tokens) = if use_max_completion_tokens {
+            (None, config.max_tokens)
+        } else {
+            (config.max_tokens, None)
+        };
+        Self {
+            model: Cow::Borrowed(config.engine.as_str()),
+            messages: messages.into(),
+            stream: false,
+            temperature: config.temperature,
+            top_p: config.top_p,
+            max_tokens,
+            max_completion_tokens,
+            frequency_penalty: config.frequency_penalty,
+            presence_penalty: config.presence_penalty,
+            reply_count: config.reply_count,
+            stop: Cow::Borrowed(&config.stop),
+            logit_bias: Cow::Borrowed(&config.logit_bias),
+            seed: config.seed,
+            logprobs: config.logprobs,
+            top_logprobs: config.top_logprobs,
+            reasoning_effort: config.reasoning_effort,
+            user: config.user.as_deref().map(Cow::Borrowed),
+            #[cfg(feature = "streams")]
+            stream_options: config.include_stream_usage.then_some(StreamOptions {
+                include_usage: true,
+            }),
+            response_format: config.response_format.clone(),
+            #[cfg(feature = "functions")]
+            functions: Cow::Borrowed(&[]),
+            #[cfg(feature = "functions")]
+            tools: Cow::Borrowed(&[]),
+            #[cfg(feature = "functions")]
+            tool_choice: config.tool_choice.clone(),
+        }
+    }
+
+    /// Constructs a new builder for the request. Unset parameters are taken from the default [`ModelConfiguration`]
+    pub fn builder() -> CompletionRequestBuilder<'a> {
+        CompletionRequestBuilder::default()
+    }
+
+    /// A copy of this request that borrows all data from it, with the `stream` flag overridden
+    pub(crate) fn borrowed_with_stream(&self, stream: bool) -> CompletionRequest<'_> {
+        CompletionRequest {
+            model: Cow::Borrowed(&self.model),
+            messages: Cow::Borrowed(&self.messages),
+            stream,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.max_tokens,
+            max_completion_tokens: self.max_completion_tokens,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+            reply_count: self.reply_count,
+            stop: Cow::Borrowed(&self.stop),
+            logit_bias: Cow::Borrowed(&self.logit_bias),
+            seed: self.seed,
+            logprobs: self.logprobs,
+            top_logprobs: self.top_logprobs,
+            reasoning_effort: self.reasoning_effort,
+            user: self.user.as_deref().map(Cow::Borrowed),
+            #[cfg(feature = "streams")]
+            stream_options: self.stream_options.filter(|_| stream),
+            response_format: self.response_format.clone(),
+            #[cfg(feature = "functions")]
+            functions: Cow::Borrowed(&self.functions),
+            #[cfg(feature = "functions")]
+            tools: Cow::Borrowed(&self.tools),
+            #[cfg(feature = "functions")]
+            tool_choice: self.tool_choice.clone(),
+        }
+    }
+
+    /// A copy of this request that owns all of its data, so it outlives the borrowed data
+    pub(crate) fn to_static(&self) -> CompletionRequest<'static> {
+        CompletionRequest {
+            model: Cow::Owned(self.model.clone().into_owned()),
+            messages: Cow::Owned(self.messages.clone().into_owned()),
+            stream: self.stream,
+            temperature: self.temperature,
+            top_p: self.top_p,
+            max_tokens: self.max_tokens,
+            max_completion_tokens: self.max_completion_tokens,
+            frequency_penalty: self.frequency_penalty,
+            presence_penalty: self.presence_penalty,
+            reply_count: self.reply_count,
+            stop: Cow::Owned(self.stop.clone().into_owned()),
+            logit_bias: Cow::Owned(self.logit_bias.clone().into_owned()),
+            seed: self.seed,
+            logprobs: self.logprobs,
+            top_logprobs: self.top_logprobs,
+            reasoning_effort: self.reasoning_effort,
+            user: self.user.clone().map(|user| Cow::Owned(user.into_owned())),
+            #[cfg(feature = "streams")]
+            stream_options: self.stream_options,
+            response_format: self.response_format.clone(),
+            #[cfg(feature = "functions")]
+            functions: Cow::Owned(self.functions.clone().into_owned()),
+            #[cfg(feature = "functions")]
+            tools: Cow::Owned(self.tools.clone().into_owned()),
+            #[cfg(feature = "functions")]
+            tool_choice: self.tool_choice.clone(),
+        }
+    }
+}
+
+impl Default for CompletionRequest<'_> {
+    fn default() -> Self {
+        CompletionRequest::from_config(&ModelConfiguration::default(), Vec::new()).to_static()
+    }
+}
+
+/// Options of streamed responses
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Serialize)]
+#[cfg(feature = "streams")]
+pub struct StreamOptions {
+    /// Whether an additional chunk with the token usage of the whole request is streamed before the end of the stream
+    pub include_usage: bool,
+}
+
+fn is_empty_slice<T>(slice: &[T]) -> bool {
+    slice.is_empty()
+}
+
+fn serialize_wire_messages<S: Serializer>(
+    messages: &[ChatMessage],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(messages.iter().map(WireMessage::from))
+}
+
+/// The shape of a message expected by the API, which differs from the stored [`ChatMessage`] for multimodal messages
+#[derive(Serialize)]
+struct WireMessage<'a> {
+    role: Role,
+    content: WireContent<'a>,
+    #[cfg(feature = "functions")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    function_call: Option<&'a FunctionCall>,
+    #[cfg(feature = "functions")]
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tool_calls: &'a [ToolCall],
+    #[cfg(feature = "functions")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<&'a str>,
+    #[cfg(feature = "functions")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum WireContent<'a> {
+    Text(&'a str),
+    Parts(Vec<WireContentPart<'a>>),
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum WireContentPart<'a> {
+    Text { text: &'a str },
+    ImageUrl { image_url: WireImageUrl<'a> },
+    InputAudio { input_audio: &'a AudioPart },
+}
+
+#[derive(Serialize)]
+struct WireImageUrl<'a> {
+    url: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<ImageDetail>,
+}
+
+impl<'a> From<&'a ContentPart> for WireContentPart<'a> {
+    fn from(value: &'a ContentPart) -> Self {
+        match value {
+            ContentPart::Text(text) => Self::Text { text },
+            ContentPart::Image(image) => Self::ImageUrl {
+                image_url: WireImageUrl {
+                    url: &image.url,
+                    detail: image.detail,
+                },
+            },
+            ContentPart::Audio(input_audio) => Self::InputAudio { input_audio },
+        }
+    }
+}
+
+impl<'a> From<&'a ChatMessage> for WireMessage<'a> {
+    fn from(value: &'a ChatMessage) -> Self {
+        let content = if value.content_parts.is_empty() {
+            WireContent::Text(&value.content)
+        } else {
+            let text = (!value.content.is_empty()).then(|| WireContentPart::Text {
+                text: &value.content,
+            });
+            WireContent::Parts(
+                text.into_iter()
+                    .chain(value.content_parts.iter().map(WireContentPart::from))
+                    .collect(),
+            )
+        };
+        Self {
+            role: value.role,
+            content,
+            #[cfg(feature = "functions")]
+            function_call: value.function_call.as_ref(),
+            #[cfg(feature = "functions")]
+            tool_calls: &value.tool_calls,
+            #[cfg(feature = "functions")]
+            tool_call_id: value.tool_call_id.as_deref(),
+            #[cfg(feature = "functions")]
+            name: value.name.as_deref(),
+        }
+    }
 }
 
 /// Represents a response from the API
 #[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum ServerResponse {
     /// An error occurred, most likely the model was just overloaded
     Error {
@@ -118,16 +735,26 @@ pub enum ServerResponse {
 
 /// An error happened while requesting completion
 #[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct CompletionError {
     /// Message, describing the error
     pub message: String,
-    /// The type of error. Example: `server_error`
-    #[serde(rename = "type")]
+    /// The type of error. Example: `server_error`. Empty if the backend does not report it
+    #[serde(default, rename = "type")]
     pub error_type: String,
+    /// Error code. Example: `rate_limit_exceeded`
+    #[serde(default)]
+    pub code: Option<String>,
+    /// The request parameter the error relates to, if any
+    #[serde(default)]
+    pub param: Option<String>,
 }
 
-/// A response struct received from the API after requesting a message completion
+/// A response struct received from the API after requesting a message completion.
+///
+/// Unknown fields are ignored, unless the `strict` crate feature is enabled.
 #[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct CompletionResponse {
     /// Unique ID of the message, but not in a UUID format.
     /// Example: `chatcmpl-6p5FEv1JHictSSnDZsGU4KvbuBsbu`
@@ -136,13 +763,27 @@ pub struct CompletionResponse {
     /// Unix seconds timestamp of when the response was created
     #[serde(rename = "created")]
     pub created_timestamp: Option<u64>,
-    /// The model that was used for this completion
+    /// The model that was used for this completion. Empty if the backend does not report it
+    #[serde(default)]
     pub model: String,
-    /// Token usage of this completion
+    /// Token usage of this completion. Zeroed if the backend does not report it
+    #[serde(default)]
     pub usage: TokenUsage,
     /// Message choices for this response, guaranteed to contain at least one message response
     #[serde(rename = "choices")]
     pub message_choices: Vec<MessageChoice>,
+    /// Type of the returned object, usually `chat.completion`
+    #[serde(default)]
+    pub object: Option<String>,
+    /// Content filtering results of the prompt, reported by Azure OpenAI
+    #[serde(default, alias = "prompt_annotations")]
+    pub prompt_filter_results: Vec<PromptFilterResult>,
+    /// Fingerprint of the backend configuration that generated the response
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
+    /// Rate limit state reported in the response headers. `None` if the backend does not report it
+    #[serde(skip)]
+    pub rate_limit: Option<RateLimitInfo>,
 }
 
 impl CompletionResponse {
@@ -151,21 +792,133 @@ impl CompletionResponse {
         // Unwrap is safe here, as we know that at least one message choice is provided
         &self.message_choices.first().unwrap().message
     }
+
+    /// All message choices of this response, when more than one reply is requested with `reply_count`
+    pub fn choices(&self) -> &[MessageChoice] {
+        &self.message_choices
+    }
+
+    /// The message of the choice with the provided index, if it exists
+    pub fn choice(&self, index: usize) -> Option<&ChatMessage> {
+        self.message_choices.get(index).map(|choice| &choice.message)
+    }
+
+    /// Iterates over the messages of all choices
+    pub fn messages(&self) -> impl Iterator<Item = &ChatMessage> {
+        self.message_choices.iter().map(|choice| &choice.message)
+    }
 }
 
 /// A message completion choice struct
 #[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct MessageChoice {
     /// The actual message
     pub message: ChatMessage,
-    /// The reason completion was stopped
-    pub finish_reason: String,
+    /// The reason completion was stopped. `None` if the backend does not report it
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
     /// The index of this message in the outer `message_choices` array
+    #[serde(default)]
     pub index: u32,
+    /// Content filtering results of this message, reported by Azure OpenAI
+    #[serde(default)]
+    pub content_filter_results: Option<ContentFilterResults>,
+    /// Log probabilities of the message tokens, if requested with `logprobs`
+    #[serde(default)]
+    pub logprobs: Option<Logprobs>,
 }
 
-/// The token usage of a specific response
-#[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize)]
+/// The reason the model stopped generating a message
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(from = "String")]
+pub enum FinishReason {
+    /// The model finished the message or hit a stop sequence
+    Stop,
+    /// The message was cut off by the token limit
+    Length,
+    /// The model called tools
+    ToolCalls,
+    /// The model called a deprecated function
+    FunctionCall,
+    /// The message was cut off by the content filter
+    ContentFilter,
+    /// A reason unknown to this crate, reported by newer or alternative backends
+    Other(String),
+}
+
+impl FinishReason {
+    /// The reason as reported by the API, e.g. `tool_calls`
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Stop => "stop",
+            Self::Length => "length",
+            Self::ToolCalls => "tool_calls",
+            Self::FunctionCall => "function_call",
+            Self::ContentFilter => "content_filter",
+            Self::Other(other) => other,
+        }
+    }
+}
+
+impl From<String> for FinishReason {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "stop" => Self::Stop,
+            "length" => Self::Length,
+            "tool_calls" => Self::ToolCalls,
+            "function_call" => Self::FunctionCall,
+            "content_filter" => Self::ContentFilter,
+            _ => Self::Other(value),
+        }
+    }
+}
+
+impl std::fmt::Display for FinishReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Log probabilities of the tokens of a message choice
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct Logprobs {
+    /// Log probabilities of the message content tokens
+    #[serde(default, deserialize_with = "deserialize_maybe_null_vec")]
+    pub content: Vec<TokenLogprob>,
+}
+
+/// Log probability of a single token, along with the most likely alternatives
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct TokenLogprob {
+    /// The token
+    pub token: String,
+    /// Log probability of the token
+    pub logprob: f64,
+    /// UTF-8 bytes of the token, as tokens may contain parts of multibyte characters
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+    /// The most likely tokens at this position, if requested with `top_logprobs`
+    #[serde(default, deserialize_with = "deserialize_maybe_null_vec")]
+    pub top_logprobs: Vec<TopLogprob>,
+}
+
+/// One of the most likely tokens at a position
+#[derive(Debug, Clone, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct TopLogprob {
+    /// The token
+    pub token: String,
+    /// Log probability of the token
+    pub logprob: f64,
+    /// UTF-8 bytes of the token
+    #[serde(default)]
+    pub bytes: Option<Vec<u8>>,
+}
+
+/// The token usage of a specific response. Counts not reported by the backend are zeroed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(default)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
 pub struct TokenUsage {
     /// Tokens spent on the prompt message (including previous messages)
     pub prompt_tokens: u32,
@@ -173,10 +926,165 @@ pub struct TokenUsage {
     pub completion_tokens: u32,
     /// Total amount of tokens used (`prompt_tokens + completion_tokens`)
     pub total_tokens: u32,
+    /// Breakdown of the prompt tokens, if reported by the backend
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+    /// Breakdown of the completion tokens, if reported by the backend
+    pub completion_tokens_details: Option<CompletionTokensDetails>,
+}
+
+impl AddAssign for TokenUsage {
+    fn add_assign(&mut self, rhs: Self) {
+        self.prompt_tokens += rhs.prompt_tokens;
+        self.completion_tokens += rhs.completion_tokens;
+        self.total_tokens += rhs.total_tokens;
+        add_details(&mut self.prompt_tokens_details, rhs.prompt_tokens_details);
+        add_details(
+            &mut self.completion_tokens_details,
+            rhs.completion_tokens_details,
+        );
+    }
+}
+
+fn add_details<T: AddAssign + Default>(details: &mut Option<T>, rhs: Option<T>) {
+    if let Some(rhs) = rhs {
+        *details.get_or_insert_with(T::default) += rhs;
+    }
+}
+
+/// Cumulative requests and token usage of a client, see [`ChatGPT::usage_snapshot()`](crate::client::ChatGPT::usage_snapshot)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct UsageSnapshot {
+    /// Requests sent to the API, not counting retries
+    pub requests: u64,
+    /// Tokens spent on prompts
+    pub prompt_tokens: u64,
+    /// Prompt tokens served from the prompt cache
+    pub cached_tokens: u64,
+    /// Tokens spent on completions
+    pub completion_tokens: u64,
+    /// Completion tokens spent on the hidden thinking of reasoning models
+    pub reasoning_tokens: u64,
+    /// Total amount of tokens used
+    pub total_tokens: u64,
+}
+
+impl UsageSnapshot {
+    pub(crate) fn add_usage(&mut self, usage: &TokenUsage) {
+        self.prompt_tokens += u64::from(usage.prompt_tokens);
+        self.completion_tokens += u64::from(usage.completion_tokens);
+        self.total_tokens += u64::from(usage.total_tokens);
+        if let Some(details) = usage.prompt_tokens_details {
+            self.cached_tokens += u64::from(details.cached_tokens);
+        }
+        if let Some(details) = usage.completion_tokens_details {
+            self.reasoning_tokens += u64::from(details.reasoning_tokens);
+        }
+    }
+}
+
+/// Breakdown of the prompt tokens of a response
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(default)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct PromptTokensDetails {
+    /// Prompt tokens served from the prompt cache, which are billed at a discount
+    pub cached_tokens: u32,
+    /// Audio input tokens
+    pub audio_tokens: u32,
+}
+
+impl AddAssign for PromptTokensDetails {
+    fn add_assign(&mut self, rhs: Self) {
+        self.cached_tokens += rhs.cached_tokens;
+        self.audio_tokens += rhs.audio_tokens;
+    }
+}
+
+/// Rate limit state of the API key, reported in the `x-ratelimit-*` response headers.
+/// Values not reported by the backend are `None`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RateLimitInfo {
+    /// Maximum amount of requests allowed before the limit resets
+    pub limit_requests: Option<u64>,
+    /// Maximum amount of tokens allowed before the limit resets
+    pub limit_tokens: Option<u64>,
+    /// Requests remaining before the limit is exceeded
+    pub remaining_requests: Option<u64>,
+    /// Tokens remaining before the limit is exceeded
+    pub remaining_tokens: Option<u64>,
+    /// Time until the request limit resets
+    pub reset_requests: Option<Duration>,
+    /// Time until the token limit resets
+    pub reset_tokens: Option<Duration>,
+}
+
+/// Breakdown of the completion tokens of a response
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize,
+)]
+#[serde(default)]
+#[cfg_attr(feature = "strict", serde(deny_unknown_fields))]
+pub struct CompletionTokensDetails {
+    /// Tokens spent on the hidden thinking of reasoning models. They are billed, but not visible in the message
+    pub reasoning_tokens: u32,
+    /// Audio output tokens
+    pub audio_tokens: u32,
+    /// Tokens of the predicted output that appeared in the completion
+    pub accepted_prediction_tokens: u32,
+    /// Tokens of the predicted output that did not appear in the completion. They are still billed
+    pub rejected_prediction_tokens: u32,
+}
+
+impl AddAssign for CompletionTokensDetails {
+    fn add_assign(&mut self, rhs: Self) {
+        self.reasoning_tokens += rhs.reasoning_tokens;
+        self.audio_tokens += rhs.audio_tokens;
+        self.accepted_prediction_tokens += rhs.accepted_prediction_tokens;
+        self.rejected_prediction_tokens += rhs.rejected_prediction_tokens;
+    }
+}
+
+/// Content filtering results reported by Azure OpenAI, keyed by category (e.g. `hate`, `violence` or `jailbreak`)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+#[serde(transparent)]
+pub struct ContentFilterResults(pub BTreeMap<String, ContentFilterResult>);
+
+impl ContentFilterResults {
+    /// Returns `true` if the content was filtered in any category
+    pub fn is_filtered(&self) -> bool {
+        self.0.values().any(|result| result.filtered)
+    }
+}
+
+/// A content filtering result of a single category
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+pub struct ContentFilterResult {
+    /// Whether the content was filtered
+    #[serde(default)]
+    pub filtered: bool,
+    /// Severity of the content. Example: `safe`, `low`, `medium` or `high`
+    #[serde(default)]
+    pub severity: Option<String>,
+    /// Whether the content was detected, for categories without severity levels (e.g. `jailbreak`)
+    #[serde(default)]
+    pub detected: Option<bool>,
+}
+
+/// Content filtering results of a single prompt, reported by Azure OpenAI
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize)]
+pub struct PromptFilterResult {
+    /// Index of the prompt these results refer to
+    #[serde(default)]
+    pub prompt_index: usize,
+    /// The content filtering results
+    #[serde(default)]
+    pub content_filter_results: ContentFilterResults,
 }
 
 /// A single response chunk, returned from streamed request
-#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash)]
 #[cfg(feature = "streams")]
 pub enum ResponseChunk {
     /// A chunk of message content
@@ -186,6 +1094,54 @@ pub enum ResponseChunk {
         /// Index of the message. Used when `reply_count` is set to more than 1 in API config
         response_index: usize,
     },
+    /// A chunk of the thinking trace of reasoning models, streamed before the message content by some backends
+    ReasoningContent {
+        /// Piece of the thinking trace
+        delta: String,
+        /// Index of the message. Used when `reply_count` is set to more than 1 in API config
+        response_index: usize,
+    },
+    /// Content filtering results of the prompt, reported by Azure OpenAI before the response
+    PromptFilter {
+        /// Index of the prompt these results refer to
+        prompt_index: usize,
+        /// The content filtering results
+        results: ContentFilterResults,
+    },
+    /// Content filtering results of the response content streamed so far, reported by Azure OpenAI
+    ContentFilter {
+        /// The content filtering results
+        results: ContentFilterResults,
+        /// Index of the message. Used when `reply_count` is set to more than 1 in API config
+        response_index: usize,
+    },
+    /// Marks beginning of a tool call requested by the model
+    ToolCallBegin {
+        /// Identifier of the tool call. `None` for the deprecated function calls
+        id: Option<String>,
+        /// Name of the called function
+        name: String,
+        /// Index of the tool call in the message
+        call_index: usize,
+        /// Index of the message. Used when `reply_count` is set to more than 1 in API config
+        response_index: usize,
+    },
+    /// A chunk of stringified JSON arguments of a tool call
+    ToolCallArgumentsDelta {
+        /// Piece of the arguments
+        delta: String,
+        /// Index of the tool call in the message
+        call_index: usize,
+        /// Index of the message. Used when `reply_count` is set to more than 1 in API config
+        response_index: usize,
+    },
+    /// Ends a tool call, after all of its arguments were streamed
+    ToolCallEnd {
+        /// Index of the tool call in the message
+        call_index: usize,
+        /// Index of the message. Used when `reply_count` is set to more than 1 in API config
+        response_index: usize,
+    },
     /// Marks beginning of a new message response, with no actual content yet
     BeginResponse {
         /// The respondent's role (usually `Assistant`)
@@ -197,27 +1153,504 @@ pub enum ResponseChunk {
     CloseResponse {
         /// Index of the message finished. Used when `reply_count` is set to more than 1 in API config
         response_index: usize,
+        /// The reason the message was finished. `None` if the backend does not report it
+        finish_reason: Option<FinishReason>,
+    },
+    /// Marks that the response exceeded the configured [`StreamLengthLimit`](crate::config::StreamLengthLimit),
+    /// and the request was aborted. No chunks follow this one.
+    Truncated {
+        /// Index of the message that exceeded the limit. Used when `reply_count` is set to more than 1 in API config
+        response_index: usize,
     },
+    /// Token usage of the whole request, sent at the end of the stream when
+    /// [`ModelConfiguration::include_stream_usage`](crate::config::ModelConfiguration::include_stream_usage) is enabled
+    Usage(TokenUsage),
+    /// Rate limit state reported in the response headers, sent before any other chunk
+    RateLimit(RateLimitInfo),
+    /// Marks that the connection dropped mid-response and the request was re-sent with the content streamed so far,
+    /// when [`ModelConfiguration::stream_resume_attempts`](crate::config::ModelConfiguration::stream_resume_attempts)
+    /// is set. The following content chunks continue the interrupted response.
+    Resumed,
     /// Marks end of stream
     Done,
 }
 
+/// Assembles streamed response chunks into a [`CompletionResponse`], so the chunks can be rendered as they arrive
+/// while the final messages, finish reasons and usage are collected.
+///
+/// The message ID, creation timestamp and model are not streamed, so they stay empty
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[cfg(feature = "streams")]
+pub struct ResponseAccumulator {
+    response: CompletionResponse,
+}
+
+#[cfg(feature = "streams")]
+impl Default for ResponseAccumulator {
+    fn default() -> Self {
+        Self {
+            response: CompletionResponse {
+                message_id: None,
+                created_timestamp: None,
+                model: String::new(),
+                usage: TokenUsage::default(),
+                message_choices: Vec::new(),
+                object: None,
+                prompt_filter_results: Vec::new(),
+                system_fingerprint: None,
+                rate_limit: None,
+            },
+        }
+    }
+}
+
+#[cfg(feature = "streams")]
+impl ResponseAccumulator {
+    /// Consumes the whole stream and assembles the response. Fails with the first error of the stream
+    pub async fn collect<S: futures_util::Stream<Item = crate::Result<ResponseChunk>>>(
+        stream: S,
+    ) -> crate::Result<CompletionResponse> {
+        use futures_util::StreamExt;
+
+        let mut accumulator = Self::default();
+        let mut stream = std::pin::pin!(stream);
+        while let Some(chunk) = stream.next().await {
+            accumulator.push(&chunk?);
+        }
+        Ok(accumulator.finish())
+    }
+
+    /// Applies the chunk to the response assembled so far
+    pub fn push(&mut self, chunk: &ResponseChunk) {
+        match chunk {
+            ResponseChunk::Content {
+                delta,
+                response_index,
+            } => self.choice(*response_index).message.content.push_str(delta),
+            ResponseChunk::ReasoningContent {
+                delta,
+                response_index,
+            } => self
+                .choice(*response_index)
+                .message
+                .reasoning_content
+                .get_or_insert_with(String::new)
+                .push_str(delta),
+            ResponseChunk::PromptFilter {
+                prompt_index,
+                results,
+            } => self
+                .response
+                .prompt_filter_results
+                .push(PromptFilterResult {
+                    prompt_index: *prompt_index,
+                    content_filter_results: results.clone(),
+                }),
+            ResponseChunk::ContentFilter {
+                results,
+                response_index,
+            } => self.choice(*response_index).content_filter_results = Some(results.clone()),
+            #[cfg(feature = "functions")]
+            ResponseChunk::ToolCallBegin {
+                id,
+                name,
+                call_index: _,
+                response_index,
+            } => {
+                let message = &mut self.choice(*response_index).message;
+                let function = FunctionCall {
+                    name: name.clone(),
+                    arguments: String::new(),
+                };
+                match id {
+                    Some(id) => message.tool_calls.push(ToolCall {
+                        id: id.clone(),
+                        tool_type: "function".to_owned(),
+                        function,
+                    }),
+                    None => message.function_call = Some(function),
+                }
+            }
+            #[cfg(feature = "functions")]
+            ResponseChunk::ToolCallArgumentsDelta {
+                delta,
+                call_index,
+                response_index,
+            } => {
+                let message = &mut self.choice(*response_index).message;
+                let function = match message.function_call.as_mut() {
+                    Some(function) if message.tool_calls.is_empty() => Some(function),
+                    _ => message
+                        .tool_calls
+                        .get_mut(*call_index)
+                        .map(|call| &mut call.function),
+                };
+                if let Some(function) = function {
+                    function.arguments.push_str(delta);
+                }
+            }
+            ResponseChunk::BeginResponse {
+                role,
+                response_index,
+            } => self.choice(*response_index).message.role = *role,
+            // duplicate close chunks without a reason must not erase the reason reported before
+            ResponseChunk::CloseResponse {
+                response_index,
+                finish_reason: Some(finish_reason),
+            } => self.choice(*response_index).finish_reason = Some(finish_reason.clone()),
+            ResponseChunk::Truncated { response_index } => {
+                self.choice(*response_index).finish_reason = Some(FinishReason::Length)
+            }
+            ResponseChunk::Usage(usage) => self.response.usage = usage.clone(),
+            ResponseChunk::RateLimit(info) => self.response.rate_limit = Some(*info),
+            _ => {}
+        }
+    }
+
+    /// The response assembled so far
+    pub fn response(&self) -> &CompletionResponse {
+        &self.response
+    }
+
+    /// Finishes the assembled response
+    pub fn finish(self) -> CompletionResponse {
+        self.response
+    }
+
+    /// The choice with the index, created along with the preceding ones if it has not been streamed yet
+    fn choice(&mut self, index: usize) -> &mut MessageChoice {
+        let choices = &mut self.response.message_choices;
+        while choices.len() <= index {
+            choices.push(MessageChoice {
+                message: ChatMessage::new(Role::Assistant, String::new()),
+                finish_reason: None,
+                index: choices.len() as u32,
+                content_filter_results: None,
+                logprobs: None,
+            });
+        }
+        &mut choices[index]
+    }
+}
+
+#[cfg(feature = "streams")]
+impl ResponseChunk {
+    /// Index of the message this chunk refers to. `None` for chunks of the whole response, e.g. [`Self::Usage`]
+    pub fn response_index(&self) -> Option<usize> {
+        match self {
+            Self::Content { response_index, .. }
+            | Self::ReasoningContent { response_index, .. }
+            | Self::ContentFilter { response_index, .. }
+            | Self::ToolCallBegin { response_index, .. }
+            | Self::ToolCallArgumentsDelta { response_index, .. }
+            | Self::ToolCallEnd { response_index, .. }
+            | Self::BeginResponse { response_index, .. }
+            | Self::CloseResponse { response_index, .. }
+            | Self::Truncated { response_index } => Some(*response_index),
+            Self::PromptFilter { .. }
+            | Self::Usage(_)
+            | Self::RateLimit(_)
+            | Self::Resumed
+            | Self::Done => None,
+        }
+    }
+}
+
+/// The chunks of a single message choice, split from a stream requested with `reply_count` above 1
+/// by [`ChoiceStream::split()`].
+///
+/// Chunks of the whole response, e.g. [`ResponseChunk::Usage`], are passed to every choice. An error of the
+/// underlying stream is only passed to the choice that was polled when it occurred.
+#[cfg(feature = "streams")]
+pub struct ChoiceStream<S> {
+    index: usize,
+    shared: Arc<Mutex<SharedChoices<S>>>,
+    wakers: Arc<ChoiceWakers>,
+}
+
+#[cfg(feature = "streams")]
+struct SharedChoices<S> {
+    inner: S,
+    /// Chunks received for each choice but not polled yet. `None` once the choice stream is dropped
+    queues: Vec<Option<VecDeque<ResponseChunk>>>,
+    finished: bool,
+}
+
+/// Wakes all choice streams waiting for the underlying stream, as any of them may receive its next chunk
+#[cfg(feature = "streams")]
+struct ChoiceWakers(Mutex<Vec<Option<Waker>>>);
+
+#[cfg(feature = "streams")]
+impl Wake for ChoiceWakers {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut wakers = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        for waker in wakers.iter_mut().filter_map(Option::take) {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(feature = "streams")]
+impl<S: futures_util::Stream<Item = crate::Result<ResponseChunk>> + Unpin> ChoiceStream<S> {
+    /// Splits the stream into one stream per message choice, e.g. to render multiple candidates concurrently.
+    /// Chunks of choices with an index of `count` or above are dropped.
+    pub fn split(stream: S, count: usize) -> Vec<Self> {
+        let shared = Arc::new(Mutex::new(SharedChoices {
+            inner: stream,
+            queues: vec![Some(VecDeque::new()); count],
+            finished: false,
+        }));
+        let wakers = Arc::new(ChoiceWakers(Mutex::new(vec![None; count])));
+        (0..count)
+            .map(|index| Self {
+                index,
+                shared: shared.clone(),
+                wakers: wakers.clone(),
+            })
+            .collect()
+    }
+
+    /// Index of the message choice of this stream
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+#[cfg(feature = "streams")]
+impl<S: futures_util::Stream<Item = crate::Result<ResponseChunk>> + Unpin> futures_util::Stream
+    for ChoiceStream<S>
+{
+    type Item = crate::Result<ResponseChunk>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        use futures_util::StreamExt;
+
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            let queue = shared.queues[self.index].as_mut();
+            if let Some(chunk) = queue.and_then(VecDeque::pop_front) {
+                return Poll::Ready(Some(Ok(chunk)));
+            }
+            if shared.finished {
+                return Poll::Ready(None);
+            }
+            self.wakers.0.lock().unwrap_or_else(PoisonError::into_inner)[self.index] =
+                Some(cx.waker().clone());
+            let waker = Waker::from(self.wakers.clone());
+            let chunk = match shared
+                .inner
+                .poll_next_unpin(&mut Context::from_waker(&waker))
+            {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => {
+                    shared.finished = true;
+                    self.wakers.wake_by_ref();
+                    continue;
+                }
+                Poll::Ready(Some(Err(err))) => return Poll::Ready(Some(Err(err))),
+                Poll::Ready(Some(Ok(chunk))) => chunk,
+            };
+            match chunk.response_index() {
+                Some(index) => {
+                    if let Some(Some(queue)) = shared.queues.get_mut(index) {
+                        queue.push_back(chunk);
+                    }
+                }
+                None => {
+                    for queue in shared.queues.iter_mut().flatten() {
+                        queue.push_back(chunk.clone());
+                    }
+                }
+            }
+            self.wakers.wake_by_ref();
+        }
+    }
+}
+
+#[cfg(feature = "streams")]
+impl<S> Drop for ChoiceStream<S> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        shared.queues[self.index] = None;
+    }
+}
+
+/// Where [`CoalescedStream`] splits the buffered content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg(feature = "streams")]
+pub enum CoalesceBoundary {
+    /// After whitespace, so only whole words are emitted
+    Word,
+    /// After whitespace following `.`, `!` or `?`, or after a line break, e.g. for text-to-speech
+    Sentence,
+    /// Once at least the amount of bytes is buffered
+    Bytes(usize),
+}
+
+#[cfg(feature = "streams")]
+impl CoalesceBoundary {
+    /// Length of the longest prefix of the buffer that ends at this boundary
+    fn split_point(self, buffer: &str) -> Option<usize> {
+        match self {
+            Self::Word => buffer
+                .char_indices()
+                .rfind(|(_, c)| c.is_whitespace())
+                .map(|(index, c)| index + c.len_utf8()),
+            Self::Sentence => {
+                let mut split = None;
+                let mut previous = None;
+                for (index, c) in buffer.char_indices() {
+                    let after_sentence = matches!(previous, Some('.' | '!' | '?'));
+                    if c == '\n' || (c.is_whitespace() && after_sentence) {
+                        split = Some(index + c.len_utf8());
+                    }
+                    previous = Some(c);
+                }
+                split
+            }
+            Self::Bytes(size) => {
+                (!buffer.is_empty() && buffer.len() >= size).then_some(buffer.len())
+            }
+        }
+    }
+}
+
+/// Buffers the content deltas of a stream and emits them at word, sentence or size boundaries,
+/// e.g. for text-to-speech or to avoid flicker of terminals updated on every token.
+///
+/// Other chunks are passed through in order, after the content buffered before them. The rest of the content
+/// is emitted once the stream ends.
+#[cfg(feature = "streams")]
+pub struct CoalescedStream<S> {
+    inner: S,
+    boundary: CoalesceBoundary,
+    /// Content buffered for each message choice
+    buffers: Vec<String>,
+    output: VecDeque<crate::Result<ResponseChunk>>,
+    finished: bool,
+}
+
+#[cfg(feature = "streams")]
+impl<S: futures_util::Stream<Item = crate::Result<ResponseChunk>> + Unpin> CoalescedStream<S> {
+    /// Coalesces the content of the stream at the boundary
+    pub fn new(stream: S, boundary: CoalesceBoundary) -> Self {
+        Self {
+            inner: stream,
+            boundary,
+            buffers: Vec::new(),
+            output: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    fn flush(&mut self, response_index: usize) {
+        if let Some(buffer) = self.buffers.get_mut(response_index) {
+            if !buffer.is_empty() {
+                self.output.push_back(Ok(ResponseChunk::Content {
+                    delta: std::mem::take(buffer),
+                    response_index,
+                }));
+            }
+        }
+    }
+
+    fn flush_all(&mut self) {
+        for response_index in 0..self.buffers.len() {
+            self.flush(response_index);
+        }
+    }
+}
+
+#[cfg(feature = "streams")]
+impl<S: futures_util::Stream<Item = crate::Result<ResponseChunk>> + Unpin> futures_util::Stream
+    for CoalescedStream<S>
+{
+    type Item = crate::Result<ResponseChunk>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        use futures_util::StreamExt;
+
+        let this = &mut *self;
+        loop {
+            if let Some(item) = this.output.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+            if this.finished {
+                return Poll::Ready(None);
+            }
+            match this.inner.poll_next_unpin(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(None) => {
+                    this.finished = true;
+                    this.flush_all();
+                }
+                Poll::Ready(Some(Ok(ResponseChunk::Content {
+                    delta,
+                    response_index,
+                }))) => {
+                    if this.buffers.len() <= response_index {
+                        this.buffers.resize(response_index + 1, String::new());
+                    }
+                    let buffer = &mut this.buffers[response_index];
+                    buffer.push_str(&delta);
+                    if let Some(split) = this.boundary.split_point(buffer) {
+                        let rest = buffer.split_off(split);
+                        this.output.push_back(Ok(ResponseChunk::Content {
+                            delta: std::mem::replace(buffer, rest),
+                            response_index,
+                        }));
+                    }
+                }
+                Poll::Ready(Some(item)) => {
+                    match item.as_ref().ok().and_then(ResponseChunk::response_index) {
+                        Some(response_index) => this.flush(response_index),
+                        None => this.flush_all(),
+                    }
+                    this.output.push_back(item);
+                }
+            }
+        }
+    }
+}
+
 /// A part of a chunked inbound response
 #[derive(Debug, Clone, Deserialize)]
 #[cfg(feature = "streams")]
 pub struct InboundResponseChunk {
-    /// All message chunks in this response part (only one usually)
+    /// All message chunks in this response part (only one usually). Azure OpenAI sends chunks without any choices
+    #[serde(default)]
     pub choices: Vec<InboundChunkChoice>,
+    /// Content filtering results of the prompt, reported by Azure OpenAI
+    #[serde(default, alias = "prompt_annotations")]
+    pub prompt_filter_results: Vec<PromptFilterResult>,
+    /// Token usage of the whole request, only present in the final chunk if requested with `stream_options`
+    #[serde(default)]
+    pub usage: Option<TokenUsage>,
+    /// An error that interrupted the response, sent mid-stream instead of a chunk
+    #[serde(default)]
+    pub error: Option<CompletionError>,
 }
 
 /// A single message part of a chunked inbound response
 #[derive(Debug, Clone, Deserialize)]
 #[cfg(feature = "streams")]
 pub struct InboundChunkChoice {
-    /// The part value of the response
-    pub delta: InboundChunkPayload,
+    /// The part value of the response. Absent in chunks carrying only content filtering results
+    #[serde(default)]
+    pub delta: Option<InboundChunkPayload>,
     /// Index of the message this chunk refers to
+    #[serde(default)]
     pub index: usize,
+    /// Content filtering results of the content streamed so far, reported by Azure OpenAI
+    #[serde(default)]
+    pub content_filter_results: Option<ContentFilterResults>,
+    /// The reason the message was finished, sent with its last chunk
+    #[serde(default)]
+    pub finish_reason: Option<FinishReason>,
 }
 
 /// Contains different chunked inbound response payloads
@@ -229,12 +1662,524 @@ pub enum InboundChunkPayload {
     AnnounceRoles {
         /// The announced role
         role: Role,
+        /// The first part of content, sent alongside the role by some backends
+        #[serde(default)]
+        content: Option<String>,
+        /// The first part of the thinking trace, sent alongside the role by some backends
+        #[serde(default)]
+        reasoning_content: Option<String>,
+        /// The first parts of tool calls, sent alongside the role
+        #[serde(default, deserialize_with = "deserialize_maybe_null_vec")]
+        tool_calls: Vec<InboundToolCallDelta>,
+        /// The first part of a deprecated function call, sent alongside the role
+        #[serde(default)]
+        function_call: Option<InboundFunctionCallDelta>,
+    },
+    /// Streams parts of tool calls
+    StreamToolCalls {
+        /// The parts of tool calls
+        tool_calls: Vec<InboundToolCallDelta>,
+    },
+    /// Streams a part of a deprecated function call
+    StreamFunctionCall {
+        /// The part of the function call
+        function_call: InboundFunctionCallDelta,
+    },
+    /// Streams a part of the thinking trace of reasoning models
+    StreamReasoning {
+        /// The part of the thinking trace
+        reasoning_content: String,
+        /// The part of content, usually `null` while reasoning
+        #[serde(default)]
+        content: Option<String>,
     },
     /// Streams a part of message content
     StreamContent {
         /// The part of content
+        #[serde(deserialize_with = "deserialize_maybe_null")]
         content: String,
     },
     /// Closes a single message
     Close {},
 }
+
+/// A part of a tool call in a chunked inbound response
+#[derive(Debug, Clone, Deserialize)]
+#[cfg(feature = "streams")]
+pub struct InboundToolCallDelta {
+    /// Index of the tool call in the message
+    #[serde(default)]
+    pub index: usize,
+    /// Identifier of the tool call, only sent in its first part
+    #[serde(default)]
+    pub id: Option<String>,
+    /// The part of the called function
+    #[serde(default)]
+    pub function: InboundFunctionCallDelta,
+}
+
+/// A part of a function call in a chunked inbound response
+#[derive(Debug, Clone, Default, Deserialize)]
+#[cfg(feature = "streams")]
+pub struct InboundFunctionCallDelta {
+    /// Name of the called function, only sent in the first part of the call
+    #[serde(default)]
+    pub name: Option<String>,
+    /// A part of the stringified JSON arguments
+    #[serde(default)]
+    pub arguments: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::config::{ChatGPTEngine, ModelConfiguration, ReasoningEffort, ResponseFormat};
+    use crate::types::{
+        AudioFormat, AudioPart, ChatMessage, CompletionRequest, CompletionResponse, FinishReason,
+        ImagePart, LogitBias, Role, TokenUsage, WireMessage,
+    };
+
+    #[test]
+    fn test_wire_message_serialization() {
+        let plain = ChatMessage::new(Role::User, "Hello");
+        let multimodal = ChatMessage::new(Role::User, "What is in this image?")
+            .with_content_part(ImagePart::from_url("https://example.com/image.png"));
+        let request = CompletionRequest::builder()
+            .model("gpt-4o")
+            .messages(vec![plain, multimodal])
+            .build()
+            .unwrap();
+        let serialized = serde_json::to_value(request).unwrap();
+        assert_eq!(
+            serialized["messages"],
+            json!([
+                { "role": "user", "content": "Hello" },
+                {
+                    "role": "user",
+                    "content": [
+                        { "type": "text", "text": "What is in this image?" },
+                        { "type": "image_url", "image_url": { "url": "https://example.com/image.png" } }
+                    ]
+                }
+            ])
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "functions")]
+    fn test_tool_message_serialization() {
+        use crate::functions::{FunctionCall, ToolCall};
+
+        let call = ChatMessage {
+            tool_calls: vec![ToolCall {
+                id: "call_1".to_owned(),
+                tool_type: "function".to_owned(),
+                function: FunctionCall {
+                    name: "get_weather".to_owned(),
+                    arguments: "{}".to_owned(),
+                },
+            }],
+            ..ChatMessage::new(Role::Assistant, "")
+        };
+        let result = ChatMessage::tool_result("call_1", "Sunny");
+        let request = CompletionRequest::builder()
+            .messages(vec![call, result])
+            .build()
+            .unwrap();
+        let serialized = serde_json::to_value(request).unwrap();
+        assert_eq!(serialized["messages"][0]["tool_calls"][0]["id"], "call_1");
+        assert_eq!(
+            serialized["messages"][1],
+            json!({ "role": "tool", "content": "Sunny", "tool_call_id": "call_1" })
+        );
+        assert!(serialized.get("tools").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "functions")]
+    fn test_result_message_constructors() -> crate::Result<()> {
+        let tool = ChatMessage::tool_result_json("call_1", &json!({ "temperature": 20 }))?;
+        assert_eq!(
+            serde_json::to_value(WireMessage::from(&tool))?,
+            json!({ "role": "tool", "content": "{\"temperature\":20}", "tool_call_id": "call_1" })
+        );
+
+        let function = ChatMessage::function_result("get_weather", "Sunny")?;
+        assert_eq!(
+            serde_json::to_value(WireMessage::from(&function))?,
+            json!({ "role": "function", "content": "\"Sunny\"", "name": "get_weather" })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_audio_part_serialization() {
+        let message = ChatMessage::new(Role::User, "Transcribe this")
+            .with_content_part(AudioPart::from_bytes(b"RIFF", AudioFormat::Wav));
+        assert_eq!(
+            serde_json::to_value(WireMessage::from(&message)).unwrap()["content"][1],
+            json!({ "type": "input_audio", "input_audio": { "data": "UklGRg==", "format": "wav" } })
+        );
+    }
+
+    #[test]
+    fn test_response_format_serialization() {
+        let history = [ChatMessage::new(Role::User, "Reply in JSON")];
+        let config = ModelConfiguration::default();
+        let mut request = CompletionRequest::from_config(&config, &history[..]);
+        assert!(serde_json::to_value(&request)
+            .unwrap()
+            .get("response_format")
+            .is_none());
+
+        request.response_format = Some(ResponseFormat::JsonObject);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["response_format"],
+            json!({ "type": "json_object" })
+        );
+    }
+
+    #[test]
+    fn test_stop_serialization() {
+        let history = [ChatMessage::new(Role::User, "Count to ten")];
+        let config = ModelConfiguration {
+            stop: vec!["5".to_owned()],
+            ..Default::default()
+        };
+        let request = CompletionRequest::from_config(&config, &history[..]);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["stop"],
+            json!(["5"])
+        );
+
+        let config = ModelConfiguration::default();
+        let request = CompletionRequest::from_config(&config, &history[..]);
+        assert!(serde_json::to_value(&request)
+            .unwrap()
+            .get("stop")
+            .is_none());
+    }
+
+    #[test]
+    fn test_logit_bias_serialization() {
+        let history = [ChatMessage::new(Role::User, "Say hello")];
+        let config = ModelConfiguration {
+            logit_bias: LogitBias::new().with_bias(15339, -100.0),
+            ..Default::default()
+        };
+        let request = CompletionRequest::from_config(&config, &history[..]);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["logit_bias"],
+            json!({ "15339": -100.0 })
+        );
+    }
+
+    #[test]
+    fn test_seed_serialization() {
+        let request = CompletionRequest::builder().seed(42).build().unwrap();
+        assert_eq!(serde_json::to_value(&request).unwrap()["seed"], json!(42));
+        let request = CompletionRequest::default();
+        assert!(serde_json::to_value(&request)
+            .unwrap()
+            .get("seed")
+            .is_none());
+    }
+
+    #[test]
+    fn test_max_completion_tokens() {
+        let history = [ChatMessage::new(Role::User, "Hi")];
+        let mut config = ModelConfiguration {
+            max_tokens: Some(100),
+            ..Default::default()
+        };
+        let request =
+            serde_json::to_value(CompletionRequest::from_config(&config, &history[..])).unwrap();
+        assert_eq!(request["max_tokens"], json!(100));
+        assert!(request.get("max_completion_tokens").is_none());
+
+        config.engine = ChatGPTEngine::Custom("o3-mini");
+        let request =
+            serde_json::to_value(CompletionRequest::from_config(&config, &history[..])).unwrap();
+        assert_eq!(request["max_completion_tokens"], json!(100));
+        assert!(request.get("max_tokens").is_none());
+    }
+
+    #[test]
+    fn test_reasoning_effort_serialization() {
+        let request = CompletionRequest::builder()
+            .reasoning_effort(ReasoningEffort::High)
+            .build()
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["reasoning_effort"],
+            json!("high")
+        );
+    }
+
+    #[test]
+    fn test_messages_as_map_keys() {
+        let messages = std::collections::HashSet::from([
+            ChatMessage::new(Role::User, "Hello"),
+            ChatMessage::new(Role::User, "Hello"),
+            ChatMessage::default(),
+        ]);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(ChatMessage::default().role, Role::User);
+    }
+
+    #[test]
+    fn test_minimal_completion_response() {
+        // llama.cpp and other local backends omit some of the fields
+        let response: CompletionResponse = serde_json::from_value(json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": "Hi!" },
+                "finish_reason": null
+            }]
+        }))
+        .unwrap();
+        assert_eq!(response.message().content, "Hi!");
+        assert_eq!(response.usage.total_tokens, 0);
+        assert_eq!(response.message_choices[0].finish_reason, None);
+    }
+
+    #[test]
+    fn test_finish_reason() {
+        let reason = |value| serde_json::from_value::<FinishReason>(json!(value)).unwrap();
+        assert_eq!(reason("length"), FinishReason::Length);
+        assert_eq!(reason("tool_calls"), FinishReason::ToolCalls);
+        assert_eq!(reason("eos"), FinishReason::Other("eos".to_owned()));
+        assert_eq!(reason("content_filter").to_string(), "content_filter");
+    }
+
+    #[test]
+    fn test_logprobs_response() {
+        let response: CompletionResponse = serde_json::from_value(json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": "Hi" },
+                "finish_reason": "stop",
+                "logprobs": { "content": [{
+                    "token": "Hi", "logprob": -0.25, "bytes": [72, 105],
+                    "top_logprobs": [{ "token": "Hello", "logprob": -1.5, "bytes": null }]
+                }] }
+            }]
+        }))
+        .unwrap();
+        let logprobs = response.message_choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.content[0].bytes.as_deref(), Some(&b"Hi"[..]));
+        assert_eq!(logprobs.content[0].top_logprobs[0].token, "Hello");
+    }
+
+    #[test]
+    fn test_usage_details() {
+        let usage = |cached, reasoning| {
+            serde_json::from_value::<TokenUsage>(json!({
+                "prompt_tokens": 100, "completion_tokens": 50, "total_tokens": 150,
+                "prompt_tokens_details": { "cached_tokens": cached },
+                "completion_tokens_details": { "reasoning_tokens": reasoning }
+            }))
+            .unwrap()
+        };
+        let mut total = usage(80, 30);
+        assert_eq!(total.prompt_tokens_details.unwrap().cached_tokens, 80);
+        total += usage(20, 10);
+        assert_eq!(total.total_tokens, 300);
+        assert_eq!(
+            total.completion_tokens_details.unwrap().reasoning_tokens,
+            40
+        );
+    }
+
+    #[test]
+    fn test_unknown_response_fields() {
+        let response = serde_json::from_value::<CompletionResponse>(json!({
+            "choices": [{
+                "message": { "role": "assistant", "content": "Hi", "refusal": null },
+                "logprobs": null
+            }],
+            "service_tier": "default"
+        }));
+        assert_eq!(response.is_ok(), !cfg!(feature = "strict"));
+    }
+
+    #[test]
+    #[cfg(feature = "streams")]
+    fn test_role_and_content_delta() {
+        use crate::types::InboundChunkPayload;
+
+        let payload: InboundChunkPayload =
+            serde_json::from_value(json!({ "role": "assistant", "content": "Hello" })).unwrap();
+        assert!(matches!(
+            payload,
+            InboundChunkPayload::AnnounceRoles { role: Role::Assistant, content: Some(content), .. } if content == "Hello"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "streams")]
+    fn test_reasoning_deltas() {
+        use crate::types::{InboundChunkPayload, ResponseChunk};
+
+        let parse = |value| serde_json::from_value::<InboundChunkPayload>(value).unwrap();
+        assert!(matches!(
+            parse(json!({ "content": null, "reasoning_content": "Hmm" })),
+            InboundChunkPayload::StreamReasoning { reasoning_content, content: None } if reasoning_content == "Hmm"
+        ));
+        assert!(matches!(
+            parse(json!({ "content": "Hi", "reasoning_content": null })),
+            InboundChunkPayload::StreamContent { content } if content == "Hi"
+        ));
+        assert!(matches!(parse(json!({})), InboundChunkPayload::Close {}));
+
+        let messages = ChatMessage::from_response_chunks(vec![
+            ResponseChunk::BeginResponse {
+                role: Role::Assistant,
+                response_index: 0,
+            },
+            ResponseChunk::ReasoningContent {
+                delta: "Hmm".to_string(),
+                response_index: 0,
+            },
+            ResponseChunk::Content {
+                delta: "Hi".to_string(),
+                response_index: 0,
+            },
+        ]);
+        assert_eq!(messages[0].reasoning_content.as_deref(), Some("Hmm"));
+        assert_eq!(messages[0].content, "Hi");
+    }
+
+    #[cfg(feature = "streams")]
+    #[tokio::test]
+    async fn test_choice_stream() {
+        use futures_util::StreamExt;
+
+        use crate::types::{ChoiceStream, ResponseChunk};
+
+        let content = |delta: &str, response_index| ResponseChunk::Content {
+            delta: delta.to_string(),
+            response_index,
+        };
+        let chunks = [
+            content("A", 0),
+            content("B", 1),
+            content("C", 2),
+            content("A", 0),
+            ResponseChunk::Done,
+        ];
+        let mut choices = ChoiceStream::split(futures_util::stream::iter(chunks.map(Ok)), 2);
+        let second = choices.pop().unwrap().map(Result::unwrap);
+        let first = choices.pop().unwrap().map(Result::unwrap);
+        let (first, second) = tokio::join!(first.collect::<Vec<_>>(), second.collect::<Vec<_>>());
+        assert_eq!(
+            first,
+            [content("A", 0), content("A", 0), ResponseChunk::Done]
+        );
+        assert_eq!(second, [content("B", 1), ResponseChunk::Done]);
+    }
+
+    #[cfg(feature = "streams")]
+    #[tokio::test]
+    async fn test_coalesced_stream() {
+        use futures_util::StreamExt;
+
+        use crate::types::{CoalesceBoundary, CoalescedStream, ResponseChunk};
+
+        let content = |delta: &str| ResponseChunk::Content {
+            delta: delta.to_string(),
+            response_index: 0,
+        };
+        let coalesce = |boundary| {
+            let chunks =
+                ["Hel", "lo wor", "ld. How", " are", " you?"].map(|delta| Ok(content(delta)));
+            let chunks = futures_util::stream::iter(chunks)
+                .chain(futures_util::stream::iter([Ok(ResponseChunk::Done)]));
+            CoalescedStream::new(chunks, boundary)
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            coalesce(CoalesceBoundary::Word).await,
+            [
+                content("Hello "),
+                content("world. "),
+                content("How "),
+                content("are "),
+                content("you?"),
+                ResponseChunk::Done
+            ]
+        );
+        assert_eq!(
+            coalesce(CoalesceBoundary::Sentence).await,
+            [
+                content("Hello world. "),
+                content("How are you?"),
+                ResponseChunk::Done
+            ]
+        );
+        assert_eq!(
+            coalesce(CoalesceBoundary::Bytes(8)).await,
+            [
+                content("Hello wor"),
+                content("ld. How are"),
+                content(" you?"),
+                ResponseChunk::Done
+            ]
+        );
+    }
+
+    #[cfg(feature = "streams")]
+    #[tokio::test]
+    async fn test_response_accumulator() -> crate::Result<()> {
+        use crate::types::{ResponseAccumulator, ResponseChunk};
+
+        let chunks = [
+            ResponseChunk::BeginResponse {
+                role: Role::Assistant,
+                response_index: 0,
+            },
+            ResponseChunk::Content {
+                delta: "Hel".to_string(),
+                response_index: 0,
+            },
+            ResponseChunk::Content {
+                delta: "lo".to_string(),
+                response_index: 0,
+            },
+            ResponseChunk::Content {
+                delta: "Hi".to_string(),
+                response_index: 1,
+            },
+            ResponseChunk::CloseResponse {
+                response_index: 0,
+                finish_reason: Some(FinishReason::Stop),
+            },
+            // some backends close the message again without a reason
+            ResponseChunk::CloseResponse {
+                response_index: 0,
+                finish_reason: None,
+            },
+            ResponseChunk::Truncated { response_index: 1 },
+            ResponseChunk::Usage(TokenUsage {
+                total_tokens: 12,
+                ..Default::default()
+            }),
+            ResponseChunk::Done,
+        ];
+        let response =
+            ResponseAccumulator::collect(futures_util::stream::iter(chunks.map(Ok))).await?;
+        assert_eq!(response.message().content, "Hello");
+        assert_eq!(
+            response.message_choices[0].finish_reason,
+            Some(FinishReason::Stop)
+        );
+        assert_eq!(response.message_choices[1].message.content, "Hi");
+        assert_eq!(response.message_choices[1].index, 1);
+        assert_eq!(
+            response.message_choices[1].finish_reason,
+            Some(FinishReason::Length)
+        );
+        assert_eq!(response.usage.total_tokens, 12);
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::Path;
+
+use bytes::Bytes;
+use reqwest::multipart::Part;
+use reqwest::Body;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::AsyncRead;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio_util::io::ReaderStream;
+
+/// A file sent to the API as a part of a multipart request.
+///
+/// Files opened with [`Self::from_path()`] or [`Self::from_reader()`] are streamed as the request is sent,
+/// so large files (e.g. long audio recordings) are never loaded into memory whole. They are not available on WASM,
+/// where files can only be uploaded from memory.
+#[derive(Debug)]
+pub struct FileUpload {
+    file_name: String,
+    mime_type: Option<String>,
+    length: Option<u64>,
+    body: Body,
+}
+
+impl FileUpload {
+    /// Opens a local file for streaming upload. The MIME type is guessed from the file extension.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_path<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let file = tokio::fs::File::open(path).await?;
+        let length = file.metadata().await?.len();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "file".to_string());
+        Ok(Self {
+            mime_type: mime_from_path(path).map(str::to_owned),
+            file_name,
+            length: Some(length),
+            body: Body::wrap_stream(ReaderStream::new(file)),
+        })
+    }
+
+    /// Streams the file contents from an arbitrary async reader.
+    ///
+    /// The file name is sent to the API, which often uses its extension to determine the file format.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_reader<R, S>(reader: R, file_name: S) -> Self
+    where
+        R: AsyncRead + Send + Sync + 'static,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        Self {
+            mime_type: mime_from_path(file_name.as_ref()).map(str::to_owned),
+            file_name,
+            length: None,
+            body: Body::wrap_stream(ReaderStream::new(reader)),
+        }
+    }
+
+    /// Uploads the file contents from memory
+    pub fn from_bytes<B, S>(bytes: B, file_name: S) -> Self
+    where
+        B: Into<Bytes>,
+        S: Into<String>,
+    {
+        let file_name = file_name.into();
+        let bytes = bytes.into();
+        Self {
+            mime_type: mime_from_path(file_name.as_ref()).map(str::to_owned),
+            file_name,
+            length: Some(bytes.len() as u64),
+            body: Body::from(bytes),
+        }
+    }
+
+    /// Overrides the MIME type of this file
+    pub fn with_mime_type<S: Into<String>>(mut self, mime_type: S) -> Self {
+        self.mime_type = Some(mime_type.into());
+        self
+    }
+
+    /// Sets the length of the file in bytes, if it is known in advance.
+    ///
+    /// Files streamed with an unknown length are sent with chunked transfer encoding.
+    pub fn with_length(mut self, length: u64) -> Self {
+        self.length = Some(length);
+        self
+    }
+
+    /// Name of the file sent to the API
+    pub fn file_name(&self) -> &str {
+        &self.file_name
+    }
+
+    /// Converts this file into a multipart form part, e.g. to send it to endpoints that are not covered by this crate
+    pub fn into_part(self) -> crate::Result<Part> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let part = match self.length {
+            Some(length) => Part::stream_with_length(self.body, length),
+            None => Part::stream(self.body),
+        };
+        // the length of the in-memory body is known to the `fetch` API
+        #[cfg(target_arch = "wasm32")]
+        let part = Part::stream(self.body);
+        let part = part.file_name(self.file_name);
+        Ok(match self.mime_type {
+            Some(mime_type) => part.mime_str(&mime_type)?,
+            None => part,
+        })
+    }
+}
+
+/// Guesses the MIME type of common file formats accepted by the API from the file extension
+pub(crate) fn mime_from_path(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "mp3" | "mpga" | "mpeg" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        "wav" => "audio/wav",
+        "webm" => "audio/webm",
+        "ogg" | "oga" => "audio/ogg",
+        "flac" => "audio/flac",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "json" => "application/json",
+        "jsonl" => "application/jsonl",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        "md" => "text/markdown",
+        "csv" => "text/csv",
+        _ => return None,
+    })
+}
@@ -0,0 +1,372 @@
+use std::time::Duration;
+
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{ChatGPT, Deletion, List};
+use crate::files::FilePurpose;
+use crate::upload::FileUpload;
+
+/// A request creating a vector store
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Serialize)]
+pub struct VectorStoreRequest {
+    /// Name of the vector store
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Ids of uploaded files added to the vector store when it is created
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub file_ids: Vec<String>,
+    /// Expiration of the vector store after its last use
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_after: Option<ExpiresAfter>,
+}
+
+impl VectorStoreRequest {
+    /// Sets the name of the vector store
+    pub fn with_name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Adds an uploaded file to the vector store
+    pub fn with_file<S: Into<String>>(mut self, file_id: S) -> Self {
+        self.file_ids.push(file_id.into());
+        self
+    }
+
+    /// Expires the vector store after it was not used for the provided amount of days
+    pub fn with_expiration_days(mut self, days: u32) -> Self {
+        self.expires_after = Some(ExpiresAfter {
+            anchor: "last_active_at".to_owned(),
+            days,
+        });
+        self
+    }
+}
+
+/// Expiration of a vector store
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+pub struct ExpiresAfter {
+    /// The timestamp the expiration is counted from, currently always `last_active_at`
+    pub anchor: String,
+    /// Amount of days until the expiration
+    pub days: u32,
+}
+
+/// A vector store, searched by the file search tools
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct VectorStore {
+    /// Identifier of the vector store
+    pub id: String,
+    /// Unix timestamp of the creation of the vector store
+    #[serde(default)]
+    pub created_at: u64,
+    /// Name of the vector store
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Status of the vector store
+    pub status: VectorStoreStatus,
+    /// Amount of files of the vector store by their status
+    #[serde(default)]
+    pub file_counts: FileCounts,
+    /// Storage used by the vector store in bytes
+    #[serde(default)]
+    pub usage_bytes: u64,
+}
+
+/// The status of a vector store
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorStoreStatus {
+    /// Files are being ingested
+    InProgress,
+    /// All the files were processed
+    Completed,
+    /// The vector store expired, and can no longer be used
+    Expired,
+}
+
+/// Amount of files of a vector store by their status
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+#[serde(default)]
+pub struct FileCounts {
+    /// Files being ingested
+    pub in_progress: u32,
+    /// Files ready to be searched
+    pub completed: u32,
+    /// Files that could not be ingested
+    pub failed: u32,
+    /// Files whose ingestion was cancelled
+    pub cancelled: u32,
+    /// Total amount of files
+    pub total: u32,
+}
+
+/// A file attached to a vector store
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct VectorStoreFile {
+    /// Identifier of the file, the same as the id of the uploaded file
+    pub id: String,
+    /// Unix timestamp of the attachment of the file
+    #[serde(default)]
+    pub created_at: u64,
+    /// Identifier of the vector store
+    pub vector_store_id: String,
+    /// Ingestion status of the file
+    pub status: VectorStoreFileStatus,
+    /// The reason of the failure, if the ingestion failed
+    #[serde(default)]
+    pub last_error: Option<VectorStoreFileError>,
+    /// Storage used by the file in the vector store in bytes
+    #[serde(default)]
+    pub usage_bytes: u64,
+}
+
+/// The ingestion status of a vector store file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VectorStoreFileStatus {
+    /// The file is being ingested
+    InProgress,
+    /// The file is ready to be searched
+    Completed,
+    /// The ingestion was cancelled
+    Cancelled,
+    /// The ingestion failed, see [`VectorStoreFile::last_error`]
+    Failed,
+}
+
+/// The reason the ingestion of a vector store file failed
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Deserialize)]
+pub struct VectorStoreFileError {
+    /// Machine-readable code of the error, e.g. `unsupported_file`
+    #[serde(default)]
+    pub code: String,
+    /// Description of the error
+    #[serde(default)]
+    pub message: String,
+}
+
+#[derive(Debug, Serialize)]
+struct AddFile<'a> {
+    file_id: &'a str,
+}
+
+impl ChatGPT {
+    /// Creates a vector store, ingesting the provided files in the background.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn create_vector_store(
+        &self,
+        request: &VectorStoreRequest,
+    ) -> crate::Result<VectorStore> {
+        let url = self.resource_url("vector_stores", &[])?;
+        self.send_beta_request(Method::POST, url, Some(request))
+            .await
+    }
+
+    /// Lists the vector stores, the most recent first.
+    ///
+    /// Vector stores created before the vector store with the id `after` are listed if it is set, up to `limit`
+    /// vector stores.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn list_vector_stores(
+        &self,
+        after: Option<&str>,
+        limit: Option<u32>,
+    ) -> crate::Result<Vec<VectorStore>> {
+        let url = self.page_url("vector_stores", &[], after, limit)?;
+        let list: List<VectorStore> = self
+            .send_beta_request(Method::GET, url, None::<&()>)
+            .await?;
+        Ok(list.data)
+    }
+
+    /// Retrieves the vector store with the provided id.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn retrieve_vector_store(&self, id: &str) -> crate::Result<VectorStore> {
+        let url = self.resource_url("vector_stores", &[id])?;
+        self.send_beta_request(Method::GET, url, None::<&()>).await
+    }
+
+    /// Deletes the vector store with the provided id. The files stay uploaded. Returns `true` if it was deleted.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn delete_vector_store(&self, id: &str) -> crate::Result<bool> {
+        let url = self.resource_url("vector_stores", &[id])?;
+        let deletion: Deletion = self
+            .send_beta_request(Method::DELETE, url, None::<&()>)
+            .await?;
+        Ok(deletion.deleted)
+    }
+
+    /// Adds an uploaded file to the vector store. The file is ingested in the background.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn add_vector_store_file(
+        &self,
+        vector_store_id: &str,
+        file_id: &str,
+    ) -> crate::Result<VectorStoreFile> {
+        let url = self.resource_url("vector_stores", &[vector_store_id, "files"])?;
+        let body = AddFile { file_id };
+        self.send_beta_request(Method::POST, url, Some(&body)).await
+    }
+
+    /// Uploads the file with the `assistants` purpose, and adds it to the vector store.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn upload_vector_store_file(
+        &self,
+        vector_store_id: &str,
+        file: FileUpload,
+    ) -> crate::Result<VectorStoreFile> {
+        let uploaded = self.upload_file(file, FilePurpose::Assistants).await?;
+        self.add_vector_store_file(vector_store_id, &uploaded.id)
+            .await
+    }
+
+    /// Lists the files of the vector store, the most recent first.
+    ///
+    /// Files added before the file with the id `after` are listed if it is set, up to `limit` files.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn list_vector_store_files(
+        &self,
+        vector_store_id: &str,
+        after: Option<&str>,
+        limit: Option<u32>,
+    ) -> crate::Result<Vec<VectorStoreFile>> {
+        let url = self.page_url("vector_stores", &[vector_store_id, "files"], after, limit)?;
+        let list: List<VectorStoreFile> = self
+            .send_beta_request(Method::GET, url, None::<&()>)
+            .await?;
+        Ok(list.data)
+    }
+
+    /// Retrieves the file of the vector store with the provided id.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn retrieve_vector_store_file(
+        &self,
+        vector_store_id: &str,
+        file_id: &str,
+    ) -> crate::Result<VectorStoreFile> {
+        let url = self.resource_url("vector_stores", &[vector_store_id, "files", file_id])?;
+        self.send_beta_request(Method::GET, url, None::<&()>).await
+    }
+
+    /// Polls the file of the vector store every `poll_interval` until its ingestion is finished, successfully or not.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn wait_for_vector_store_file(
+        &self,
+        vector_store_id: &str,
+        file_id: &str,
+        poll_interval: Duration,
+    ) -> crate::Result<VectorStoreFile> {
+        loop {
+            let file = self
+                .retrieve_vector_store_file(vector_store_id, file_id)
+                .await?;
+            if file.status != VectorStoreFileStatus::InProgress {
+                return Ok(file);
+            }
+            crate::client::sleep(poll_interval).await;
+        }
+    }
+
+    /// Polls the vector store every `poll_interval` until none of its files are being ingested.
+    ///
+    /// Requires the `vector_stores` crate feature
+    pub async fn wait_for_vector_store(
+        &self,
+        id: &str,
+        poll_interval: Duration,
+    ) -> crate::Result<VectorStore> {
+        loop {
+            let store = self.retrieve_vector_store(id).await?;
+            if store.status != VectorStoreStatus::InProgress {
+                return Ok(store);
+            }
+            crate::client::sleep(poll_interval).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::{VectorStoreFileStatus, VectorStoreRequest, VectorStoreStatus};
+    use crate::client::ChatGPT;
+    use crate::transport::JsonTransport;
+    use crate::upload::FileUpload;
+
+    #[tokio::test]
+    async fn test_vector_store_ingestion() -> crate::Result<()> {
+        // a vector store, whose file is ingested on the second poll
+        let polls = AtomicUsize::new(0);
+        let transport = JsonTransport(move |request: &reqwest::Request| {
+            let path = request.url().path();
+            if path != "/v1/files" {
+                assert_eq!(request.headers()["openai-beta"], "assistants=v2");
+            }
+            let body = request
+                .body()
+                .and_then(reqwest::Body::as_bytes)
+                .map(|body| std::str::from_utf8(body).unwrap());
+            let file = |status: &str| {
+                serde_json::json!({
+                    "id": "file-1", "object": "vector_store.file", "vector_store_id": "vs_1",
+                    "status": status, "usage_bytes": 0, "last_error": null
+                })
+            };
+            match (request.method().as_str(), path) {
+                ("POST", "/v1/files") => serde_json::json!({
+                    "id": "file-1", "filename": "manual.pdf", "purpose": "assistants"
+                }),
+                ("POST", "/v1/vector_stores") => {
+                    assert_eq!(
+                        body,
+                        Some(
+                            r#"{"name":"Manuals","expires_after":{"anchor":"last_active_at","days":7}}"#
+                        )
+                    );
+                    serde_json::json!({
+                        "id": "vs_1", "object": "vector_store", "name": "Manuals", "status": "completed",
+                        "file_counts": { "in_progress": 0, "completed": 0, "failed": 0, "cancelled": 0, "total": 0 }
+                    })
+                }
+                ("POST", "/v1/vector_stores/vs_1/files") => {
+                    assert_eq!(body, Some(r#"{"file_id":"file-1"}"#));
+                    file("in_progress")
+                }
+                ("GET", "/v1/vector_stores/vs_1/files/file-1") => {
+                    match polls.fetch_add(1, Ordering::SeqCst) {
+                        0 => file("in_progress"),
+                        _ => file("completed"),
+                    }
+                }
+                _ => panic!("Unexpected request to {path}"),
+            }
+        });
+        let client = ChatGPT::new_with_transport("", transport, Default::default())?;
+        let request = VectorStoreRequest::default()
+            .with_name("Manuals")
+            .with_expiration_days(7);
+        let store = client.create_vector_store(&request).await?;
+        assert_eq!(store.status, VectorStoreStatus::Completed);
+
+        let file = FileUpload::from_bytes(&b"%PDF"[..], "manual.pdf");
+        let file = client.upload_vector_store_file(&store.id, file).await?;
+        assert_eq!(file.status, VectorStoreFileStatus::InProgress);
+        let file = client
+            .wait_for_vector_store_file(&store.id, &file.id, std::time::Duration::from_millis(1))
+            .await?;
+        assert_eq!(file.status, VectorStoreFileStatus::Completed);
+        Ok(())
+    }
+}
@@ -0,0 +1,163 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use reqwest::IntoUrl;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+
+use crate::types::ImagePart;
+
+/// Limits applied to images before they are embedded into vision requests
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd)]
+pub struct ImageLimits {
+    /// Maximum width and height of the image in pixels. Larger images are downscaled, preserving the aspect ratio
+    pub max_dimension: u32,
+    /// Maximum size of the encoded image in bytes. Larger images are re-encoded as JPEG,
+    /// with lower quality and smaller dimensions until they fit
+    pub max_bytes: usize,
+    /// Quality used when re-encoding images as JPEG, from 1 to 100
+    pub jpeg_quality: u8,
+}
+
+impl Default for ImageLimits {
+    fn default() -> Self {
+        Self {
+            max_dimension: 2048,
+            max_bytes: 20 * 1024 * 1024,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+impl ImagePart {
+    /// Downloads the image and embeds it as a base64 `data:` URL, for images the API backend cannot access itself.
+    ///
+    /// The image is downloaded with a separate HTTP client, so the API key is never sent to the image host.
+    pub async fn fetch<U: IntoUrl>(url: U) -> crate::Result<Self> {
+        let bytes = download(url).await?;
+        let format = image::guess_format(&bytes)?;
+        Ok(Self::from_bytes(bytes, format.to_mime_type()))
+    }
+
+    /// Reads a local image file and embeds it as a base64 `data:` URL. The MIME type is detected from the file contents.
+    ///
+    /// Not available on WASM
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_path<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let bytes = tokio::fs::read(path).await?;
+        let format = image::guess_format(&bytes)?;
+        Ok(Self::from_bytes(bytes, format.to_mime_type()))
+    }
+
+    /// Reads a local image file, downscales and re-encodes it if it does not fit the limits, and embeds it as a base64 `data:` URL.
+    ///
+    /// Not available on WASM
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn from_path_with_limits<P: AsRef<Path>>(
+        path: P,
+        limits: &ImageLimits,
+    ) -> crate::Result<Self> {
+        let bytes = tokio::fs::read(path).await?;
+        Self::from_bytes_with_limits(&bytes, limits)
+    }
+
+    /// Downloads the image, downscales and re-encodes it if it does not fit the limits, and embeds it as a base64 `data:` URL.
+    pub async fn fetch_with_limits<U: IntoUrl>(
+        url: U,
+        limits: &ImageLimits,
+    ) -> crate::Result<Self> {
+        let bytes = download(url).await?;
+        Self::from_bytes_with_limits(&bytes, limits)
+    }
+
+    /// Downscales and re-encodes the raw image if it does not fit the limits, and embeds it as a base64 `data:` URL.
+    ///
+    /// The image format is detected from its contents. Re-encoded images lose transparency.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &ImageLimits) -> crate::Result<Self> {
+        let format = image::guess_format(bytes)?;
+        let image = image::load_from_memory_with_format(bytes, format)?;
+        let (width, height) = image.dimensions();
+        let oversized = width > limits.max_dimension || height > limits.max_dimension;
+        if !oversized && bytes.len() <= limits.max_bytes {
+            return Ok(Self::from_bytes(bytes, format.to_mime_type()));
+        }
+
+        let image = if oversized {
+            image.resize(
+                limits.max_dimension,
+                limits.max_dimension,
+                FilterType::Lanczos3,
+            )
+        } else {
+            image
+        };
+        Ok(Self::from_bytes(
+            encode_within_limits(image, limits)?,
+            "image/jpeg",
+        ))
+    }
+}
+
+async fn download<U: IntoUrl>(url: U) -> crate::Result<bytes::Bytes> {
+    Ok(reqwest::Client::new()
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?)
+}
+
+fn encode_within_limits(mut image: DynamicImage, limits: &ImageLimits) -> crate::Result<Vec<u8>> {
+    let mut quality = limits.jpeg_quality.clamp(1, 100);
+    loop {
+        let mut encoded = Vec::new();
+        JpegEncoder::new_with_quality(&mut encoded, quality).encode_image(&image.to_rgb8())?;
+        let (width, height) = image.dimensions();
+        if encoded.len() <= limits.max_bytes || (width <= 16 && height <= 16) {
+            return Ok(encoded);
+        }
+        if quality > 40 {
+            quality -= 15;
+        } else {
+            image = image.resize(width / 2, height / 2, FilterType::Triangle);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, ImageFormat};
+
+    use super::ImageLimits;
+    use crate::types::ImagePart;
+
+    #[test]
+    fn test_image_downscaling() -> crate::Result<()> {
+        let mut png = Vec::new();
+        DynamicImage::new_rgb8(400, 200)
+            .write_to(&mut std::io::Cursor::new(&mut png), ImageFormat::Png)?;
+
+        let untouched = ImagePart::from_bytes_with_limits(&png, &ImageLimits::default())?;
+        assert!(untouched.url.starts_with("data:image/png;base64,"));
+
+        let limits = ImageLimits {
+            max_dimension: 100,
+            ..Default::default()
+        };
+        let downscaled = ImagePart::from_bytes_with_limits(&png, &limits)?;
+        assert!(downscaled.url.starts_with("data:image/jpeg;base64,"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_image_from_path() -> crate::Result<()> {
+        // the extension is deliberately misleading, the format is detected from the contents
+        let path = std::env::temp_dir().join("chatgpt_rs_test_image.jpg");
+        DynamicImage::new_rgb8(4, 4).save_with_format(&path, ImageFormat::Png)?;
+        let image = ImagePart::from_path(&path).await;
+        std::fs::remove_file(&path)?;
+        assert!(image?.url.starts_with("data:image/png;base64,"));
+        Ok(())
+    }
+}